@@ -1,6 +1,9 @@
-/// Abstract Syntax Tree definitions for Morph
+//! Abstract Syntax Tree definitions for Morph
 
 use std::fmt;
+use crate::symbol::Symbol;
+
+pub mod visitor;
 
 /// Represents the different modes a function can be in
 #[derive(Debug, Clone, PartialEq)]
@@ -23,6 +26,7 @@ pub enum BinaryOp {
     LessEq,   // <=
     Greater,  // >
     GreaterEq,// >=
+    In,       // in
 }
 
 /// Unary operators
@@ -63,6 +67,8 @@ pub enum Pattern {
     Identifier(String),         // variable name
     Range(Box<Pattern>, Box<Pattern>), // 1..10
     Tuple(Vec<Pattern>),        // (a, b, c)
+    Record(Vec<(Symbol, Pattern)>), // { x: 0, y }
+    List(Vec<Pattern>, Option<String>), // [first, second, ..rest]
 }
 
 /// Literal values
@@ -73,7 +79,7 @@ pub enum Literal {
     String(String),
     Boolean(bool),
     List(Vec<Expression>),
-    Record(Vec<(String, Expression)>),
+    Record(Vec<(Symbol, Expression)>),
 }
 
 /// An expression node
@@ -129,7 +135,7 @@ pub enum Expression {
     /// Field access: obj.field
     FieldAccess {
         object: Box<Expression>,
-        field: String,
+        field: Symbol,
     },
     
     /// Index access: arr[index]
@@ -138,14 +144,71 @@ pub enum Expression {
         index: Box<Expression>,
     },
     
+    /// Slice: arr[start:end], where either bound may be left out
+    SliceAccess {
+        object: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
+    
     /// Lambda/closure: (params) => expr
     Lambda {
         params: Vec<Parameter>,
         body: Box<Expression>,
     },
+
+    /// Partial application: f(_, x), a function of its `_` arguments. The
+    /// callee and the other arguments are evaluated once, when it's created
+    Partial {
+        callee: Box<Expression>,
+        args: Vec<Expression>,
+    },
     
     /// Claim expression: claim expr
     Claim(Box<Expression>),
+    
+    /// Spread argument: f(...list), passing each element as an argument.
+    /// Only valid directly inside a call's argument list
+    Spread(Box<Expression>),
+
+    /// Named argument: f(name: value), bound to the parameter called `name`.
+    /// Only valid directly inside a call's argument list, after any
+    /// positional arguments
+    NamedArg {
+        name: String,
+        value: Box<Expression>,
+    },
+
+    /// Type cast: expr as Type, converting between Int, Float and String
+    Cast {
+        expr: Box<Expression>,
+        target: TypeAnnotation,
+    },
+}
+
+impl Expression {
+    /// Whether this is a `_` placeholder argument of a partial application
+    pub fn is_placeholder(&self) -> bool {
+        matches!(self, Expression::Identifier(name) if name == "_")
+    }
+
+    /// The lambda the partial application `callee(args)` stands for, each
+    /// `_` becoming a parameter: `add(_, 5)` is `($p0) => add($p0, 5)`
+    pub fn partial_lambda(callee: Expression, mut args: Vec<Expression>) -> Expression {
+        let mut params = Vec::new();
+        for arg in &mut args {
+            if arg.is_placeholder() {
+                // `$` can't appear in source identifiers, so these never clash
+                let name = format!("$p{}", params.len());
+                *arg = Expression::Identifier(name.clone());
+                params.push(Parameter { name, type_annotation: None });
+            }
+        }
+        Expression::Lambda {
+            params,
+            body: Box::new(Expression::Call { callee: Box::new(callee), args }),
+        }
+    }
 }
 
 /// A match arm: pattern => expression
@@ -162,9 +225,41 @@ pub struct Parameter {
     pub type_annotation: Option<TypeAnnotation>,
 }
 
-/// A statement
+/// A location in the source: 1-based line and column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize) -> Self {
+        Span { line, column }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// A statement and where it starts in the source
 #[derive(Debug, Clone, PartialEq)]
-pub enum Statement {
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+}
+
+/// The different kinds of statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
     /// Variable declaration: let x = expr; or var x = expr;
     VariableDecl {
         name: String,
@@ -178,6 +273,12 @@ pub enum Statement {
     
     /// Return statement
     Return(Option<Expression>),
+
+    /// Leave the innermost enclosing loop
+    Break,
+
+    /// Skip to the next iteration of the innermost enclosing loop
+    Continue,
     
     /// For loop: for item in iterable { ... }
     For {
@@ -199,9 +300,13 @@ pub enum Statement {
 pub struct FunctionDecl {
     pub mode: FunctionMode,
     pub name: String,
+    /// Generic type parameters, e.g. `T` in `proto id<T>(x: T) -> T`
+    pub type_params: Vec<String>,
     pub params: Vec<Parameter>,
     pub return_type: Option<TypeAnnotation>,
     pub body: Vec<Statement>,
+    /// Text of the `///` comments preceding the declaration
+    pub doc: Option<String>,
 }
 
 /// A type declaration
@@ -209,6 +314,8 @@ pub struct FunctionDecl {
 pub struct TypeDecl {
     pub name: String,
     pub definition: TypeDefinition,
+    /// Text of the `///` comments preceding the declaration
+    pub doc: Option<String>,
 }
 
 /// Type definition variants
@@ -231,6 +338,18 @@ pub struct SolveBlock {
     pub params: Vec<Parameter>,
     pub constraints: Vec<Constraint>,
     pub return_expr: Option<Expression>,
+    /// Text of the `///` comments preceding the block
+    pub doc: Option<String>,
+}
+
+/// A verify block: named `ensure` assertions checked by `mrc verify`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyBlock {
+    pub name: String,
+    /// `let` bindings and `ensure` assertions, in order
+    pub constraints: Vec<Constraint>,
+    /// Text of the `///` comments preceding the block
+    pub doc: Option<String>,
 }
 
 /// A constraint in a solve block
@@ -243,6 +362,23 @@ pub enum Constraint {
     },
     /// Ensure clause: ensure expr
     Ensure(Expression),
+    /// Choice over a domain: let x in expr
+    Choice {
+        name: String,
+        domain: Expression,
+    },
+    /// Objective: minimize expr / maximize expr
+    Objective {
+        kind: ObjectiveKind,
+        expr: Expression,
+    },
+}
+
+/// Direction of a solve objective
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectiveKind {
+    Min,
+    Max,
 }
 
 /// Import statement
@@ -250,6 +386,14 @@ pub enum Constraint {
 pub struct Import {
     pub module: String,
     pub items: Option<Vec<String>>, // None for "import module", Some for selective import
+    pub alias: Option<String>,      // Some for "import module as alias"
+}
+
+impl Import {
+    /// Name the module's exports are bound under
+    pub fn binding_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.module)
+    }
 }
 
 /// Top-level declaration in a module
@@ -258,7 +402,10 @@ pub enum Declaration {
     Function(FunctionDecl),
     Type(TypeDecl),
     Solve(SolveBlock),
+    Verify(VerifyBlock),
     Import(Import),
+    /// Top-level binding: a `let`/`var` statement evaluated before `main`
+    Global(Statement),
 }
 
 /// A complete module/program
@@ -275,6 +422,12 @@ impl Module {
     }
 }
 
+impl Default for Module {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Display for BinaryOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -289,6 +442,21 @@ impl fmt::Display for BinaryOp {
             BinaryOp::LessEq => write!(f, "<="),
             BinaryOp::Greater => write!(f, ">"),
             BinaryOp::GreaterEq => write!(f, ">="),
+            BinaryOp::In => write!(f, "in"),
+        }
+    }
+}
+
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |types: &[TypeAnnotation]| {
+            types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        match self {
+            TypeAnnotation::Named(name) => write!(f, "{}", name),
+            TypeAnnotation::Generic(name, params) => write!(f, "{}<{}>", name, join(params)),
+            TypeAnnotation::Function(params, ret) => write!(f, "({}) -> {}", join(params), ret),
+            TypeAnnotation::Ghost(inner, _) => write!(f, "{}", inner),
         }
     }
 }
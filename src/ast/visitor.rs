@@ -0,0 +1,190 @@
+//! Read-only AST traversal
+//!
+//! Implement [`Visitor`] and override only the nodes you care about; the
+//! default methods call the matching `walk_*` function to recurse into
+//! children. An override can call the `walk_*` function itself to continue
+//! the traversal.
+
+use super::*;
+
+/// A read-only visitor over the AST
+pub trait Visitor {
+    fn visit_declaration(&mut self, decl: &Declaration) {
+        walk_declaration(self, decl);
+    }
+
+    fn visit_function(&mut self, func: &FunctionDecl) {
+        walk_function(self, func);
+    }
+
+    fn visit_solve_block(&mut self, solve: &SolveBlock) {
+        walk_solve_block(self, solve);
+    }
+
+    fn visit_verify_block(&mut self, verify: &VerifyBlock) {
+        walk_verify_block(self, verify);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression(self, expr);
+    }
+
+    fn visit_match_arm(&mut self, arm: &MatchArm) {
+        walk_match_arm(self, arm);
+    }
+}
+
+/// Visit every declaration in a module
+pub fn walk_module<V: Visitor + ?Sized>(visitor: &mut V, module: &Module) {
+    for decl in &module.declarations {
+        visitor.visit_declaration(decl);
+    }
+}
+
+pub fn walk_declaration<V: Visitor + ?Sized>(visitor: &mut V, decl: &Declaration) {
+    match decl {
+        Declaration::Function(func) => visitor.visit_function(func),
+        Declaration::Solve(solve) => visitor.visit_solve_block(solve),
+        Declaration::Verify(verify) => visitor.visit_verify_block(verify),
+        Declaration::Global(stmt) => visitor.visit_statement(stmt),
+        Declaration::Type(_) | Declaration::Import(_) => {}
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, func: &FunctionDecl) {
+    for stmt in &func.body {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_solve_block<V: Visitor + ?Sized>(visitor: &mut V, solve: &SolveBlock) {
+    walk_constraints(visitor, &solve.constraints);
+    if let Some(ref expr) = solve.return_expr {
+        visitor.visit_expression(expr);
+    }
+}
+
+pub fn walk_verify_block<V: Visitor + ?Sized>(visitor: &mut V, verify: &VerifyBlock) {
+    walk_constraints(visitor, &verify.constraints);
+}
+
+fn walk_constraints<V: Visitor + ?Sized>(visitor: &mut V, constraints: &[Constraint]) {
+    for constraint in constraints {
+        match constraint {
+            Constraint::Binding { expr, .. }
+            | Constraint::Ensure(expr)
+            | Constraint::Choice { domain: expr, .. }
+            | Constraint::Objective { expr, .. } => {
+                visitor.visit_expression(expr);
+            }
+        }
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Statement) {
+    match &stmt.kind {
+        StatementKind::VariableDecl { initializer, .. } => visitor.visit_expression(initializer),
+        StatementKind::Expression(expr) => visitor.visit_expression(expr),
+        StatementKind::Return(expr) => {
+            if let Some(expr) = expr {
+                visitor.visit_expression(expr);
+            }
+        }
+        StatementKind::For { iterable, guard, body, .. } => {
+            visitor.visit_expression(iterable);
+            if let Some(guard) = guard {
+                visitor.visit_expression(guard);
+            }
+            for stmt in body {
+                visitor.visit_statement(stmt);
+            }
+        }
+        StatementKind::Assignment { target, value } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(value);
+        }
+        StatementKind::Break | StatementKind::Continue => {}
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Literal(lit) => match lit {
+            Literal::List(items) => {
+                for item in items {
+                    visitor.visit_expression(item);
+                }
+            }
+            Literal::Record(fields) => {
+                for (_, value) in fields {
+                    visitor.visit_expression(value);
+                }
+            }
+            _ => {}
+        },
+        Expression::Identifier(_) => {}
+        Expression::Binary { left, right, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::Unary { expr, .. } => visitor.visit_expression(expr),
+        Expression::Call { callee, args } => {
+            visitor.visit_expression(callee);
+            for arg in args {
+                visitor.visit_expression(arg);
+            }
+        }
+        Expression::Pipe { left, right } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::Match { expr, arms } => {
+            visitor.visit_expression(expr);
+            for arm in arms {
+                visitor.visit_match_arm(arm);
+            }
+        }
+        Expression::Block(stmts) => {
+            for stmt in stmts {
+                visitor.visit_statement(stmt);
+            }
+        }
+        Expression::If { condition, then_branch, else_branch } => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_expression(else_branch);
+            }
+        }
+        Expression::FieldAccess { object, .. } => visitor.visit_expression(object),
+        Expression::IndexAccess { object, index } => {
+            visitor.visit_expression(object);
+            visitor.visit_expression(index);
+        }
+        Expression::SliceAccess { object, start, end } => {
+            visitor.visit_expression(object);
+            for bound in [start, end].into_iter().flatten() {
+                visitor.visit_expression(bound);
+            }
+        }
+        Expression::Lambda { body, .. } => visitor.visit_expression(body),
+        Expression::Partial { callee, args } => {
+            visitor.visit_expression(callee);
+            for arg in args.iter().filter(|arg| !arg.is_placeholder()) {
+                visitor.visit_expression(arg);
+            }
+        }
+        Expression::Claim(expr) => visitor.visit_expression(expr),
+        Expression::Spread(expr) => visitor.visit_expression(expr),
+        Expression::NamedArg { value, .. } => visitor.visit_expression(value),
+        Expression::Cast { expr, .. } => visitor.visit_expression(expr),
+    }
+}
+
+pub fn walk_match_arm<V: Visitor + ?Sized>(visitor: &mut V, arm: &MatchArm) {
+    visitor.visit_expression(&arm.expr);
+}
@@ -0,0 +1,69 @@
+//! ANSI colors for `mrc run --pretty-errors`
+//!
+//! Colors are only used when asked for, when stderr is a terminal and when
+//! `NO_COLOR` is unset or empty (see https://no-color.org).
+
+use std::ffi::OsString;
+use std::io::IsTerminal;
+
+const RED: &str = "\x1b[31m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Styles error text, or leaves it plain when colors are off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    /// Colors if `requested` and the environment supports them
+    pub fn detect(requested: bool) -> Self {
+        Palette {
+            enabled: use_color(requested, std::io::stderr().is_terminal(), std::env::var_os("NO_COLOR")),
+        }
+    }
+
+    /// An error message, in red
+    pub fn error(&self, text: impl std::fmt::Display) -> String {
+        self.paint(RED, text)
+    }
+
+    /// A source location, in cyan
+    pub fn location(&self, text: impl std::fmt::Display) -> String {
+        self.paint(CYAN, text)
+    }
+
+    fn paint(&self, color: &str, text: impl std::fmt::Display) -> String {
+        if self.enabled {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Whether to color output, given the flag, whether it goes to a terminal
+/// and the value of `NO_COLOR`
+fn use_color(requested: bool, is_terminal: bool, no_color: Option<OsString>) -> bool {
+    requested && is_terminal && no_color.is_none_or(|value| value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_color_unless_terminal() {
+        assert!(use_color(true, true, None));
+        assert!(!use_color(true, false, None));
+        assert!(!use_color(false, true, None));
+        assert!(!use_color(true, true, Some("1".into())));
+        assert!(use_color(true, true, Some("".into())));
+
+        let plain = Palette { enabled: use_color(true, false, None) };
+        assert_eq!(plain.error("Undefined variable: x"), "Undefined variable: x");
+        let colored = Palette { enabled: true };
+        assert_eq!(colored.location("line 1, column 2"), "\x1b[36mline 1, column 2\x1b[0m");
+    }
+}
@@ -1,11 +1,29 @@
-use clap::{Parser as ClapParser, Subcommand};
-use anyhow::Result;
-use std::path::PathBuf;
+use clap::{Parser as ClapParser, Subcommand, ValueEnum};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use crate::lexer::Lexer;
-use crate::parser::Parser as MorphParser;
+use crate::parser::{ParseError, Parser as MorphParser};
 use crate::interpreter::Interpreter;
+use crate::interpreter::value::{RuntimeError, Value};
+use crate::interpreter::coverage::CoverageReport;
 use crate::types::TypeChecker;
+use crate::types::explain::explanation;
+use crate::lint::lint_module;
+use crate::doc::render_markdown;
+use crate::dot::render_dot;
+use crate::format::{check_formatting, format_source};
+use crate::diagnostic::{check_module, is_failure, Severity};
+use crate::codegen;
+use crate::purity::Purity;
+use crate::stats::Stats;
+use crate::table::render_table;
+use color::Palette;
+
+mod color;
+mod repl;
+mod watch;
 
 /// Morph Compiler CLI
 #[derive(ClapParser)]
@@ -17,6 +35,8 @@ pub struct Cli {
     pub command: Commands,
 }
 
+// Parsed once per process, so the size of `Run` doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     /// Run a Morph file dynamically (Stage 0-1)
@@ -27,6 +47,119 @@ pub enum Commands {
         /// Enable verbose output
         #[arg(short, long)]
         verbose: bool,
+        
+        /// Print every function call and return value to stderr
+        #[arg(long)]
+        trace: bool,
+        
+        /// Seed for the `random` builtins, for reproducible runs
+        #[arg(long)]
+        seed: Option<u64>,
+        
+        /// Run without side-effecting builtins: I/O, randomness, clock, environment
+        #[arg(long, alias = "no-builtins")]
+        sandbox: bool,
+        
+        /// Print the global bindings and their values after execution
+        #[arg(long)]
+        dump_env: bool,
+        
+        /// Print the inferred types of functions and `let` bindings before running
+        #[arg(long)]
+        dump_types: bool,
+        
+        /// Print call counts and time spent per function after execution
+        #[arg(long)]
+        profile: bool,
+        
+        /// Print the percentage of lines executed and the lines that never ran
+        #[arg(long)]
+        coverage: bool,
+        
+        /// Write each line's hit count and the percentage executed to this file as JSON
+        #[arg(long, value_name = "FILE")]
+        emit_coverage_json: Option<PathBuf>,
+        
+        /// Fail with an error when user function calls nest deeper than this [default: 1000]
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+        
+        /// Make arithmetic mixing Int and Float a runtime error
+        #[arg(long)]
+        strict_arithmetic: bool,
+        
+        /// Refuse to run if the function named by `--fn` (default `main`)
+        /// performs IO, directly or through the functions it calls
+        #[arg(long)]
+        deny_side_effects: bool,
+        
+        /// Function checked by `--deny-side-effects`
+        #[arg(long = "fn", value_name = "NAME", requires = "deny_side_effects")]
+        function: Option<String>,
+        
+        /// Set a variable for the `env` builtin; may be repeated
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        env_vars: Vec<(String, String)>,
+        
+        /// Flush output after every `log` and `print`, for piping to another process
+        #[arg(long)]
+        line_buffered: bool,
+        
+        /// Stop right after the first `log` or `print`, saying where it was
+        #[arg(long)]
+        halt_on_first_log: bool,
+        
+        /// Stop the program once `log` and `print` have written N lines
+        #[arg(long, value_name = "N")]
+        limit_output: Option<usize>,
+        
+        /// Fail unless the program's result has this runtime type (e.g. Int, String, List)
+        #[arg(long, value_name = "TYPE")]
+        assert_type: Option<String>,
+        
+        /// Color type and runtime errors when printing to a terminal (unless NO_COLOR is set)
+        #[arg(long)]
+        pretty_errors: bool,
+        
+        /// On a parse error, print the tokens around the one that failed
+        #[arg(long)]
+        dump_tokens_on_error: bool,
+        
+        /// Print the program's result, as plain text or as a table of records
+        #[arg(long, value_enum, value_name = "FORMAT")]
+        out_format: Option<OutFormat>,
+        
+        /// Write the program's result to this file as JSON
+        #[arg(long, value_name = "FILE")]
+        result_file: Option<PathBuf>,
+        
+        /// Write the final global bindings to this file as a JSON object, leaving out functions
+        #[arg(long, value_name = "FILE")]
+        dump_bindings_json: Option<PathBuf>,
+        
+        /// On a runtime error, open a prompt in the scope the error was raised in
+        #[arg(long)]
+        repl_on_error: bool,
+        
+        /// Report a crash inside the interpreter as an error instead of aborting
+        #[arg(long)]
+        capture_panics: bool,
+        
+        /// Re-run the file whenever it changes on disk
+        #[arg(long)]
+        watch: bool,
+        
+        /// JSON file whose value is passed to `main` instead of `--args`
+        #[arg(long, value_name = "FILE", conflicts_with = "args")]
+        json_input: Option<PathBuf>,
+        
+        /// Pass all of standard input to `main` as a String instead of `--args`
+        #[arg(long, conflicts_with_all = ["args", "json_input"])]
+        pipe_input: bool,
+        
+        /// Arguments passed to `main` as a list (Int, Float, or String)
+        #[arg(long, num_args = 0.., allow_hyphen_values = true)]
+        args: Vec<String>,
     },
     
     /// Check stability scores for a Morph file
@@ -35,6 +168,12 @@ pub enum Commands {
         file: PathBuf,
     },
     
+    /// Print function counts, sizes and stability for a file or a directory
+    Stats {
+        /// Morph source file, or a directory searched for `.morph` files
+        path: PathBuf,
+    },
+    
     /// Compile a Morph file to native binary (Stage 3)
     Harden {
         /// Path to the Morph source file
@@ -43,6 +182,10 @@ pub enum Commands {
         /// Output file path
         #[arg(short, long)]
         output: Option<PathBuf>,
+        
+        /// Print the generated Cranelift IR instead of building a binary
+        #[arg(long, conflicts_with = "output")]
+        emit_ir: bool,
     },
     
     /// Build and package solid fragments
@@ -62,20 +205,104 @@ pub enum Commands {
     Parse {
         /// Path to the Morph source file
         file: PathBuf,
+
+        /// Print the AST as a Graphviz DOT graph instead
+        #[arg(long)]
+        dot: bool,
     },
+    
+    /// Format a Morph file in place
+    Fmt {
+        /// Path to the Morph source file
+        file: PathBuf,
+        
+        /// Don't write the file; print a diff and fail if it isn't formatted
+        #[arg(long)]
+        check: bool,
+    },
+    
+    /// Type check and lint a file without running it
+    Check {
+        /// Path to the Morph source file
+        #[arg(required_unless_present = "explain")]
+        file: Option<PathBuf>,
+        
+        /// Treat warnings as errors
+        #[arg(long)]
+        strict: bool,
+        
+        /// Print a longer explanation of an error code, like E0001, instead of checking a file
+        #[arg(long, value_name = "CODE", conflicts_with_all = ["file", "strict"])]
+        explain: Option<String>,
+    },
+    
+    /// Run the `ensure` assertions in every `verify` block
+    Verify {
+        /// Path to the Morph source file
+        file: PathBuf,
+    },
+    
+    /// Report unused variables and parameters
+    Lint {
+        /// Path to the Morph source file
+        file: PathBuf,
+        
+        /// Exit with an error if there are any warnings
+        #[arg(long)]
+        strict: bool,
+    },
+    
+    /// Print function signatures and doc comments as Markdown
+    Doc {
+        /// Path to the Morph source file
+        file: PathBuf,
+    },
+}
+
+/// How `run --out-format` prints the result
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutFormat {
+    /// The value as `log` would print it
+    Text,
+    /// An aligned table if the value is a list of records, else as text
+    Table,
 }
 
 /// Execute the CLI command
 pub fn execute(cli: Cli) -> Result<()> {
     match cli.command {
-        Commands::Run { file, verbose } => {
-            run_file(file, verbose)
+        Commands::Run {
+            file, verbose, trace, seed, sandbox, dump_env, dump_types, profile, coverage, emit_coverage_json, max_depth,
+            strict_arithmetic, deny_side_effects, function, env_vars, line_buffered, halt_on_first_log, limit_output, pretty_errors,
+            dump_tokens_on_error, assert_type, out_format, result_file, dump_bindings_json, repl_on_error, capture_panics, watch, json_input,
+            pipe_input, args,
+        } => {
+            let pure_function = deny_side_effects.then(|| function.unwrap_or_else(|| "main".to_string()));
+            let options = RunOptions {
+                verbose, trace, seed, sandbox, dump_env, dump_types, profile, coverage, emit_coverage_json, max_depth,
+                strict_arithmetic, pure_function, env_vars, line_buffered, halt_on_first_log, limit_output, pretty_errors,
+                dump_tokens_on_error, assert_type, out_format, result_file, dump_bindings_json, repl_on_error, capture_panics,
+                json_input, pipe_input,
+            };
+            if watch {
+                watch_file(file, options, args);
+                Ok(())
+            } else {
+                run_file(file, options, args)
+            }
         }
         Commands::Status { file } => {
             check_status(file)
         }
-        Commands::Harden { file, output } => {
-            harden_file(file, output)
+        Commands::Stats { path } => {
+            stats_path(path)
+        }
+        Commands::Harden { file, output, emit_ir } => {
+            if emit_ir {
+                emit_ir_file(file)
+            } else {
+                harden_file(file, output)
+            }
         }
         Commands::Build { release } => {
             build_project(release)
@@ -83,19 +310,137 @@ pub fn execute(cli: Cli) -> Result<()> {
         Commands::Tokenize { file } => {
             tokenize_file(file)
         }
-        Commands::Parse { file } => {
-            parse_file(file)
+        Commands::Parse { file, dot } => {
+            parse_file(file, dot)
+        }
+        Commands::Fmt { file, check } => {
+            fmt_file(file, check)
+        }
+        Commands::Check { file, strict, explain } => {
+            match (explain, file) {
+                (Some(code), _) => explain_code(&code),
+                (None, Some(file)) => check_file(file, strict),
+                (None, None) => unreachable!("clap requires a file without --explain"),
+            }
+        }
+        Commands::Verify { file } => {
+            verify_file(file)
         }
+        Commands::Lint { file, strict } => {
+            lint_file(file, strict)
+        }
+        Commands::Doc { file } => {
+            doc_file(file)
+        }
+    }
+}
+
+/// Tokens shown either side of the failing one by `--dump-tokens-on-error`
+const TOKEN_WINDOW: usize = 5;
+
+/// Flags controlling how `run` executes a file
+#[derive(Clone, Default)]
+struct RunOptions {
+    verbose: bool,
+    trace: bool,
+    seed: Option<u64>,
+    sandbox: bool,
+    dump_env: bool,
+    dump_types: bool,
+    profile: bool,
+    coverage: bool,
+    /// File to write the coverage report to as JSON
+    emit_coverage_json: Option<PathBuf>,
+    max_depth: Option<usize>,
+    strict_arithmetic: bool,
+    /// Function that must be free of side effects to run at all
+    pure_function: Option<String>,
+    env_vars: Vec<(String, String)>,
+    line_buffered: bool,
+    halt_on_first_log: bool,
+    /// Most lines of output before the program is stopped
+    limit_output: Option<usize>,
+    pretty_errors: bool,
+    dump_tokens_on_error: bool,
+    /// Runtime type name the result must have
+    assert_type: Option<String>,
+    out_format: Option<OutFormat>,
+    result_file: Option<PathBuf>,
+    dump_bindings_json: Option<PathBuf>,
+    repl_on_error: bool,
+    /// Turn a panic during the run into an error
+    capture_panics: bool,
+    json_input: Option<PathBuf>,
+    pipe_input: bool,
+}
+
+/// Parse a `--env KEY=VALUE` argument
+fn parse_env_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got `{}`", arg)),
     }
 }
 
 /// Run a Morph file (Stage 0: Draft mode)
-fn run_file(file: PathBuf, verbose: bool) -> Result<()> {
+fn run_file(file: PathBuf, options: RunOptions, args: Vec<String>) -> Result<()> {
+    if !run_once(&file, &options, &args)? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Run a Morph file now and again every time it changes, until interrupted
+fn watch_file(file: PathBuf, options: RunOptions, args: Vec<String>) {
+    let mut first = true;
+    watch::watch(&file, Duration::from_millis(200), None, || {
+        if !first {
+            println!("\n--- {} changed, re-running ---\n", file.display());
+        }
+        first = false;
+        if let Err(e) = run_once(&file, &options, &args) {
+            eprintln!("Error: {}", e);
+        }
+    });
+}
+
+/// Run a Morph file once, returning whether it type checked and ran
+/// without a runtime error (those errors are printed here). With
+/// `capture_panics`, a panic anywhere in the run is returned as an error
+fn run_once(file: &Path, options: &RunOptions, args: &[String]) -> Result<bool> {
+    if !options.capture_panics {
+        return run_pipeline(file, options, args);
+    }
+    // The panic comes back as an error, so the default hook mustn't also
+    // print it with a backtrace
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let run = std::panic::AssertUnwindSafe(|| run_pipeline(file, options, args));
+    let result = std::panic::catch_unwind(run);
+    std::panic::set_hook(hook);
+    result.unwrap_or_else(|panic| {
+        let message = panic.downcast_ref::<&str>().copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("no message");
+        bail!("Internal error: the interpreter panicked: {}", message)
+    })
+}
+
+/// Everything `run_once` does, from reading `file` to printing the result
+fn run_pipeline(file: &Path, options: &RunOptions, args: &[String]) -> Result<bool> {
+    let RunOptions {
+        verbose, trace, seed, sandbox, dump_env, dump_types, profile, coverage, ref emit_coverage_json, max_depth,
+        strict_arithmetic, ref pure_function,
+        ref env_vars, line_buffered, halt_on_first_log, limit_output, pretty_errors, dump_tokens_on_error,
+        ref assert_type, out_format, ref result_file, ref dump_bindings_json, repl_on_error,
+        capture_panics: _, ref json_input, pipe_input,
+    } = *options;
     if verbose {
         println!("Running Morph file: {}", file.display());
     }
     
-    let source = std::fs::read_to_string(&file)?;
+    let source = std::fs::read_to_string(file)?;
+    let palette = Palette::detect(pretty_errors);
     
     // Stage 0: Draft - Tree-walk interpretation
     if verbose {
@@ -112,7 +457,15 @@ fn run_file(file: PathBuf, verbose: bool) -> Result<()> {
     
     // Parse
     let mut parser = MorphParser::new(tokens);
-    let ast = parser.parse()?;
+    let ast = match parser.parse() {
+        Ok(ast) => ast,
+        Err(e) => {
+            if let (true, Some(error)) = (dump_tokens_on_error, e.downcast_ref::<ParseError>()) {
+                eprint!("{}", error.token_window(parser.tokens(), TOKEN_WINDOW));
+            }
+            return Err(e);
+        }
+    };
     
     if verbose {
         println!("  Parsed {} declarations", ast.declarations.len());
@@ -125,32 +478,167 @@ fn run_file(file: PathBuf, verbose: bool) -> Result<()> {
             if verbose {
                 println!("  Type checking passed");
             }
+            if dump_types {
+                for (name, ty) in type_checker.inferred_types() {
+                    println!("{}: {}", name, ty);
+                }
+            }
         }
         Err(errors) => {
             eprintln!("Type errors:");
             for error in &errors {
-                eprintln!("  - {}", error);
+                match error.span {
+                    Some(span) => eprintln!(
+                        "  - {}: [{}] {}", palette.location(span), error.error.code(), palette.error(&error.error)
+                    ),
+                    None => eprintln!("  - [{}] {}", error.error.code(), palette.error(&error.error)),
+                }
             }
-            std::process::exit(1);
+            return Ok(false);
+        }
+    }
+    
+    if let Some(name) = pure_function {
+        let purity = Purity::new(&ast);
+        if !purity.contains(name) {
+            bail!("No function named `{}` to check for side effects", name);
+        }
+        if let Some(path) = purity.side_effect_path(name) {
+            eprintln!("Side effect in `{}`: {}", name, path.join(" -> "));
+            return Ok(false);
         }
     }
     
     // Execute with interpreter
-    let mut interpreter = Interpreter::new();
-    match interpreter.interpret(&ast) {
+    let mut interpreter = if sandbox {
+        Interpreter::new_sandboxed()
+    } else {
+        Interpreter::new()
+    };
+    if trace {
+        interpreter.set_trace(Box::new(std::io::stderr()));
+    }
+    if let Some(seed) = seed {
+        interpreter.set_seed(seed);
+    }
+    if profile {
+        interpreter.enable_profile();
+    }
+    if coverage || emit_coverage_json.is_some() {
+        interpreter.enable_coverage();
+    }
+    if repl_on_error {
+        interpreter.enable_error_scope();
+    }
+    if let Some(max_depth) = max_depth {
+        interpreter.set_max_depth(max_depth);
+    }
+    interpreter.set_strict_arithmetic(strict_arithmetic);
+    interpreter.set_line_buffered(line_buffered);
+    interpreter.set_halt_on_first_log(halt_on_first_log);
+    if let Some(limit) = limit_output {
+        interpreter.set_output_limit(limit);
+    }
+    for (key, value) in env_vars {
+        interpreter.set_env_var(key.clone(), value.clone());
+    }
+    let input = match json_input {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)?;
+            let json = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+            Value::from_json(json)
+        }
+        // Read through the interpreter, so `stdin()` sees the same text
+        None if pipe_input => Value::String(interpreter.read_input()?),
+        None => Value::list(args.iter().map(|arg| Value::from_arg(arg)).collect()),
+    };
+    match interpreter.interpret_with_input(&ast, input) {
         Ok(result) => {
             if verbose {
                 println!("  Result: {}", result);
             }
+            match out_format {
+                Some(OutFormat::Table) => match render_table(&result) {
+                    Some(table) => print!("{}", table),
+                    None => println!("{}", result),
+                },
+                Some(OutFormat::Text) => println!("{}", result),
+                None => {}
+            }
+            if dump_env {
+                for (name, value) in interpreter.global_bindings() {
+                    println!("{} = {}", name, value);
+                }
+            }
+            if profile {
+                print_profile(&interpreter);
+            }
+            if let Some(report) = interpreter.coverage_report(&ast) {
+                if coverage {
+                    print_coverage(&report);
+                }
+                if let Some(path) = emit_coverage_json {
+                    write_json(path, &report.to_json(), "coverage")?;
+                }
+            }
+            if let Some(path) = result_file {
+                write_json(path, &result.to_json()?, "result")?;
+            }
+            if let Some(path) = dump_bindings_json {
+                write_json(path, &interpreter.bindings_json(), "bindings")?;
+            }
+            if let Some(expected) = assert_type {
+                if result.type_name() != expected {
+                    eprintln!("Result type mismatch: expected {}, got {} ({})", expected, result.type_name(), result);
+                    return Ok(false);
+                }
+            }
             println!("Execution complete");
+            Ok(true)
+        }
+        // Stopping is what was asked for, so it isn't a failure
+        Err(e @ RuntimeError::Halted(_)) => {
+            eprintln!("{}", e);
+            Ok(true)
         }
         Err(e) => {
-            eprintln!("Runtime error: {}", e);
-            std::process::exit(1);
+            eprintln!("Runtime error: {}", palette.error(&e));
+            if repl_on_error && interpreter.enter_error_scope() {
+                eprintln!("Variables at the error are in scope; :quit to leave");
+                repl::repl(&mut interpreter, std::io::stdin().lock(), &mut std::io::stdout())?;
+            }
+            Ok(false)
         }
     }
-    
-    Ok(())
+}
+
+/// Write `json` to `path`, pretty-printed; `what` names it in errors
+fn write_json(path: &Path, json: &serde_json::Value, what: &str) -> Result<()> {
+    let mut text = serde_json::to_string_pretty(json)?;
+    text.push('\n');
+    std::fs::write(path, text)
+        .with_context(|| format!("Failed to write {} to {}", what, path.display()))
+}
+
+/// Print the per-function profile collected during a run
+fn print_profile(interpreter: &Interpreter) {
+    println!("{:<24} {:>10} {:>14}", "function", "calls", "total (ms)");
+    for (name, profile) in interpreter.profile_report() {
+        println!(
+            "{:<24} {:>10} {:>14.3}",
+            name, profile.calls, profile.total.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+/// Print the share of lines executed and list the ones that never ran
+fn print_coverage(report: &CoverageReport) {
+    println!("coverage: {:.1}% of {} lines", report.percent(), report.lines.len());
+    if !report.missed.is_empty() {
+        let missed: Vec<String> = report.missed.iter().map(|line| line.to_string()).collect();
+        println!("unexecuted lines: {}", missed.join(", "));
+    }
 }
 
 /// Check stability scores for a file
@@ -174,6 +662,58 @@ fn check_status(file: PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Print metrics summed over a file or every Morph file under a directory
+fn stats_path(path: PathBuf) -> Result<()> {
+    let files = if path.is_dir() {
+        let mut files = Vec::new();
+        collect_morph_files(&path, &mut files)?;
+        files.sort();
+        files
+    } else {
+        vec![path.clone()]
+    };
+    if files.is_empty() {
+        bail!("No .morph files in {}", path.display());
+    }
+    
+    let mut stats = Stats::default();
+    for file in &files {
+        let source = std::fs::read_to_string(file)?;
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize().with_context(|| file.display().to_string())?;
+        let mut parser = MorphParser::new(tokens);
+        let ast = parser.parse().with_context(|| file.display().to_string())?;
+        stats.add_module(&source, &ast);
+    }
+    print!("{}", stats.report());
+    Ok(())
+}
+
+/// Add the `.morph` files under `dir`, at any depth, to `files`
+fn collect_morph_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_morph_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "morph") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Print the Cranelift IR of a file's solid functions
+fn emit_ir_file(file: PathBuf) -> Result<()> {
+    let source = std::fs::read_to_string(&file)?;
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize()?;
+    let mut parser = MorphParser::new(tokens);
+    let ast = parser.parse()?;
+    
+    print!("{}", codegen::emit_ir(&ast)?);
+    Ok(())
+}
+
 /// Compile to native binary (Stage 3: Solid mode)
 fn harden_file(file: PathBuf, output: Option<PathBuf>) -> Result<()> {
     let output_path = output.unwrap_or_else(|| {
@@ -190,12 +730,33 @@ fn harden_file(file: PathBuf, output: Option<PathBuf>) -> Result<()> {
     let mut parser = MorphParser::new(tokens);
     let ast = parser.parse()?;
     
-    println!("Stage 3: Solid (LLVM Native Binary)");
+    println!("Stage 3: Solid (Cranelift Native Code)");
     println!("  Parsed {} declarations", ast.declarations.len());
     
-    // TODO: Implement LLVM backend for Stage 3
-    println!("Native compilation not yet implemented");
-    println!("AST structure validated successfully");
+    let name = output_path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "morph".to_string());
+    let object = codegen::compile_object(&ast, &name)?;
+    
+    let object_path = output_path.with_extension("o");
+    std::fs::write(&object_path, &object.bytes)?;
+    println!("  Wrote object file {}", object_path.display());
+    
+    if !object.has_entry {
+        println!("  No parameterless solid `main`; skipping link step");
+        return Ok(());
+    }
+    
+    let status = std::process::Command::new("cc")
+        .arg(&object_path)
+        .arg("-o")
+        .arg(&output_path)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to run linker `cc`: {}", e))?;
+    if !status.success() {
+        anyhow::bail!("Linking {} failed ({})", output_path.display(), status);
+    }
+    println!("  Linked {}", output_path.display());
     
     Ok(())
 }
@@ -231,17 +792,267 @@ fn tokenize_file(file: PathBuf) -> Result<()> {
 }
 
 /// Parse a file and print AST
-fn parse_file(file: PathBuf) -> Result<()> {
+fn parse_file(file: PathBuf, dot: bool) -> Result<()> {
+    let source = std::fs::read_to_string(&file)?;
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize()?;
+    let mut parser = MorphParser::new(tokens);
+    let ast = parser.parse()?;
+
+    // DOT output goes straight to stdout so it can be piped into `dot`
+    if dot {
+        print!("{}", render_dot(&ast));
+        return Ok(());
+    }
+
     println!("Parsing: {}", file.display());
     println!("{}", "=".repeat(60));
+    println!("{:#?}", ast);
     
+    Ok(())
+}
+
+/// Lint a file and print warnings
+fn lint_file(file: PathBuf, strict: bool) -> Result<()> {
     let source = std::fs::read_to_string(&file)?;
     let mut lexer = Lexer::new(&source);
     let tokens = lexer.tokenize()?;
     let mut parser = MorphParser::new(tokens);
     let ast = parser.parse()?;
     
-    println!("{:#?}", ast);
+    let warnings = lint_module(&ast);
+    for warning in &warnings {
+        println!("warning: {}", warning);
+    }
+    println!("{}: {} warning(s)", file.display(), warnings.len());
+    
+    if strict && !warnings.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Format a file in place, or with `check` report whether it needs it
+fn fmt_file(file: PathBuf, check: bool) -> Result<()> {
+    let source = std::fs::read_to_string(&file)?;
+    
+    if check {
+        if let Some(diff) = check_formatting(&file.display().to_string(), &source) {
+            println!("{} is not formatted", file.display());
+            print!("{}", diff);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
+    let formatted = format_source(&source);
+    if formatted != source {
+        std::fs::write(&file, formatted)?;
+        println!("Formatted {}", file.display());
+    }
+    Ok(())
+}
+
+/// Type check and lint a file, failing on errors (or warnings if `strict`)
+fn check_file(file: PathBuf, strict: bool) -> Result<()> {
+    let source = std::fs::read_to_string(&file)?;
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize()?;
+    let mut parser = MorphParser::new(tokens);
+    let ast = parser.parse()?;
+    
+    let diagnostics = check_module(&ast);
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    println!(
+        "{}: {} error(s), {} warning(s)",
+        file.display(), errors, diagnostics.len() - errors
+    );
     
+    if is_failure(&diagnostics, strict) {
+        std::process::exit(1);
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Print the explanation of a type error code
+fn explain_code(code: &str) -> Result<()> {
+    match explanation(code) {
+        Some(text) => {
+            println!("{}\n\n{}", code.to_ascii_uppercase(), text);
+            Ok(())
+        }
+        None => bail!("Unknown error code: {}", code),
+    }
+}
+
+/// Run a file's verify blocks, failing if any `ensure` doesn't hold
+fn verify_file(file: PathBuf) -> Result<()> {
+    let source = std::fs::read_to_string(&file)?;
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize()?;
+    let mut parser = MorphParser::new(tokens);
+    let ast = parser.parse()?;
+    
+    if let Err(errors) = TypeChecker::new().check_module(&ast) {
+        eprintln!("Type errors:");
+        for error in &errors {
+            eprintln!("  - {}", error);
+        }
+        std::process::exit(1);
+    }
+    
+    let outcomes = match Interpreter::new().verify(&ast) {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            eprintln!("Runtime error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let (mut passed, mut failed) = (0, 0);
+    for outcome in &outcomes {
+        for (i, held) in outcome.passed.iter().enumerate() {
+            let status = if *held { "ok" } else { "FAILED" };
+            println!("{} {}: ensure #{}", status, outcome.name, i + 1);
+            if *held {
+                passed += 1;
+            } else {
+                failed += 1;
+            }
+        }
+    }
+    println!("{}: {} passed, {} failed", file.display(), passed, failed);
+    
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Print a file's documentation as Markdown
+fn doc_file(file: PathBuf) -> Result<()> {
+    let source = std::fs::read_to_string(&file)?;
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize()?;
+    let mut parser = MorphParser::new(tokens);
+    let ast = parser.parse()?;
+    
+    print!("{}", render_markdown(&ast));
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file in the temp dir, deleted when dropped even if the test fails
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!("morph_{}_{}", std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Write `source` to a temp file called `name` and run it
+    fn run_source(name: &str, source: &str, options: &RunOptions) -> Result<bool> {
+        let file = TempFile::new(name);
+        std::fs::write(&file.0, source).unwrap();
+        run_once(&file.0, options, &[])
+    }
+
+    #[test]
+    fn test_assert_type() {
+        let assert_type = |name: &str| {
+            let options = RunOptions { assert_type: Some(name.to_string()), ..RunOptions::default() };
+            run_source("assert_type.morph", r#"proto main() { return "seven" }"#, &options).unwrap()
+        };
+
+        assert!(!assert_type("Int"));
+        assert!(assert_type("String"));
+    }
+
+    #[test]
+    fn test_result_file() {
+        let result = TempFile::new("result.json");
+        let source = r#"proto main() { return { name: "morph", sizes: [1, 2.5] } }"#;
+
+        let options = RunOptions { result_file: Some(result.0.clone()), ..RunOptions::default() };
+        assert!(run_source("result.morph", source, &options).unwrap());
+        let written = std::fs::read_to_string(&result.0).unwrap();
+        assert_eq!(written, "{\n  \"name\": \"morph\",\n  \"sizes\": [\n    1,\n    2.5\n  ]\n}\n");
+    }
+
+    #[test]
+    fn test_push_returns_the_list() {
+        let result = TempFile::new("push.json");
+        let source = "proto main() {\n    let xs: List<Int> = [1]\n    let ys = push(xs, 2)\n    let first: Int = push([], 3)[0]\n    return [len(xs), len(ys), ys[1], first]\n}\n";
+
+        // The checker and the interpreter agree on what push returns
+        let options = RunOptions { result_file: Some(result.0.clone()), ..RunOptions::default() };
+        assert!(run_source("push.morph", source, &options).unwrap());
+        let written = std::fs::read_to_string(&result.0).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(written, serde_json::json!([1, 2, 2, 3]));
+    }
+
+    #[test]
+    fn test_widened_numbers_are_floats() {
+        let result = TempFile::new("widen.json");
+        let source = r#"
+            proto pick(b: Bool) {
+                if b {
+                    return 1
+                }
+                return 2.5
+            }
+
+            proto half(x: Float) -> Float {
+                return x / 2
+            }
+
+            proto main() {
+                let x: Float = pick(true)
+                return [x / 2, half(3), pick(false)]
+            }
+        "#;
+
+        // Ints the checker widened to Float divide as Floats when run
+        let options = RunOptions { result_file: Some(result.0.clone()), ..RunOptions::default() };
+        assert!(run_source("widen.morph", source, &options).unwrap());
+        let written = std::fs::read_to_string(&result.0).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(written, serde_json::json!([0.5, 1.5, 2.5]));
+    }
+
+    #[test]
+    fn test_dump_bindings_json() {
+        let bindings = TempFile::new("bindings.json");
+        let source = "import math\n\nlet limit = 3 * 4\nlet names = [\"a\", \"b\"]\n\nproto main() {\n    return limit\n}\n";
+
+        let options = RunOptions { dump_bindings_json: Some(bindings.0.clone()), ..RunOptions::default() };
+        assert!(run_source("bindings.morph", source, &options).unwrap());
+        let written = std::fs::read_to_string(&bindings.0).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(written, serde_json::json!({ "limit": 12, "names": ["a", "b"] }));
+    }
+
+    #[test]
+    fn test_capture_panics() {
+        // Dividing the smallest Int by -1 overflows, which panics in Rust
+        let source = "proto main() {\n    let min = -9223372036854775807 - 1\n    return min / -1\n}\n";
+
+        let options = RunOptions { capture_panics: true, ..RunOptions::default() };
+        let error = run_source("panic.morph", source, &options).unwrap_err();
+        assert_eq!(error.to_string(), "Internal error: the interpreter panicked: attempt to divide with overflow");
+    }
+}
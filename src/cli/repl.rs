@@ -0,0 +1,85 @@
+//! Interactive prompt for `mrc run --repl-on-error`
+//!
+//! Each line is parsed as one or more statements and run in the
+//! interpreter's current scope; the value of the last one is printed
+//! unless it is Unit. Assigning to a name that doesn't exist defines it.
+//! `:quit` or end of input leaves the prompt.
+
+use std::io::{self, BufRead, Write};
+
+use crate::interpreter::Interpreter;
+use crate::interpreter::value::Value;
+use crate::lexer::Lexer;
+use crate::parser::Parser as MorphParser;
+
+/// Read and run lines from `input` until it ends, writing prompts, results
+/// and errors to `output`
+pub fn repl(interpreter: &mut Interpreter, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut lines = input.lines();
+    loop {
+        write!(output, "morph> ")?;
+        output.flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            writeln!(output)?;
+            return Ok(());
+        };
+        let line = line.trim();
+        if line == ":quit" {
+            return Ok(());
+        }
+
+        let statements = Lexer::new(line).tokenize()
+            .and_then(|tokens| MorphParser::new(tokens).parse_statements());
+        match statements {
+            Ok(statements) => match interpreter.eval_statements(&statements) {
+                Ok(Value::Unit) => {}
+                Ok(value) => writeln!(output, "{}", value)?,
+                Err(e) => writeln!(output, "Runtime error: {}", e)?,
+            },
+            Err(e) => writeln!(output, "Error: {}", e)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl_reads_variables_after_error() {
+        let source = r#"
+            proto main() {
+                let total = 40
+                let items = [1, 2]
+                return items[total]
+            }
+        "#;
+        let mut parser = MorphParser::new(Lexer::new(source).tokenize().unwrap());
+        let module = parser.parse().unwrap();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.enable_error_scope();
+        assert!(interpreter.interpret(&module).is_err());
+        assert!(interpreter.enter_error_scope());
+
+        let mut output = Vec::new();
+        repl(&mut interpreter, "total + 2\nlen(items)\nmissing\n:quit\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "morph> 42\nmorph> 2\nmorph> Runtime error: Undefined variable: missing\nmorph> ",
+        );
+    }
+
+    #[test]
+    fn test_repl_assignment_defines_variables() {
+        let mut interpreter = Interpreter::new();
+        let mut output = Vec::new();
+        let input = "x = 5\nx\nx = x + 1\nx\nlet y = 1\ny = 2\n";
+        repl(&mut interpreter, input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "morph> morph> 5\nmorph> morph> 6\nmorph> morph> \
+             Runtime error: Invalid operation: Cannot assign to y, which is declared with let\nmorph> \n",
+        );
+    }
+}
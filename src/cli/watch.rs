@@ -0,0 +1,61 @@
+//! Polling file watcher for `mrc run --watch`
+
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Last modification time of `path`, or `None` if it can't be read
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Call `run` now, then again each time the modification time of `path`
+/// changes, checking every `interval`. Stops after `max_runs` runs if given,
+/// otherwise loops forever.
+pub fn watch(path: &Path, interval: Duration, max_runs: Option<usize>, mut run: impl FnMut()) {
+    let mut last = modified_time(path);
+    let mut runs = 0;
+    loop {
+        run();
+        runs += 1;
+        if max_runs.is_some_and(|max| runs >= max) {
+            return;
+        }
+
+        loop {
+            thread::sleep(interval);
+            let current = modified_time(path);
+            if current != last {
+                last = current;
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_rerun_on_change() {
+        let path = std::env::temp_dir().join(format!("morph_watch_{}.morph", std::process::id()));
+        std::fs::write(&path, "proto main() { return 1 }").unwrap();
+
+        // Touch the file once the first run has happened
+        let touched = path.clone();
+        let toucher = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let file = File::options().write(true).open(&touched).unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        });
+
+        let mut runs = 0;
+        watch(&path, Duration::from_millis(10), Some(2), || runs += 1);
+        toucher.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(runs, 2);
+    }
+}
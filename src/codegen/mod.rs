@@ -0,0 +1,366 @@
+//! Native code generation for `solid` functions (Stage 3: Solid)
+//!
+//! Lowers the integer subset of Morph to machine code with Cranelift:
+//! `Int`/`Bool` parameters and results, arithmetic and comparisons, `let`,
+//! `return`, `if` and calls between solid functions. Every value is a
+//! 64-bit integer; booleans are `0`/`1`. Arithmetic wraps on overflow, and
+//! division and remainder by zero give `0` rather than trapping. Anything
+//! outside the subset is rejected with [`CodegenError::Unsupported`].
+
+pub mod translate;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, UserFuncName};
+use cranelift_codegen::isa::OwnedTargetIsa;
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module as Backend};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+
+use crate::ast::{Declaration, FunctionDecl, FunctionMode, Module, TypeAnnotation};
+use translate::{translate_function, CompiledFunction};
+
+/// Code generation errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodegenError {
+    /// The program uses a construct outside the native subset
+    Unsupported(String),
+    /// Cranelift failed to build, verify, or emit code
+    Backend(String),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::Unsupported(msg) => write!(f, "Unsupported in solid mode: {}", msg),
+            CodegenError::Backend(msg) => write!(f, "Code generation failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+fn backend_error(e: impl fmt::Display) -> CodegenError {
+    CodegenError::Backend(e.to_string())
+}
+
+/// Symbol a compiled Morph function is exported under in object files
+pub fn symbol_name(name: &str) -> String {
+    format!("morph_{}", name)
+}
+
+/// Solid functions compiled in memory and ready to call
+pub struct JitProgram {
+    module: JITModule,
+    functions: HashMap<String, CompiledFunction>,
+}
+
+impl JitProgram {
+    /// Call a compiled function with integer arguments
+    pub fn call(&self, name: &str, args: &[i64]) -> Result<i64, CodegenError> {
+        let func = self.functions.get(name)
+            .ok_or_else(|| CodegenError::Unsupported(format!("no solid function named `{}`", name)))?;
+        if func.arity != args.len() {
+            return Err(CodegenError::Unsupported(format!(
+                "`{}` expects {} arguments, got {}", name, func.arity, args.len()
+            )));
+        }
+
+        let ptr = self.module.get_finalized_function(func.id);
+        // SAFETY: every compiled function uses the host's default calling
+        // convention with `arity` i64 parameters and one i64 result, which
+        // matches the `extern "C"` signatures below.
+        unsafe {
+            match *args {
+                [] => Ok(std::mem::transmute::<*const u8, extern "C" fn() -> i64>(ptr)()),
+                [a] => Ok(std::mem::transmute::<*const u8, extern "C" fn(i64) -> i64>(ptr)(a)),
+                [a, b] => Ok(std::mem::transmute::<*const u8, extern "C" fn(i64, i64) -> i64>(ptr)(a, b)),
+                [a, b, c] => Ok(std::mem::transmute::<*const u8, extern "C" fn(i64, i64, i64) -> i64>(ptr)(a, b, c)),
+                [a, b, c, d] => Ok(std::mem::transmute::<*const u8, extern "C" fn(i64, i64, i64, i64) -> i64>(ptr)(a, b, c, d)),
+                _ => Err(CodegenError::Unsupported(
+                    "calling compiled functions with more than 4 arguments".to_string()
+                )),
+            }
+        }
+    }
+}
+
+/// Compile every solid function in a module for in-process execution
+pub fn compile_jit(module: &Module) -> Result<JitProgram, CodegenError> {
+    let isa = host_isa(false)?;
+    let mut backend = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+    let functions = lower_module(&mut backend, module, Linkage::Local, None)?;
+    backend.finalize_definitions().map_err(backend_error)?;
+
+    Ok(JitProgram { module: backend, functions })
+}
+
+/// Compile every solid function in a module to a relocatable object file.
+///
+/// If the module has a parameterless solid `main`, a C `main` entry point
+/// calling it is generated too, so the object can be linked into a binary
+/// whose exit code is `main`'s result.
+pub fn compile_object(module: &Module, name: &str) -> Result<ObjectOutput, CodegenError> {
+    let isa = host_isa(true)?;
+    let builder = ObjectBuilder::new(isa, name, default_libcall_names()).map_err(backend_error)?;
+    let mut backend = ObjectModule::new(builder);
+    let functions = lower_module(&mut backend, module, Linkage::Export, None)?;
+
+    let has_entry = match functions.get("main") {
+        Some(main) if main.arity == 0 => {
+            define_entry_point(&mut backend, main.id)?;
+            true
+        }
+        _ => false,
+    };
+
+    let bytes = backend.finish().emit().map_err(backend_error)?;
+    Ok(ObjectOutput { bytes, has_entry })
+}
+
+/// Textual Cranelift IR of every solid function in a module, as generated
+/// before optimization: one instruction per line, grouped into labelled
+/// blocks
+pub fn emit_ir(module: &Module) -> Result<String, CodegenError> {
+    let isa = host_isa(false)?;
+    let mut backend = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+    let mut ir = String::new();
+    lower_module(&mut backend, module, Linkage::Local, Some(&mut ir))?;
+    Ok(ir)
+}
+
+/// A compiled object file
+pub struct ObjectOutput {
+    pub bytes: Vec<u8>,
+    /// Whether a C `main` entry point was generated
+    pub has_entry: bool,
+}
+
+/// Build an ISA for the host machine
+fn host_isa(is_pic: bool) -> Result<OwnedTargetIsa, CodegenError> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").map_err(backend_error)?;
+    flags.set("is_pic", if is_pic { "true" } else { "false" }).map_err(backend_error)?;
+    let isa_builder = cranelift_native::builder().map_err(backend_error)?;
+    isa_builder.finish(settings::Flags::new(flags)).map_err(backend_error)
+}
+
+/// Declare and define every solid function of `module` in the backend,
+/// appending each function's IR to `ir` if given
+fn lower_module<B: Backend>(
+    backend: &mut B,
+    module: &Module,
+    linkage: Linkage,
+    mut ir: Option<&mut String>,
+) -> Result<HashMap<String, CompiledFunction>, CodegenError> {
+    let decls: Vec<&FunctionDecl> = module.declarations.iter()
+        .filter_map(|d| match d {
+            Declaration::Function(f) if f.mode == FunctionMode::Solid => Some(f),
+            _ => None,
+        })
+        .collect();
+
+    if decls.is_empty() {
+        return Err(CodegenError::Unsupported("no solid functions to compile".to_string()));
+    }
+
+    // Declare all functions first so they can call each other
+    let mut functions = HashMap::new();
+    for decl in &decls {
+        check_signature(decl)?;
+        let mut sig = backend.make_signature();
+        sig.params.extend(decl.params.iter().map(|_| AbiParam::new(types::I64)));
+        sig.returns.push(AbiParam::new(types::I64));
+
+        let id = backend.declare_function(&symbol_name(&decl.name), linkage, &sig)
+            .map_err(backend_error)?;
+        functions.insert(decl.name.clone(), CompiledFunction { id, arity: decl.params.len() });
+    }
+
+    let mut ctx = backend.make_context();
+    let mut builder_ctx = FunctionBuilderContext::new();
+    for decl in &decls {
+        let id = functions[&decl.name].id;
+        ctx.func.signature = backend.declarations().get_function_decl(id).signature.clone();
+        ctx.func.name = UserFuncName::user(0, id.as_u32());
+
+        let callees = functions.iter()
+            .map(|(name, f)| (name.clone(), (backend.declare_func_in_func(f.id, &mut ctx.func), f.arity)))
+            .collect();
+        translate_function(&mut ctx.func, &mut builder_ctx, decl, &callees)?;
+
+        if let Some(ir) = ir.as_deref_mut() {
+            // Show the function under its Morph name rather than its id
+            let mut named = ctx.func.clone();
+            named.name = UserFuncName::testcase(&decl.name);
+            ir.push_str(&named.display().to_string());
+            ir.push('\n');
+        }
+
+        backend.define_function(id, &mut ctx).map_err(backend_error)?;
+        backend.clear_context(&mut ctx);
+    }
+
+    Ok(functions)
+}
+
+/// Define a C `main` that calls the Morph `main` and returns its result
+fn define_entry_point<B: Backend>(backend: &mut B, morph_main: FuncId) -> Result<(), CodegenError> {
+    let mut sig = backend.make_signature();
+    sig.returns.push(AbiParam::new(types::I32));
+    let id = backend.declare_function("main", Linkage::Export, &sig).map_err(backend_error)?;
+
+    let mut ctx = backend.make_context();
+    ctx.func.signature = sig;
+    ctx.func.name = UserFuncName::user(0, id.as_u32());
+    let callee = backend.declare_func_in_func(morph_main, &mut ctx.func);
+
+    let mut builder_ctx = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+    let block = builder.create_block();
+    builder.switch_to_block(block);
+    builder.seal_block(block);
+    let call = builder.ins().call(callee, &[]);
+    let result = builder.inst_results(call)[0];
+    let code = builder.ins().ireduce(types::I32, result);
+    builder.ins().return_(&[code]);
+    builder.finalize();
+
+    backend.define_function(id, &mut ctx).map_err(backend_error)
+}
+
+/// Reject parameter and return types outside the integer subset
+fn check_signature(decl: &FunctionDecl) -> Result<(), CodegenError> {
+    for param in &decl.params {
+        match param.type_annotation {
+            Some(ref annotation) if is_integer_type(annotation) => {}
+            _ => return Err(CodegenError::Unsupported(format!(
+                "parameter `{}` of `{}` must be annotated Int or Bool", param.name, decl.name
+            ))),
+        }
+    }
+    match decl.return_type {
+        Some(ref annotation) if !is_integer_type(annotation) => Err(CodegenError::Unsupported(
+            format!("return type of `{}` must be Int or Bool", decl.name)
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Types represented as a 64-bit integer in native code
+pub(crate) fn is_integer_type(annotation: &TypeAnnotation) -> bool {
+    matches!(annotation, TypeAnnotation::Named(name) if name == "Int" || name == "Bool")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse_source(source: &str) -> Module {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_compile_add() {
+        let module = parse_source(r#"
+            solid add(a: Int, b: Int) -> Int {
+                return a + b
+            }
+        "#);
+
+        let program = compile_jit(&module).unwrap();
+        assert_eq!(program.call("add", &[2, 3]).unwrap(), 5);
+        assert_eq!(program.call("add", &[-7, 3]).unwrap(), -4);
+    }
+
+    #[test]
+    fn test_compile_recursion_and_if() {
+        let module = parse_source(r#"
+            solid fact(n: Int) -> Int {
+                if n <= 1 {
+                    return 1
+                }
+                return n * fact(n - 1)
+            }
+
+            solid max(a: Int, b: Int) -> Int {
+                let bigger = if a > b { a } else { b }
+                return bigger
+            }
+        "#);
+
+        let program = compile_jit(&module).unwrap();
+        assert_eq!(program.call("fact", &[10]).unwrap(), 3628800);
+        assert_eq!(program.call("max", &[4, 9]).unwrap(), 9);
+        assert_eq!(program.call("max", &[9, 4]).unwrap(), 9);
+    }
+
+    #[test]
+    fn test_division_does_not_trap() {
+        let module = parse_source(r#"
+            solid div(a: Int, b: Int) -> Int {
+                return a / b
+            }
+
+            solid rem(a: Int, b: Int) -> Int {
+                return a % b
+            }
+        "#);
+
+        let program = compile_jit(&module).unwrap();
+        assert_eq!(program.call("div", &[-7, 2]).unwrap(), -3);
+        assert_eq!(program.call("rem", &[-7, 2]).unwrap(), -1);
+        assert_eq!(program.call("div", &[7, 0]).unwrap(), 0);
+        assert_eq!(program.call("rem", &[7, 0]).unwrap(), 0);
+        assert_eq!(program.call("div", &[i64::MIN, -1]).unwrap(), i64::MIN);
+        assert_eq!(program.call("rem", &[i64::MIN, -1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_unsupported_construct() {
+        let module = parse_source(r#"
+            solid half(a: Int) -> Int {
+                return a * 0.5
+            }
+        "#);
+
+        let result = compile_jit(&module);
+        assert!(matches!(result, Err(CodegenError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_emit_ir() {
+        let module = parse_source(r#"
+            solid scale(a: Int, b: Int) -> Int {
+                return (a + b) * 2 - b
+            }
+        "#);
+
+        let ir = emit_ir(&module).unwrap();
+        assert!(ir.starts_with("function %scale(i64, i64) -> i64"));
+        for opcode in ["block0(v0: i64, v1: i64):", "iadd v0, v1", "iconst.i64 2", "imul", "isub", "return"] {
+            assert!(ir.contains(opcode), "missing `{}` in:\n{}", opcode, ir);
+        }
+    }
+
+    #[test]
+    fn test_object_with_entry_point() {
+        let module = parse_source(r#"
+            solid main() -> Int {
+                return 42
+            }
+        "#);
+
+        let object = compile_object(&module, "test").unwrap();
+        assert!(object.has_entry);
+        assert!(!object.bytes.is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, Function, FuncRef, InstBuilder, Value};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_module::FuncId;
+
+use crate::ast::{BinaryOp, Expression, FunctionDecl, Literal, Statement, StatementKind, TypeAnnotation, UnaryOp};
+use super::{is_integer_type, CodegenError};
+
+/// A solid function declared in the backend
+#[derive(Debug, Clone, Copy)]
+pub struct CompiledFunction {
+    pub id: FuncId,
+    pub arity: usize,
+}
+
+/// Functions callable from the one being translated: name -> (reference, arity)
+pub type Callees = HashMap<String, (FuncRef, usize)>;
+
+/// Translate a solid function's body into Cranelift IR
+pub fn translate_function(
+    func: &mut Function,
+    builder_ctx: &mut FunctionBuilderContext,
+    decl: &FunctionDecl,
+    callees: &Callees,
+) -> Result<(), CodegenError> {
+    let mut builder = FunctionBuilder::new(func, builder_ctx);
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    builder.seal_block(entry);
+
+    let mut translator = FunctionTranslator {
+        builder,
+        scopes: vec![HashMap::new()],
+        next_variable: 0,
+        callees,
+    };
+
+    for (i, param) in decl.params.iter().enumerate() {
+        let value = translator.builder.block_params(entry)[i];
+        translator.define(&param.name, value);
+    }
+
+    // Falling off the end returns the value of the last statement
+    let result = translator.translate_statements(&decl.body)?;
+    translator.builder.ins().return_(&[result]);
+
+    translator.builder.seal_all_blocks();
+    translator.builder.finalize();
+    Ok(())
+}
+
+/// Lowers statements and expressions of one function
+struct FunctionTranslator<'a> {
+    builder: FunctionBuilder<'a>,
+    /// Lexical scopes mapping names to SSA variables
+    scopes: Vec<HashMap<String, Variable>>,
+    next_variable: u32,
+    callees: &'a Callees,
+}
+
+impl FunctionTranslator<'_> {
+    /// Bind a new variable in the innermost scope
+    fn define(&mut self, name: &str, value: Value) {
+        let variable = Variable::from_u32(self.next_variable);
+        self.next_variable += 1;
+        self.builder.declare_var(variable, types::I64);
+        self.builder.def_var(variable, value);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), variable);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Variable, CodegenError> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.get(name).copied())
+            .ok_or_else(|| CodegenError::Unsupported(format!("reference to unknown variable `{}`", name)))
+    }
+
+    fn zero(&mut self) -> Value {
+        self.builder.ins().iconst(types::I64, 0)
+    }
+
+    /// Translate statements, returning the value of the last one (0 if none)
+    fn translate_statements(&mut self, stmts: &[Statement]) -> Result<Value, CodegenError> {
+        let mut result = None;
+        for stmt in stmts {
+            result = Some(self.translate_statement(stmt)?);
+        }
+        Ok(match result {
+            Some(value) => value,
+            None => self.zero(),
+        })
+    }
+
+    fn translate_statement(&mut self, stmt: &Statement) -> Result<Value, CodegenError> {
+        match &stmt.kind {
+            StatementKind::VariableDecl { name, type_annotation, initializer, .. } => {
+                if let Some(annotation) = type_annotation {
+                    if !is_integer_type(annotation) {
+                        return Err(CodegenError::Unsupported(
+                            format!("variable `{}` must be Int or Bool", name)
+                        ));
+                    }
+                }
+                let value = self.translate_expression(initializer)?;
+                self.define(name, value);
+                Ok(self.zero())
+            }
+            StatementKind::Expression(expr) => self.translate_expression(expr),
+            StatementKind::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.translate_expression(expr)?,
+                    None => self.zero(),
+                };
+                self.builder.ins().return_(&[value]);
+
+                // Anything after a return is unreachable; give it a fresh
+                // block so the IR stays well-formed
+                let after = self.builder.create_block();
+                self.builder.switch_to_block(after);
+                self.builder.seal_block(after);
+                Ok(value)
+            }
+            StatementKind::Assignment { target: Expression::Identifier(name), value } => {
+                let variable = self.lookup(name)?;
+                let value = self.translate_expression(value)?;
+                self.builder.def_var(variable, value);
+                Ok(self.zero())
+            }
+            StatementKind::Assignment { .. } => Err(CodegenError::Unsupported(
+                "assignment to fields or indices".to_string()
+            )),
+            StatementKind::For { .. } => Err(CodegenError::Unsupported("for loops".to_string())),
+            StatementKind::Break | StatementKind::Continue => Err(CodegenError::Unsupported(
+                "break and continue".to_string()
+            )),
+        }
+    }
+
+    fn translate_expression(&mut self, expr: &Expression) -> Result<Value, CodegenError> {
+        match expr {
+            Expression::Literal(Literal::Integer(n)) => Ok(self.builder.ins().iconst(types::I64, *n)),
+            Expression::Literal(Literal::Boolean(b)) => Ok(self.builder.ins().iconst(types::I64, *b as i64)),
+            Expression::Literal(_) => Err(CodegenError::Unsupported(
+                "non-integer literals".to_string()
+            )),
+            Expression::Identifier(name) => {
+                let variable = self.lookup(name)?;
+                Ok(self.builder.use_var(variable))
+            }
+            Expression::Binary { op: BinaryOp::In, .. } => Err(CodegenError::Unsupported(
+                "`in` expressions".to_string()
+            )),
+            Expression::Binary { left, op, right } => {
+                let lhs = self.translate_expression(left)?;
+                let rhs = self.translate_expression(right)?;
+                Ok(self.translate_binary_op(op, lhs, rhs))
+            }
+            Expression::Unary { op, expr } => {
+                let value = self.translate_expression(expr)?;
+                Ok(match op {
+                    UnaryOp::Negate => self.builder.ins().ineg(value),
+                    UnaryOp::Not => {
+                        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, value, 0);
+                        self.builder.ins().uextend(types::I64, is_zero)
+                    }
+                })
+            }
+            Expression::Call { callee, args } => {
+                let Expression::Identifier(name) = callee.as_ref() else {
+                    return Err(CodegenError::Unsupported("calls through function values".to_string()));
+                };
+                let (func_ref, arity) = *self.callees.get(name).ok_or_else(|| {
+                    CodegenError::Unsupported(format!("call to `{}`, which is not a solid function", name))
+                })?;
+                if arity != args.len() {
+                    return Err(CodegenError::Unsupported(format!(
+                        "`{}` expects {} arguments, got {}", name, arity, args.len()
+                    )));
+                }
+
+                let mut arg_values = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_values.push(self.translate_expression(arg)?);
+                }
+                let call = self.builder.ins().call(func_ref, &arg_values);
+                Ok(self.builder.inst_results(call)[0])
+            }
+            Expression::If { condition, then_branch, else_branch } => {
+                let cond = self.translate_expression(condition)?;
+
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+                self.builder.append_block_param(merge_block, types::I64);
+
+                self.builder.ins().brif(cond, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                self.builder.seal_block(then_block);
+                let then_value = self.translate_expression(then_branch)?;
+                self.builder.ins().jump(merge_block, &[then_value]);
+
+                self.builder.switch_to_block(else_block);
+                self.builder.seal_block(else_block);
+                let else_value = match else_branch {
+                    Some(else_branch) => self.translate_expression(else_branch)?,
+                    None => self.zero(),
+                };
+                self.builder.ins().jump(merge_block, &[else_value]);
+
+                self.builder.switch_to_block(merge_block);
+                self.builder.seal_block(merge_block);
+                Ok(self.builder.block_params(merge_block)[0])
+            }
+            Expression::Block(stmts) => {
+                self.scopes.push(HashMap::new());
+                let result = self.translate_statements(stmts);
+                self.scopes.pop();
+                result
+            }
+            Expression::Claim(expr) => self.translate_expression(expr),
+            Expression::Pipe { .. } => Err(CodegenError::Unsupported("pipe expressions".to_string())),
+            Expression::Match { .. } => Err(CodegenError::Unsupported("match expressions".to_string())),
+            Expression::FieldAccess { .. } => Err(CodegenError::Unsupported("records".to_string())),
+            Expression::IndexAccess { .. } | Expression::SliceAccess { .. } => Err(CodegenError::Unsupported("lists".to_string())),
+            Expression::Lambda { .. } => Err(CodegenError::Unsupported("lambdas".to_string())),
+            Expression::Partial { .. } => Err(CodegenError::Unsupported("partial application".to_string())),
+            Expression::Spread(_) => Err(CodegenError::Unsupported("spread arguments".to_string())),
+            Expression::NamedArg { .. } => Err(CodegenError::Unsupported("named arguments".to_string())),
+            // Every value is already an Int
+            Expression::Cast { expr, target: TypeAnnotation::Named(name) } if name == "Int" => {
+                self.translate_expression(expr)
+            }
+            Expression::Cast { target, .. } => Err(CodegenError::Unsupported(format!("casts to {}", target))),
+        }
+    }
+
+    fn translate_binary_op(&mut self, op: &BinaryOp, lhs: Value, rhs: Value) -> Value {
+        let cc = match op {
+            BinaryOp::Add => return self.builder.ins().iadd(lhs, rhs),
+            BinaryOp::Subtract => return self.builder.ins().isub(lhs, rhs),
+            BinaryOp::Multiply => return self.builder.ins().imul(lhs, rhs),
+            BinaryOp::Divide => {
+                let divisor = self.safe_divisor(lhs, rhs);
+                let quotient = self.builder.ins().sdiv(lhs, divisor);
+                // Dividing by zero gives zero
+                let zero = self.zero();
+                return self.builder.ins().select(rhs, quotient, zero);
+            }
+            BinaryOp::Modulo => {
+                let divisor = self.safe_divisor(lhs, rhs);
+                return self.builder.ins().srem(lhs, divisor);
+            }
+            BinaryOp::Equal => IntCC::Equal,
+            BinaryOp::NotEqual => IntCC::NotEqual,
+            BinaryOp::Less => IntCC::SignedLessThan,
+            BinaryOp::LessEq => IntCC::SignedLessThanOrEqual,
+            BinaryOp::Greater => IntCC::SignedGreaterThan,
+            BinaryOp::GreaterEq => IntCC::SignedGreaterThanOrEqual,
+            BinaryOp::In => unreachable!("`in` is rejected before translation"),
+        };
+        let flag = self.builder.ins().icmp(cc, lhs, rhs);
+        self.builder.ins().uextend(types::I64, flag)
+    }
+
+    /// `rhs`, or 1 where dividing by it would trap: a zero divisor, or
+    /// `i64::MIN / -1`. Dividing by 1 instead wraps the overflow to
+    /// `i64::MIN` and leaves a remainder of 0
+    fn safe_divisor(&mut self, lhs: Value, rhs: Value) -> Value {
+        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+        let is_min = self.builder.ins().icmp_imm(IntCC::Equal, lhs, i64::MIN);
+        let is_minus_one = self.builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+        let overflows = self.builder.ins().band(is_min, is_minus_one);
+        let traps = self.builder.ins().bor(is_zero, overflows);
+        let one = self.builder.ins().iconst(types::I64, 1);
+        self.builder.ins().select(traps, one, rhs)
+    }
+}
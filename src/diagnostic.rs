@@ -0,0 +1,154 @@
+//! Diagnostics reported by `mrc check`
+//!
+//! Collects type errors and lint warnings for a module into one list, so
+//! the CLI can print them together and decide the exit status.
+
+use std::fmt;
+use crate::ast::{Module, Span};
+use crate::lint::{lint_module, LintWarning};
+use crate::types::{SpannedTypeError, TypeChecker, TypeWarning};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or warning about a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Error code for `mrc check --explain`, if the diagnostic has one
+    pub code: Option<&'static str>,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let severity = match self.code {
+            Some(code) => format!("{}[{}]", severity, code),
+            None => severity.to_string(),
+        };
+        match self.span {
+            Some(span) => write!(f, "{}: {}: {}", severity, span, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+impl From<SpannedTypeError> for Diagnostic {
+    fn from(error: SpannedTypeError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.error.to_string(),
+            code: Some(error.error.code()),
+            span: error.span,
+        }
+    }
+}
+
+impl From<TypeWarning> for Diagnostic {
+    fn from(warning: TypeWarning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: warning.message,
+            code: None,
+            span: Some(warning.span),
+        }
+    }
+}
+
+impl From<LintWarning> for Diagnostic {
+    fn from(warning: LintWarning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: warning.to_string(),
+            code: None,
+            span: None,
+        }
+    }
+}
+
+/// Type check and lint a module, errors first
+pub fn check_module(module: &Module) -> Vec<Diagnostic> {
+    let mut checker = TypeChecker::new();
+    let mut diagnostics: Vec<Diagnostic> = match checker.check_module(module) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.into_iter().map(Diagnostic::from).collect(),
+    };
+    diagnostics.extend(checker.warnings().iter().cloned().map(Diagnostic::from));
+    diagnostics.extend(lint_module(module).into_iter().map(Diagnostic::from));
+    diagnostics
+}
+
+/// Whether diagnostics should fail the build: any error, or under
+/// `strict` any warning too
+pub fn is_failure(diagnostics: &[Diagnostic], strict: bool) -> bool {
+    diagnostics.iter().any(|d| strict || d.severity == Severity::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn check_source(source: &str) -> Vec<Diagnostic> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let module = parser.parse().unwrap();
+        check_module(&module)
+    }
+
+    #[test]
+    fn test_warnings_fail_only_when_strict() {
+        let source = r#"
+            proto main() {
+                let unused = 1
+                return 0
+            }
+        "#;
+
+        let diagnostics = check_source(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].to_string(), "warning: unused variable `unused` in `main`");
+
+        assert!(!is_failure(&diagnostics, false));
+        assert!(is_failure(&diagnostics, true));
+    }
+
+    #[test]
+    fn test_errors_always_fail() {
+        let source = r#"
+            proto main() {
+                return 1 + "one"
+            }
+        "#;
+
+        let diagnostics = check_source(source);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].span, Some(Span::new(3, 17)));
+        assert!(is_failure(&diagnostics, false));
+        assert!(!is_failure(&[], true));
+    }
+
+    #[test]
+    fn test_type_errors_carry_codes() {
+        let source = "proto main() {\n    let bad: Int = \"text\"\n    return bad\n}\n";
+
+        let diagnostics = check_source(source);
+        assert_eq!(diagnostics[0].code, Some("E0001"));
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error[E0001]: line 2, column 5: Type mismatch: expected Int, got String"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Documentation extraction for Morph source
+//!
+//! Renders every top-level function's signature and `///` doc comment as
+//! Markdown.
+
+use crate::ast::*;
+
+/// Render the documentation of a module's functions as Markdown
+pub fn render_markdown(module: &Module) -> String {
+    let sections: Vec<String> = module.declarations.iter()
+        .filter_map(|decl| match decl {
+            Declaration::Function(func) => Some(render_function(func)),
+            _ => None,
+        })
+        .collect();
+    sections.join("\n")
+}
+
+/// Render one function as a heading with its signature, followed by its doc
+fn render_function(func: &FunctionDecl) -> String {
+    let mut out = format!("### `{}`\n", signature(func));
+    if let Some(ref doc) = func.doc {
+        out.push('\n');
+        out.push_str(doc);
+        out.push('\n');
+    }
+    out
+}
+
+/// A function's signature as it would be written in source
+pub fn signature(func: &FunctionDecl) -> String {
+    let mode = match func.mode {
+        FunctionMode::Proto => "proto",
+        FunctionMode::Solid => "solid",
+    };
+    let params: Vec<String> = func.params.iter()
+        .map(|param| match param.type_annotation {
+            Some(ref annotation) => format!("{}: {}", param.name, annotation),
+            None => param.name.clone(),
+        })
+        .collect();
+
+    let mut out = format!("{} {}", mode, func.name);
+    if !func.type_params.is_empty() {
+        out.push_str(&format!("<{}>", func.type_params.join(", ")));
+    }
+    out.push_str(&format!("({})", params.join(", ")));
+    if let Some(ref ret) = func.return_type {
+        out.push_str(&format!(" -> {}", ret));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn doc_source(source: &str) -> String {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let module = parser.parse().unwrap();
+        render_markdown(&module)
+    }
+
+    #[test]
+    fn test_render_documented_function() {
+        let source = r#"
+            /// Add two numbers.
+            ///
+            /// Overflow wraps in solid mode.
+            solid add(a: Int, b: Int) -> Int {
+                return a + b
+            }
+
+            // An ordinary comment is not documentation
+            proto shout<T>(message: T, times: List<Int>) -> T {
+                return message
+            }
+        "#;
+
+        let expected = "\
+### `solid add(a: Int, b: Int) -> Int`
+
+Add two numbers.
+
+Overflow wraps in solid mode.
+
+### `proto shout<T>(message: T, times: List<Int>) -> T`
+";
+        assert_eq!(doc_source(source), expected);
+    }
+}
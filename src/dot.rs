@@ -0,0 +1,172 @@
+//! Graphviz export of the AST for `mrc parse --dot`
+//!
+//! Every declaration, statement, expression and match arm becomes a node
+//! labelled with its kind, with an edge to each of its children.
+
+use crate::ast::*;
+use crate::ast::visitor::{self, Visitor};
+
+/// Render a module's AST as a DOT digraph
+pub fn render_dot(module: &Module) -> String {
+    let mut graph = DotGraph::default();
+    let root = graph.add_node("Module");
+    graph.parents.push(root);
+    visitor::walk_module(&mut graph, module);
+
+    let mut out = String::from("digraph ast {\n    node [shape=box];\n");
+    for (id, label) in graph.nodes.iter().enumerate() {
+        out.push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(label)));
+    }
+    for (from, to) in &graph.edges {
+        out.push_str(&format!("    n{} -> n{};\n", from, to));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Escape a label for use inside a double-quoted DOT string
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Collects nodes and edges while walking the AST
+#[derive(Default)]
+struct DotGraph {
+    nodes: Vec<String>,
+    edges: Vec<(usize, usize)>,
+    /// Ids of the nodes enclosing the one being visited
+    parents: Vec<usize>,
+}
+
+impl DotGraph {
+    /// Add a node, linked from the enclosing node if there is one
+    fn add_node(&mut self, label: &str) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(label.to_string());
+        if let Some(&parent) = self.parents.last() {
+            self.edges.push((parent, id));
+        }
+        id
+    }
+
+    /// Add a node and visit its children beneath it
+    fn nest(&mut self, label: &str, walk: impl FnOnce(&mut Self)) {
+        let id = self.add_node(label);
+        self.parents.push(id);
+        walk(self);
+        self.parents.pop();
+    }
+}
+
+fn literal_label(lit: &Literal) -> String {
+    match lit {
+        Literal::Integer(n) => n.to_string(),
+        Literal::Float(x) => x.to_string(),
+        Literal::String(s) => format!("\"{}\"", s),
+        Literal::Boolean(b) => b.to_string(),
+        Literal::List(_) => "List".to_string(),
+        Literal::Record(_) => "Record".to_string(),
+    }
+}
+
+impl Visitor for DotGraph {
+    fn visit_declaration(&mut self, decl: &Declaration) {
+        match decl {
+            Declaration::Type(ty) => {
+                self.add_node(&format!("type {}", ty.name));
+            }
+            Declaration::Import(import) => {
+                self.add_node(&format!("import {}", import.module));
+            }
+            _ => visitor::walk_declaration(self, decl),
+        }
+    }
+
+    fn visit_function(&mut self, func: &FunctionDecl) {
+        self.nest(&crate::doc::signature(func), |graph| visitor::walk_function(graph, func));
+    }
+
+    fn visit_solve_block(&mut self, solve: &SolveBlock) {
+        self.nest(&format!("solve {}", solve.name), |graph| visitor::walk_solve_block(graph, solve));
+    }
+
+    fn visit_verify_block(&mut self, verify: &VerifyBlock) {
+        self.nest(&format!("verify {}", verify.name), |graph| visitor::walk_verify_block(graph, verify));
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        let label = match &stmt.kind {
+            StatementKind::VariableDecl { name, mutable, .. } => {
+                format!("{} {}", if *mutable { "var" } else { "let" }, name)
+            }
+            StatementKind::Expression(_) => "Expression".to_string(),
+            StatementKind::Return(_) => "return".to_string(),
+            StatementKind::Break => "break".to_string(),
+            StatementKind::Continue => "continue".to_string(),
+            StatementKind::For { variable, .. } => format!("for {}", variable),
+            StatementKind::Assignment { .. } => "=".to_string(),
+        };
+        self.nest(&label, |graph| visitor::walk_statement(graph, stmt));
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        let label = match expr {
+            Expression::Literal(lit) => literal_label(lit),
+            Expression::Identifier(name) => name.clone(),
+            Expression::Binary { op, .. } => op.to_string(),
+            Expression::Unary { op, .. } => op.to_string(),
+            Expression::Call { .. } => "Call".to_string(),
+            Expression::Pipe { .. } => "|>".to_string(),
+            Expression::Match { .. } => "match".to_string(),
+            Expression::Block(_) => "Block".to_string(),
+            Expression::If { .. } => "if".to_string(),
+            Expression::FieldAccess { field, .. } => format!(".{}", field),
+            Expression::IndexAccess { .. } => "Index".to_string(),
+            Expression::SliceAccess { .. } => "Slice".to_string(),
+            Expression::Lambda { .. } => "Lambda".to_string(),
+            Expression::Partial { .. } => "Partial".to_string(),
+            Expression::Claim(_) => "claim".to_string(),
+            Expression::Spread(_) => "...".to_string(),
+            Expression::NamedArg { name, .. } => format!("{}:", name),
+            Expression::Cast { target, .. } => format!("as {}", target),
+        };
+        self.nest(&label, |graph| visitor::walk_expression(graph, expr));
+    }
+
+    fn visit_match_arm(&mut self, arm: &MatchArm) {
+        self.nest(&format!("{:?}", arm.pattern), |graph| visitor::walk_match_arm(graph, arm));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dot_source(source: &str) -> String {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let module = parser.parse().unwrap();
+        render_dot(&module)
+    }
+
+    #[test]
+    fn test_expression_graph() {
+        let dot = dot_source("proto main() { return 1 + 2 * x }");
+
+        // Module, main, return, +, 1, *, 2, x
+        assert_eq!(dot.matches("[label=").count(), 8);
+        assert_eq!(dot.matches(" -> ").count(), 7);
+        assert!(dot.starts_with("digraph ast {"));
+        assert!(dot.contains("n3 [label=\"+\"];"));
+        assert!(dot.contains("n3 -> n5;"));
+    }
+
+    #[test]
+    fn test_labels_are_escaped() {
+        let dot = dot_source(r#"proto main() { return "C:\temp" }"#);
+        assert!(dot.contains(r#"[label="\"C:\\temp\""];"#));
+    }
+}
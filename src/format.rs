@@ -0,0 +1,299 @@
+//! Source formatting for `mrc fmt`
+//!
+//! Works line by line rather than on the AST, so comments survive: each
+//! line is re-indented by four spaces per open bracket, trailing
+//! whitespace is trimmed and runs of blank lines collapse to one. Text
+//! inside multi-line string literals is left exactly as written.
+
+/// Lines of context around each change in a diff
+const CONTEXT: usize = 3;
+
+/// Format Morph source
+pub fn format_source(source: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut pending_blank = false;
+
+    for raw in source.lines() {
+        if in_string {
+            // Continuation of a multi-line string: keep it verbatim
+            let scan = scan_line(raw, true);
+            out.push_str(raw);
+            out.push('\n');
+            depth = (depth + scan.opened).saturating_sub(scan.closed);
+            in_string = scan.in_string;
+            continue;
+        }
+
+        let line = raw.trim_start();
+        if line.trim_end().is_empty() {
+            pending_blank = !out.is_empty();
+            continue;
+        }
+        if pending_blank {
+            out.push('\n');
+            pending_blank = false;
+        }
+
+        let scan = scan_line(line, false);
+        // Trailing spaces inside an unterminated string are part of it
+        let line = if scan.in_string { line } else { line.trim_end() };
+        out.push_str(&"    ".repeat(depth.saturating_sub(scan.leading_closers)));
+        out.push_str(line);
+        out.push('\n');
+        depth = (depth + scan.opened).saturating_sub(scan.closed);
+        in_string = scan.in_string;
+    }
+
+    out
+}
+
+/// Bracket counts for one line, ignoring strings and comments
+struct LineScan {
+    opened: usize,
+    closed: usize,
+    /// Closing brackets before anything else on the line
+    leading_closers: usize,
+    /// Whether the line ends inside a string literal
+    in_string: bool,
+}
+
+fn scan_line(line: &str, mut in_string: bool) -> LineScan {
+    let mut scan = LineScan { opened: 0, closed: 0, leading_closers: 0, in_string: false };
+    let mut leading = !in_string;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            in_string = c != '"';
+            continue;
+        }
+        match c {
+            '}' | ')' | ']' => {
+                scan.closed += 1;
+                if leading {
+                    scan.leading_closers += 1;
+                }
+                continue;
+            }
+            _ if c.is_whitespace() => continue,
+            '"' => in_string = true,
+            '/' if chars.peek() == Some(&'/') => break,
+            '{' | '(' | '[' => scan.opened += 1,
+            _ => {}
+        }
+        leading = false;
+    }
+
+    scan.in_string = in_string;
+    scan
+}
+
+/// The diff that `format_source` would apply to `source`, or `None` if it
+/// is already formatted
+pub fn check_formatting(path: &str, source: &str) -> Option<String> {
+    let formatted = format_source(source);
+    if formatted == source {
+        return None;
+    }
+    let diff = unified_diff(path, source, &formatted);
+    if diff.is_empty() {
+        // Same lines, so only the final newline is missing
+        return Some(format!("{}: no newline at end of file\n", path));
+    }
+    Some(diff)
+}
+
+/// Whether a line is kept, removed or added by a diff
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Line-level diff of `old` and `new` in unified format, with `path` in the
+/// headers. Empty if the two have the same lines.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = diff_lines(&old_lines, &new_lines);
+
+    // Line offsets in old and new before each edit, plus one past the end
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for (edit, _) in &edits {
+        positions.push((old_pos, new_pos));
+        match edit {
+            Edit::Keep => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Delete => old_pos += 1,
+            Edit::Insert => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    let changes: Vec<usize> = edits.iter()
+        .enumerate()
+        .filter(|(_, (edit, _))| *edit != Edit::Keep)
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", path, path);
+    let mut i = 0;
+    while i < changes.len() {
+        // Changes close enough that their context overlaps share a hunk
+        let start = changes[i].saturating_sub(CONTEXT);
+        let mut last = changes[i];
+        i += 1;
+        while i < changes.len() && changes[i] - last - 1 <= 2 * CONTEXT {
+            last = changes[i];
+            i += 1;
+        }
+        let end = (last + 1 + CONTEXT).min(edits.len());
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start),
+        ));
+        for (edit, line) in &edits[start..end] {
+            let prefix = match edit {
+                Edit::Keep => ' ',
+                Edit::Delete => '-',
+                Edit::Insert => '+',
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// A hunk header range: 1-based start line and length
+fn hunk_range(start: usize, len: usize) -> String {
+    // An empty range names the line before it
+    if len == 0 {
+        format!("{},0", start)
+    } else {
+        format!("{},{}", start + 1, len)
+    }
+}
+
+/// Shortest edit script turning `old` into `new`, via longest common subsequence
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Edit, &'a str)> {
+    // lcs[i][j] is the length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            edits.push((Edit::Keep, old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            edits.push((Edit::Delete, old[i]));
+            i += 1;
+        } else {
+            edits.push((Edit::Insert, new[j]));
+            j += 1;
+        }
+    }
+    edits.extend(old[i..].iter().map(|line| (Edit::Delete, *line)));
+    edits.extend(new[j..].iter().map(|line| (Edit::Insert, *line)));
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATTED: &str = "\
+// Greeting
+proto greet(name) {
+    let parts = [
+        \"Hello, \",
+        name
+    ]
+
+    if len(name) > 0 {
+        return parts[0] + name
+    } else {
+        return \"Hi\"
+    }
+}
+";
+
+    #[test]
+    fn test_check_rejects_unformatted_source() {
+        let unformatted = "\n\n// Greeting\nproto greet(name) {\n  let parts = [\n  \"Hello, \",\n      name\n  ]   \n\n\n  if len(name) > 0 {\nreturn parts[0] + name\n    } else {\n        return \"Hi\"\n  }\n}";
+
+        assert_eq!(format_source(unformatted), FORMATTED);
+        let diff = check_formatting("greet.morph", unformatted).unwrap();
+        assert!(diff.starts_with("--- greet.morph\n+++ greet.morph\n@@ -1,"));
+        assert!(diff.contains("\n-  let parts = [\n"));
+        assert!(diff.contains("\n+    let parts = [\n"));
+        assert!(diff.contains("\n         return \"Hi\"\n"));
+    }
+
+    #[test]
+    fn test_check_accepts_formatted_source() {
+        assert_eq!(check_formatting("greet.morph", FORMATTED), None);
+
+        let unterminated = FORMATTED.trim_end();
+        assert_eq!(
+            check_formatting("greet.morph", unterminated),
+            Some("greet.morph: no newline at end of file\n".to_string()),
+        );
+    }
+
+    #[test]
+    fn test_multiline_strings_are_kept() {
+        let source = "proto main() {\n    let text = \"first   \n  second {\n\"\n    return text\n}\n";
+        assert_eq!(format_source(source), source);
+    }
+
+    #[test]
+    fn test_diff_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nL\nm\n";
+        let diff = unified_diff("x", old, new);
+        assert_eq!(diff, "\
+--- x
++++ x
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -9,5 +9,5 @@
+ i
+ j
+ k
+-l
++L
+ m
+");
+    }
+}
@@ -0,0 +1,66 @@
+//! Line coverage for `mrc run --coverage`
+
+use std::collections::{BTreeSet, HashMap};
+use crate::ast::*;
+use crate::ast::visitor::{self, Visitor};
+
+/// Which source lines with statements on them ran, for `mrc run --coverage`.
+///
+/// Coverage is tracked per line rather than per statement, so a line
+/// counts as executed if any statement starting on it ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    /// Every line that starts a statement, in order
+    pub lines: Vec<usize>,
+    /// The lines in `lines` that never ran
+    pub missed: Vec<usize>,
+    /// How many times statements on each line in `lines` ran
+    pub hits: Vec<usize>,
+}
+
+impl CoverageReport {
+    /// Compare the statements in `module` against how often each line ran
+    pub fn new(module: &Module, executed: &HashMap<usize, usize>) -> Self {
+        let mut collector = StatementLines::default();
+        visitor::walk_module(&mut collector, module);
+        let lines: Vec<usize> = collector.lines.into_iter().collect();
+        let hits: Vec<usize> = lines.iter().map(|line| executed.get(line).copied().unwrap_or(0)).collect();
+        let missed = lines.iter().zip(&hits).filter(|(_, &hits)| hits == 0).map(|(&line, _)| line).collect();
+        CoverageReport { lines, missed, hits }
+    }
+
+    /// Percentage of lines executed; 100 for a program with no statements
+    pub fn percent(&self) -> f64 {
+        if self.lines.is_empty() {
+            return 100.0;
+        }
+        let executed = self.lines.len() - self.missed.len();
+        executed as f64 * 100.0 / self.lines.len() as f64
+    }
+
+    /// The report as JSON for `mrc run --emit-coverage-json`: the percentage
+    /// executed, and each line's hit count keyed by line number
+    pub fn to_json(&self) -> serde_json::Value {
+        let hits: serde_json::Map<String, serde_json::Value> = self.lines.iter()
+            .zip(&self.hits)
+            .map(|(line, &hits)| (line.to_string(), hits.into()))
+            .collect();
+        serde_json::json!({ "percent": self.percent(), "lines": hits })
+    }
+}
+
+/// Collects the line of every statement
+#[derive(Default)]
+struct StatementLines {
+    lines: BTreeSet<usize>,
+}
+
+impl Visitor for StatementLines {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        // Statements made up by the interpreter have no position
+        if stmt.span != Span::default() {
+            self.lines.insert(stmt.span.line);
+        }
+        visitor::walk_statement(self, stmt);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use super::value::{Value, RuntimeError};
 
 /// Environment for variable scoping
@@ -6,6 +6,9 @@ use super::value::{Value, RuntimeError};
 pub struct Environment {
     /// Current scope variables
     variables: HashMap<String, Value>,
+    /// Variables of the current scope declared with `let`, which can't be
+    /// assigned to
+    constants: HashSet<String>,
     /// Parent environment (for nested scopes)
     parent: Option<Box<Environment>>,
 }
@@ -15,6 +18,7 @@ impl Environment {
     pub fn new() -> Self {
         Environment {
             variables: HashMap::new(),
+            constants: HashSet::new(),
             parent: None,
         }
     }
@@ -23,15 +27,42 @@ impl Environment {
     pub fn with_parent(parent: Environment) -> Self {
         Environment {
             variables: HashMap::new(),
+            constants: HashSet::new(),
             parent: Some(Box::new(parent)),
         }
     }
 
+    /// Start a scope nested inside the current one
+    pub fn push_scope(&mut self) {
+        let parent = std::mem::take(self);
+        *self = Environment::with_parent(parent);
+    }
+
+    /// Leave the current scope for the one it's nested in, keeping
+    /// assignments made to that scope's variables in the meantime
+    pub fn pop_scope(&mut self) {
+        if let Some(parent) = self.parent.take() {
+            *self = *parent;
+        }
+    }
+
     /// Define a variable in the current scope
     pub fn define(&mut self, name: String, value: Value) {
+        self.constants.remove(&name);
         self.variables.insert(name, value);
     }
 
+    /// Define a variable in the current scope that can't be assigned to
+    pub fn define_constant(&mut self, name: String, value: Value) {
+        self.constants.insert(name.clone());
+        self.variables.insert(name, value);
+    }
+
+    /// Remove a variable from the current scope
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.variables.remove(name)
+    }
+
     /// Get a variable's value (searches up the scope chain)
     pub fn get(&self, name: &str) -> Result<Value, RuntimeError> {
         if let Some(value) = self.variables.get(name) {
@@ -43,9 +74,12 @@ impl Environment {
         }
     }
 
-    /// Get a mutable reference to a variable (searches up the scope chain)
+    /// Get a mutable reference to a variable (searches up the scope chain),
+    /// failing if it can't be assigned to
     pub fn get_mut(&mut self, name: &str) -> Result<&mut Value, RuntimeError> {
-        if self.variables.contains_key(name) {
+        if self.constants.contains(name) {
+            Err(constant_error(name))
+        } else if self.variables.contains_key(name) {
             self.variables.get_mut(name)
                 .ok_or_else(|| RuntimeError::UndefinedVariable(name.to_string()))
         } else if let Some(ref mut parent) = self.parent {
@@ -57,7 +91,9 @@ impl Environment {
 
     /// Assign to an existing variable (searches up the scope chain)
     pub fn assign(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
-        if self.variables.contains_key(name) {
+        if self.constants.contains(name) {
+            Err(constant_error(name))
+        } else if self.variables.contains_key(name) {
             self.variables.insert(name.to_string(), value);
             Ok(())
         } else if let Some(ref mut parent) = self.parent {
@@ -67,10 +103,27 @@ impl Environment {
         }
     }
 
+    /// Assign to a variable, defining it in the global (outermost) scope if
+    /// it doesn't exist yet. Meant for top-level REPL input, where `x = 5`
+    /// should create `x`; everywhere else use the strict `assign`.
+    pub fn assign_or_define(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if self.contains(name) {
+            self.assign(name, value)
+        } else {
+            match self.parent {
+                Some(ref mut parent) => parent.assign_or_define(name, value),
+                None => {
+                    self.define(name.to_string(), value);
+                    Ok(())
+                }
+            }
+        }
+    }
+
     /// Check if a variable exists in this scope or any parent scope
     pub fn contains(&self, name: &str) -> bool {
         self.variables.contains_key(name) 
-            || self.parent.as_ref().map_or(false, |p| p.contains(name))
+            || self.parent.as_ref().is_some_and(|p| p.contains(name))
     }
 
     /// Get all variables in the current scope (for debugging)
@@ -94,8 +147,49 @@ impl Environment {
     }
 }
 
+/// The error for assigning to a variable declared with `let`
+fn constant_error(name: &str) -> RuntimeError {
+    RuntimeError::InvalidOperation(format!("Cannot assign to {}, which is declared with let", name))
+}
+
 impl Default for Environment {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_or_define() {
+        let mut globals = Environment::new();
+        globals.define("x".to_string(), Value::Integer(1));
+        let mut scope = Environment::with_parent(globals);
+
+        // Strict assignment still rejects unknown names
+        assert_eq!(
+            scope.assign("y", Value::Integer(5)),
+            Err(RuntimeError::UndefinedVariable("y".to_string())),
+        );
+
+        // REPL-style assignment updates existing bindings in place...
+        scope.assign_or_define("x", Value::Integer(2)).unwrap();
+        assert!(scope.local_variables().is_empty());
+        assert_eq!(scope.get("x"), Ok(Value::Integer(2)));
+
+        // ...and creates missing ones in the global scope
+        scope.assign_or_define("y", Value::Integer(5)).unwrap();
+        assert!(scope.local_variables().is_empty());
+
+        // Neither can change a `let`
+        scope.define_constant("z".to_string(), Value::Integer(1));
+        assert!(scope.assign("z", Value::Integer(2)).is_err());
+        assert!(scope.assign_or_define("z", Value::Integer(2)).is_err());
+        assert_eq!(scope.get("z"), Ok(Value::Integer(1)));
+
+        let globals = scope.parent.unwrap();
+        assert_eq!(globals.get("y"), Ok(Value::Integer(5)));
+    }
+}
@@ -1,10 +1,38 @@
 pub mod value;
 pub mod environment;
+pub mod modules;
+pub mod random;
+pub mod coverage;
 
 use crate::ast::*;
 use value::{Value, RuntimeError, FunctionValue};
 use environment::Environment;
+use random::Rng;
+use coverage::CoverageReport;
 use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::rc::Rc;
+use indexmap::IndexMap;
+use crate::symbol::{Symbol, SymbolTable};
+use std::time::{Duration, Instant};
+
+/// Deepest nesting of user function calls allowed by default
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// Most elements `repeat`, `fill` and `*` repetition build (bytes, for a
+/// String), so a huge length is an error rather than an allocation failure
+/// that aborts the process. About four million elements, a few hundred
+/// megabytes of values
+const MAX_LIST_LENGTH: usize = 1 << 22;
+
+/// Characters after which a line of output counts as another line towards
+/// the output limit, so `print` without line breaks can't run past it
+const OUTPUT_LINE_WIDTH: usize = 1024;
+
+/// Builtins with side effects (IO or hidden state), left out of sandboxes
+pub const IMPURE_BUILTINS: [&str; 10] = [
+    "log", "print", "random", "random_int", "read_lines", "stdin", "stdin_lines", "now", "elapsed", "env",
+];
 
 /// Morph interpreter for Stage 0 (Draft mode)
 pub struct Interpreter {
@@ -12,6 +40,71 @@ pub struct Interpreter {
     globals: Environment,
     /// Current environment (changes with scope)
     environment: Environment,
+    /// Where `log` and `print` write
+    output: Box<dyn Write>,
+    /// Flush `output` after every `log` and `print`
+    line_buffered: bool,
+    /// Stop with `RuntimeError::Halted` once `log` or `print` has written
+    halt_on_first_log: bool,
+    /// Most lines `log` and `print` may write before the program is stopped
+    output_limit: Option<usize>,
+    /// Lines written by `log` and `print` so far, counting one that is
+    /// still open
+    output_lines: usize,
+    /// Characters written on the last line so far
+    output_column: usize,
+    /// Location of the statement being executed
+    current_span: Span,
+    /// Where `stdin` and `stdin_lines` read from
+    input: Box<dyn Read>,
+    /// Everything read from `input`, once either builtin has been called
+    input_text: Option<String>,
+    /// Where to write the call trace, if tracing is enabled
+    trace: Option<Box<dyn Write>>,
+    /// Number of user function calls currently active
+    call_depth: usize,
+    /// Limit on `call_depth`, so runaway recursion is an error rather
+    /// than a stack overflow
+    max_depth: usize,
+    /// Source of the `random` builtins
+    rng: Rng,
+    /// Variables visible to the `env` builtin
+    env_vars: HashMap<String, String>,
+    /// `return`, `break` or `continue` unwinding to the function or loop
+    /// that handles it
+    control: Option<ControlFlow>,
+    /// Per-function call counts and timings, if profiling is enabled
+    profile: Option<HashMap<String, FunctionProfile>>,
+    /// Reject arithmetic mixing Int and Float instead of promoting to Float
+    strict_arithmetic: bool,
+    /// How many times statements on each line have run, if coverage is enabled
+    coverage: Option<HashMap<usize, usize>>,
+    /// Keep the scope of the first runtime error in `error_scope`
+    keep_error_scope: bool,
+    /// Variables visible where the first runtime error was raised
+    error_scope: Option<Environment>,
+    /// Record field names made at runtime, such as `group_by` keys, and
+    /// the operator method names
+    symbols: SymbolTable,
+}
+
+/// Calls to one user function and the time spent in them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// Wall time including callees; recursive calls are only timed once,
+    /// at the outermost call
+    pub total: Duration,
+    /// Calls of this function currently on the stack
+    active: usize,
+}
+
+/// Non-local control flow raised by a statement
+#[derive(Debug, Clone, PartialEq)]
+enum ControlFlow {
+    Return(Value),
+    Break,
+    Continue,
 }
 
 impl Interpreter {
@@ -22,41 +115,230 @@ impl Interpreter {
         // Register built-in functions
         Self::register_builtins(&mut globals);
         
+        Self::with_globals(globals)
+    }
+
+    /// Create an interpreter for untrusted code: only pure builtins are
+    /// available, so everything in `IMPURE_BUILTINS` is undefined
+    pub fn new_sandboxed() -> Self {
+        let mut globals = Environment::new();
+        Self::register_builtins(&mut globals);
+        for name in IMPURE_BUILTINS {
+            globals.remove(name);
+        }
+        
+        Self::with_globals(globals)
+    }
+
+    fn with_globals(globals: Environment) -> Self {
+        // Interned up front, so looking them up on every record `+` and `-`
+        // finds them without adding to the table
+        let mut symbols = SymbolTable::new();
+        for name in ["__add__", "__sub__"] {
+            symbols.intern(name);
+        }
+
         Interpreter {
             globals: globals.clone(),
             environment: globals,
+            output: Box::new(std::io::stdout()),
+            line_buffered: false,
+            halt_on_first_log: false,
+            output_limit: None,
+            output_lines: 0,
+            output_column: 0,
+            current_span: Span::default(),
+            input: Box::new(std::io::stdin()),
+            input_text: None,
+            trace: None,
+            call_depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            rng: Rng::from_time(),
+            // Variables that aren't valid Unicode can't be Morph strings
+            env_vars: std::env::vars_os()
+                .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+                .collect(),
+            control: None,
+            profile: None,
+            strict_arithmetic: false,
+            coverage: None,
+            keep_error_scope: false,
+            error_scope: None,
+            symbols,
         }
     }
 
-    /// Register built-in functions
-    fn register_builtins(env: &mut Environment) {
-        // log function - prints to stdout
-        env.define("log".to_string(), Value::Function(FunctionValue::Builtin(|args| {
-            for (i, arg) in args.iter().enumerate() {
-                if i > 0 {
-                    print!(" ");
+    /// Seed the random number generator so `random` builtins are reproducible
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::with_seed(seed);
+    }
+
+    /// Set a variable for the `env` builtin, overriding the process environment
+    pub fn set_env_var(&mut self, key: String, value: String) {
+        self.env_vars.insert(key, value);
+    }
+
+    /// Send the output of `log` and `print` to `out` instead of stdout
+    pub fn set_output(&mut self, out: Box<dyn Write>) {
+        self.output = out;
+    }
+
+    /// Read the `stdin` builtins' input from `input` instead of stdin
+    pub fn set_input(&mut self, input: Box<dyn Read>) {
+        self.input = input;
+        self.input_text = None;
+    }
+
+    /// All of the input, read the first time it is asked for
+    pub fn read_input(&mut self) -> Result<String, RuntimeError> {
+        if let Some(text) = &self.input_text {
+            return Ok(text.clone());
+        }
+        let mut text = String::new();
+        self.input.read_to_string(&mut text)
+            .map_err(|e| RuntimeError::Custom(format!("Cannot read standard input: {}", e)))?;
+        self.input_text = Some(text.clone());
+        Ok(text)
+    }
+
+    /// Flush the output after every `log` and `print`, so it reaches a
+    /// pipe as soon as it is written
+    pub fn set_line_buffered(&mut self, line_buffered: bool) {
+        self.line_buffered = line_buffered;
+    }
+
+    /// Stop the program right after the first `log` or `print`, with an
+    /// error saying where it was
+    pub fn set_halt_on_first_log(&mut self, halt: bool) {
+        self.halt_on_first_log = halt;
+    }
+
+    /// Stop the program with `RuntimeError::OutputLimit` rather than let
+    /// `log` and `print` write more than `limit` lines. A line counts once
+    /// it's started, and every `OUTPUT_LINE_WIDTH` characters of a long
+    /// line count as another
+    pub fn set_output_limit(&mut self, limit: usize) {
+        self.output_limit = Some(limit);
+    }
+
+    /// Log every user function call and its result to `out`
+    pub fn set_trace(&mut self, out: Box<dyn Write>) {
+        self.trace = Some(out);
+    }
+
+    /// Fail calls nested deeper than `max_depth` user functions
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Count calls and time spent per user function
+    pub fn enable_profile(&mut self) {
+        self.profile = Some(HashMap::new());
+    }
+
+    /// Record which lines run, for `coverage_report`
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(HashMap::new());
+    }
+
+    /// Remember the variables in scope when a runtime error is raised, for
+    /// `enter_error_scope`
+    pub fn enable_error_scope(&mut self) {
+        self.keep_error_scope = true;
+    }
+
+    /// Switch to the scope the first runtime error was raised in, so
+    /// `eval_statements` can inspect it. Returns false if
+    /// there was no error or the scope wasn't kept.
+    pub fn enter_error_scope(&mut self) -> bool {
+        match self.error_scope.take() {
+            Some(scope) => {
+                self.environment = scope;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run statements typed at a prompt in the current scope, returning
+    /// the value of the last one. Assigning to a name that doesn't exist
+    /// yet defines it, as a global
+    pub fn eval_statements(&mut self, stmts: &[Statement]) -> Result<Value, RuntimeError> {
+        let mut result = Ok(Value::Unit);
+        for stmt in stmts {
+            result = match &stmt.kind {
+                StatementKind::Assignment { target: Expression::Identifier(name), value } => {
+                    self.evaluate(value)
+                        .and_then(|value| self.environment.assign_or_define(name, value))
+                        .map(|()| Value::Unit)
                 }
-                print!("{}", arg.to_string());
+                _ => self.execute_statement(stmt),
+            };
+            if result.is_err() || self.control.is_some() {
+                break;
             }
-            println!();
-            Ok(Value::Unit)
+        }
+        // A `return` or `break` has nothing to unwind to here
+        self.control = None;
+        result
+    }
+
+    /// Make arithmetic on an Int and a Float an error rather than
+    /// promoting the Int, so every conversion is explicit
+    pub fn set_strict_arithmetic(&mut self, strict: bool) {
+        self.strict_arithmetic = strict;
+    }
+
+    /// Profiled functions, hottest (most total time) first
+    pub fn profile_report(&self) -> Vec<(String, FunctionProfile)> {
+        let mut report: Vec<(String, FunctionProfile)> = self.profile.iter()
+            .flatten()
+            .map(|(name, profile)| (name.clone(), profile.clone()))
+            .collect();
+        report.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+        report
+    }
+
+    /// Lines of `module` executed so far, if coverage is enabled
+    pub fn coverage_report(&self, module: &Module) -> Option<CoverageReport> {
+        self.coverage.as_ref().map(|executed| CoverageReport::new(module, executed))
+    }
+
+    /// The non-function global bindings, sorted by name
+    pub fn global_bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = self.globals.local_variables().iter()
+            .filter(|(_, value)| !matches!(value, Value::Function(_)))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// The global bindings as a JSON object, in name order. Bindings with
+    /// no JSON form, such as functions and imported modules, are left out
+    pub fn bindings_json(&self) -> serde_json::Value {
+        let object = self.global_bindings().into_iter()
+            .filter_map(|(name, value)| Some((name, value.to_json().ok()?)))
+            .collect();
+        serde_json::Value::Object(object)
+    }
+
+    /// Register built-in functions
+    fn register_builtins(env: &mut Environment) {
+        // log function - prints its arguments and a newline
+        env.define("log".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            interp.write_output(args, "\n")
         })));
 
         // print function - prints without newline
-        env.define("print".to_string(), Value::Function(FunctionValue::Builtin(|args| {
-            for (i, arg) in args.iter().enumerate() {
-                if i > 0 {
-                    print!(" ");
-                }
-                print!("{}", arg.to_string());
-            }
-            Ok(Value::Unit)
+        env.define("print".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            interp.write_output(args, "")
         })));
 
         // len function - gets length of list or string
         env.define("len".to_string(), Value::Function(FunctionValue::Builtin(|args| {
             if args.len() != 1 {
-                return Err(RuntimeError::ArityMismatch { expected: 1, got: args.len() });
+                return Err(RuntimeError::ArityMismatch { function: "len".to_string(), expected: 1, got: args.len() });
             }
             match &args[0] {
                 Value::List(items) => Ok(Value::Integer(items.len() as i64)),
@@ -65,789 +347,4179 @@ impl Interpreter {
             }
         })));
 
-        // push function - adds element to list
+        // push function - the list with an element added at the end. Lists
+        // are values, so the list passed in is left as it was
         env.define("push".to_string(), Value::Function(FunctionValue::Builtin(|args| {
             if args.len() != 2 {
-                return Err(RuntimeError::ArityMismatch { expected: 2, got: args.len() });
+                return Err(RuntimeError::ArityMismatch { function: "push".to_string(), expected: 2, got: args.len() });
             }
-            // Note: This is a simplified version
-            // In a real implementation, we'd need mutable references
-            Ok(Value::Unit)
+            let mut list = args[0].clone();
+            list.as_list_mut()?.push(args[1].clone());
+            Ok(list)
         })));
 
-        // range function - creates a range of numbers
+        // range function - creates a range of numbers. Integer ranges know
+        // their length, so `collect` allocates the list once.
         env.define("range".to_string(), Value::Function(FunctionValue::Builtin(|args| {
             match args.len() {
                 1 => {
                     let end = args[0].as_integer()?;
-                    let list: Vec<Value> = (0..end).map(|i| Value::Integer(i)).collect();
-                    Ok(Value::List(list))
+                    let list: Vec<Value> = (0..end).map(Value::Integer).collect();
+                    Ok(Value::list(list))
                 }
                 2 => {
                     let start = args[0].as_integer()?;
                     let end = args[1].as_integer()?;
-                    let list: Vec<Value> = (start..end).map(|i| Value::Integer(i)).collect();
-                    Ok(Value::List(list))
+                    let list: Vec<Value> = (start..end).map(Value::Integer).collect();
+                    Ok(Value::list(list))
                 }
                 3 => {
                     let start = args[0].as_integer()?;
                     let end = args[1].as_integer()?;
                     let step = args[2].as_integer()?;
-                    let list: Vec<Value> = (start..end).step_by(step as usize).map(|i| Value::Integer(i)).collect();
-                    Ok(Value::List(list))
+                    let list: Vec<Value> = (start..end).step_by(step as usize).map(Value::Integer).collect();
+                    Ok(Value::list(list))
                 }
-                _ => Err(RuntimeError::ArityMismatch { expected: 3, got: args.len() }),
+                _ => Err(RuntimeError::ArityMismatch { function: "range".to_string(), expected: 3, got: args.len() }),
             }
         })));
-    }
 
-    /// Interpret a complete module
-    pub fn interpret(&mut self, module: &Module) -> Result<Value, RuntimeError> {
-        let mut result = Value::Unit;
-        
-        // First pass: register all function declarations
-        for decl in &module.declarations {
-            if let Declaration::Function(func) = decl {
-                let func_value = Value::Function(FunctionValue::UserDefined {
-                    decl: func.clone(),
-                    closure: Some(self.environment.snapshot()),
-                });
-                self.globals.define(func.name.clone(), func_value);
+        // repeat function - list of n copies of a value
+        env.define("repeat".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "repeat".to_string(), expected: 2, got: args.len() });
             }
-        }
-        
-        // Second pass: execute the module (look for main function)
-        let has_main = module.declarations.iter().any(|d| {
-            matches!(d, Declaration::Function(f) if f.name == "main")
-        });
-        
-        // Update environment with globals
-        self.environment = self.globals.clone();
-        
-        if has_main {
-            // Call main function
-            self.call_function("main", &[])
-        } else {
-            // Execute all top-level declarations
-            for decl in &module.declarations {
-                match decl {
-                    Declaration::Function(_) => {
-                        // Already registered
-                    }
-                    Declaration::Type(_) => {
-                        // Type declarations are compile-time only in proto mode
-                    }
-                    Declaration::Solve(solve) => {
-                        result = self.execute_solve_block(solve)?;
-                    }
-                    Declaration::Import(_) => {
-                        // TODO: Implement imports
-                    }
-                }
+            let n = list_length(&args[1], "repeat")?;
+            Ok(Value::list(vec![args[0].clone(); n]))
+        })));
+
+        // fill function - list whose element i is fn(i)
+        env.define("fill".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "fill".to_string(), expected: 2, got: args.len() });
             }
-            Ok(result)
-        }
-    }
+            let n = list_length(&args[0], "fill")?;
+            let mut items = Vec::with_capacity(n);
+            for i in 0..n {
+                items.push(interp.call_value(&args[1], &[Value::Integer(i as i64)])?);
+            }
+            Ok(Value::list(items))
+        })));
 
-    /// Execute a solve block
-    fn execute_solve_block(&mut self, solve: &SolveBlock) -> Result<Value, RuntimeError> {
-        // Create new scope for solve block
-        let previous = self.environment.clone();
-        self.environment = Environment::with_parent(self.environment.clone());
-        
-        // Bind parameters
-        for param in &solve.params {
-            self.environment.define(param.name.clone(), Value::Unit);
-        }
-        
-        // Execute constraints
-        for constraint in &solve.constraints {
-            match constraint {
-                Constraint::Binding { name, expr } => {
-                    let value = self.evaluate(expr)?;
-                    self.environment.define(name.clone(), value);
-                }
-                Constraint::Ensure(expr) => {
-                    let value = self.evaluate(expr)?;
-                    if !value.is_truthy() {
-                        return Err(RuntimeError::Custom(
-                            format!("Ensure constraint failed: {:?}", expr)
-                        ));
-                    }
-                }
+        // zip_with function - combine paired elements, stopping at the shorter list
+        env.define("zip_with".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 3 {
+                return Err(RuntimeError::ArityMismatch { function: "zip_with".to_string(), expected: 3, got: args.len() });
             }
-        }
-        
-        // Get return value
-        let result = if let Some(ref expr) = solve.return_expr {
-            self.evaluate(expr)?
-        } else {
-            Value::Unit
-        };
-        
-        // Restore environment
-        self.environment = previous;
-        
-        Ok(result)
-    }
+            let (a, b) = (args[0].as_list()?, args[1].as_list()?);
+            let mut items = Vec::with_capacity(a.len().min(b.len()));
+            for (x, y) in a.iter().zip(b.iter()) {
+                items.push(interp.call_value(&args[2], &[x.clone(), y.clone()])?);
+            }
+            Ok(Value::list(items))
+        })));
 
-    /// Call a function by name
-    fn call_function(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
-        let func = self.environment.get(name)?;
-        
-        match func {
-            Value::Function(func_val) => self.execute_function(&func_val, args),
-            _ => Err(RuntimeError::TypeError(format!("{} is not a function", name))),
-        }
-    }
+        // zip3 function - [a, b, c] lists of elements at the same index,
+        // stopping at the shortest list
+        env.define("zip3".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 3 {
+                return Err(RuntimeError::ArityMismatch { function: "zip3".to_string(), expected: 3, got: args.len() });
+            }
+            let (a, b, c) = (args[0].as_list()?, args[1].as_list()?, args[2].as_list()?);
+            let triples = a.iter().zip(b.iter()).zip(c.iter())
+                .map(|((x, y), z)| Value::list(vec![x.clone(), y.clone(), z.clone()]))
+                .collect();
+            Ok(Value::list(triples))
+        })));
 
-    /// Execute a function value
-    fn execute_function(&mut self, func: &FunctionValue, args: &[Value]) -> Result<Value, RuntimeError> {
-        match func {
-            FunctionValue::Builtin(builtin) => builtin(args),
-            FunctionValue::UserDefined { decl, closure } => {
-                // Check arity
-                if decl.params.len() != args.len() {
-                    return Err(RuntimeError::ArityMismatch {
-                        expected: decl.params.len(),
-                        got: args.len(),
-                    });
-                }
-                
-                // Create new environment with closure
-                let mut new_env = if let Some(ref closure_vars) = closure {
-                    let mut env = Environment::new();
-                    for (name, value) in closure_vars {
-                        env.define(name.clone(), value.clone());
-                    }
-                    env
-                } else {
-                    Environment::with_parent(self.environment.clone())
-                };
-                
-                // Bind parameters
-                for (param, arg) in decl.params.iter().zip(args.iter()) {
-                    new_env.define(param.name.clone(), arg.clone());
-                }
-                
-                // Execute function body
-                let previous = self.environment.clone();
-                self.environment = new_env;
-                
-                let mut result = Value::Unit;
-                for stmt in &decl.body {
-                    result = self.execute_statement(stmt)?;
-                    // Check for early return
-                    // TODO: Implement proper return handling
-                }
-                
-                // Restore environment
-                self.environment = previous;
-                
-                Ok(result)
+        // flat_map function - concatenate the lists fn returns for each element
+        env.define("flat_map".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "flat_map".to_string(), expected: 2, got: args.len() });
             }
-        }
-    }
+            let list = args[0].as_list()?;
+            // At least one element per input is the common case
+            let mut items = Vec::with_capacity(list.len());
+            for item in list.iter() {
+                let mapped = interp.call_value(&args[1], std::slice::from_ref(item))?;
+                items.extend(mapped.as_list()?.iter().cloned());
+            }
+            Ok(Value::list(items))
+        })));
 
-    /// Execute a statement
-    fn execute_statement(&mut self, stmt: &Statement) -> Result<Value, RuntimeError> {
-        match stmt {
-            Statement::VariableDecl { name, initializer, .. } => {
-                let value = self.evaluate(initializer)?;
-                self.environment.define(name.clone(), value);
-                Ok(Value::Unit)
+        // fold_right function - combine elements from the last, fn(item, acc)
+        env.define("fold_right".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 3 {
+                return Err(RuntimeError::ArityMismatch { function: "fold_right".to_string(), expected: 3, got: args.len() });
             }
-            Statement::Expression(expr) => {
-                self.evaluate(expr)
+            let mut acc = args[1].clone();
+            for item in args[0].as_list()?.iter().rev() {
+                acc = interp.call_value(&args[2], &[item.clone(), acc])?;
             }
-            Statement::Return(expr) => {
-                if let Some(expr) = expr {
-                    self.evaluate(expr)
-                } else {
-                    Ok(Value::Unit)
-                }
+            Ok(acc)
+        })));
+
+        // scan function - every accumulator of a left fold, fn(acc, item)
+        env.define("scan".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 3 {
+                return Err(RuntimeError::ArityMismatch { function: "scan".to_string(), expected: 3, got: args.len() });
             }
-            Statement::For { variable, iterable, guard, body } => {
-                let iter_value = self.evaluate(iterable)?;
-                let items = match iter_value {
-                    Value::List(items) => items,
-                    _ => return Err(RuntimeError::TypeError(
-                        "For loop requires a list".to_string()
+            let list = args[0].as_list()?;
+            let mut items = Vec::with_capacity(list.len());
+            let mut acc = args[1].clone();
+            for item in list.iter() {
+                acc = interp.call_value(&args[2], &[acc, item.clone()])?;
+                items.push(acc.clone());
+            }
+            Ok(Value::list(items))
+        })));
+
+        // apply function - call fn with the elements of a list as its arguments
+        env.define("apply".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "apply".to_string(), expected: 2, got: args.len() });
+            }
+            let call_args = args[1].as_list()?.clone();
+            interp.call_value(&args[0], &call_args)
+        })));
+
+        // call function - call fn with the rest of the arguments
+        env.define("call".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            let Some((func, call_args)) = args.split_first() else {
+                return Err(RuntimeError::ArityMismatch { function: "call".to_string(), expected: 1, got: 0 });
+            };
+            interp.call_value(func, call_args)
+        })));
+
+        // group_by function - record from each key fn returns to the elements
+        // with that key, with keys in order of first appearance
+        env.define("group_by".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "group_by".to_string(), expected: 2, got: args.len() });
+            }
+            let mut groups: IndexMap<String, Vec<Value>> = IndexMap::new();
+            for item in args[0].as_list()?.iter() {
+                let key = match interp.call_value(&args[1], std::slice::from_ref(item))? {
+                    Value::String(key) => key,
+                    key @ (Value::Integer(_) | Value::Boolean(_)) => key.to_string(),
+                    other => return Err(RuntimeError::TypeError(
+                        format!("group_by() keys must be String, Int or Bool, got {}", other.type_name())
                     )),
                 };
-                
-                let mut result = Value::Unit;
-                
-                for item in items {
-                    // Create new scope for loop body
-                    let previous = self.environment.clone();
-                    self.environment = Environment::with_parent(self.environment.clone());
-                    
-                    // Bind loop variable
-                    self.environment.define(variable.clone(), item);
-                    
-                    // Check guard if present
-                    if let Some(ref guard_expr) = guard {
-                        let guard_value = self.evaluate(guard_expr)?;
-                        if !guard_value.is_truthy() {
-                            self.environment = previous;
-                            continue;
-                        }
-                    }
-                    
-                    // Execute body
-                    for stmt in body {
-                        result = self.execute_statement(stmt)?;
-                    }
-                    
-                    // Restore environment
-                    self.environment = previous;
-                }
-                
-                Ok(result)
+                groups.entry(key).or_default().push(item.clone());
             }
-            Statement::Assignment { target, value } => {
-                let val = self.evaluate(value)?;
-                
-                // Handle simple variable assignment
-                if let Expression::Identifier(name) = target {
-                    self.environment.assign(name, val)?;
-                } else if let Expression::FieldAccess { object, field } = target {
-                    let obj_val = self.evaluate(object)?;
-                    // TODO: Handle field assignment
-                } else if let Expression::IndexAccess { object, index } = target {
-                    let mut obj_val = self.evaluate(object)?;
-                    let idx_val = self.evaluate(index)?;
-                    
-                    if let Value::List(ref mut items) = obj_val {
-                        let idx = idx_val.as_integer()?;
-                        if idx < 0 || idx as usize >= items.len() {
-                            return Err(RuntimeError::IndexOutOfBounds {
-                                index: idx,
-                                len: items.len(),
-                            });
-                        }
-                        items[idx as usize] = val;
-                    }
-                }
-                
-                Ok(Value::Unit)
+            Ok(Value::Record(groups.into_iter().map(|(key, items)| (interp.symbols.intern(&key), Value::list(items))).collect()))
+        })));
+
+        // partition function - [matching, non_matching] by a predicate, keeping order
+        env.define("partition".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "partition".to_string(), expected: 2, got: args.len() });
             }
+            let (mut matching, mut rest) = (Vec::new(), Vec::new());
+            for item in args[0].as_list()?.iter() {
+                if interp.call_value(&args[1], std::slice::from_ref(item))?.is_truthy() {
+                    matching.push(item.clone());
+                } else {
+                    rest.push(item.clone());
+                }
+            }
+            Ok(Value::list(vec![Value::list(matching), Value::list(rest)]))
+        })));
+
+        // min_by/max_by functions - the element with the smallest/largest key,
+        // the first one on ties
+        env.define("min_by".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            extreme_by(interp, args, "min_by", std::cmp::Ordering::Less)
+        })));
+        env.define("max_by".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            extreme_by(interp, args, "max_by", std::cmp::Ordering::Greater)
+        })));
+
+        // error function - stop with a runtime error carrying the message
+        env.define("error".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "error".to_string(), expected: 1, got: args.len() });
+            }
+            Err(RuntimeError::Custom(args[0].as_string()?))
+        })));
+
+        // take function - first n elements of a list
+        env.define("take".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "take".to_string(), expected: 2, got: args.len() });
+            }
+            let items = args[0].as_list()?;
+            let n = list_length(&args[1], "take")?.min(items.len());
+            Ok(Value::list(items[..n].to_vec()))
+        })));
+
+        // drop function - all but the first n elements of a list
+        env.define("drop".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "drop".to_string(), expected: 2, got: args.len() });
+            }
+            let items = args[0].as_list()?;
+            let n = list_length(&args[1], "drop")?.min(items.len());
+            Ok(Value::list(items[n..].to_vec()))
+        })));
+
+        // chunk function - split a list into sub-lists of up to size elements
+        env.define("chunk".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "chunk".to_string(), expected: 2, got: args.len() });
+            }
+            let items = args[0].as_list()?;
+            let size = args[1].as_integer()?;
+            if size <= 0 {
+                return Err(RuntimeError::InvalidOperation(
+                    format!("chunk() requires a positive size, got {}", size)
+                ));
+            }
+            let chunks = items.chunks(size as usize)
+                .map(|chunk| Value::list(chunk.to_vec()))
+                .collect();
+            Ok(Value::list(chunks))
+        })));
+
+        // flatten function - concatenate a list of lists, one level deep
+        env.define("flatten".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "flatten".to_string(), expected: 1, got: args.len() });
+            }
+            let rows = args[0].as_list()?
+                .iter()
+                .map(|row| row.as_list())
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut flat = Vec::with_capacity(rows.iter().map(|row| row.len()).sum());
+            for row in rows {
+                flat.extend(row.iter().cloned());
+            }
+            Ok(Value::list(flat))
+        })));
+
+        // transpose function - swap the rows and columns of a list of equal-length lists
+        env.define("transpose".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "transpose".to_string(), expected: 1, got: args.len() });
+            }
+            let rows = args[0].as_list()?
+                .iter()
+                .map(|row| row.as_list())
+                .collect::<Result<Vec<_>, _>>()?;
+            let width = rows.first().map_or(0, |row| row.len());
+            if let Some((i, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != width) {
+                return Err(RuntimeError::InvalidOperation(
+                    format!("transpose() requires rows of equal length, but row {} has {} elements instead of {}", i, row.len(), width)
+                ));
+            }
+            let columns = (0..width)
+                .map(|j| Value::list(rows.iter().map(|row| row[j].clone()).collect()))
+                .collect();
+            Ok(Value::list(columns))
+        })));
+
+        // contains function - substring test for strings, membership test for lists
+        env.define("contains".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "contains".to_string(), expected: 2, got: args.len() });
+            }
+            match &args[0] {
+                Value::String(s) => Ok(Value::Boolean(s.contains(args[1].as_string()?.as_str()))),
+                Value::List(items) => Ok(Value::Boolean(items.contains(&args[1]))),
+                other => Err(RuntimeError::TypeError(
+                    format!("contains() requires a String or List, got {}", other.type_name())
+                )),
+            }
+        })));
+
+        // starts_with function - prefix test for strings
+        env.define("starts_with".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "starts_with".to_string(), expected: 2, got: args.len() });
+            }
+            let s = args[0].as_string()?;
+            Ok(Value::Boolean(s.starts_with(args[1].as_string()?.as_str())))
+        })));
+
+        // ends_with function - suffix test for strings
+        env.define("ends_with".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "ends_with".to_string(), expected: 2, got: args.len() });
+            }
+            let s = args[0].as_string()?;
+            Ok(Value::Boolean(s.ends_with(args[1].as_string()?.as_str())))
+        })));
+
+        // pad_left function - right-align s in width chars of fill
+        env.define("pad_left".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            pad(args, "pad_left", |padding| padding)
+        })));
+
+        // pad_right function - left-align s in width chars of fill
+        env.define("pad_right".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            pad(args, "pad_right", |_| 0)
+        })));
+
+        // center function - center s in width chars of fill, leaning left
+        env.define("center".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            pad(args, "center", |padding| padding / 2)
+        })));
+
+        // index function - char index of the first occurrence of sub, or -1
+        env.define("index".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "index".to_string(), expected: 2, got: args.len() });
+            }
+            let s = args[0].as_string()?;
+            let found = s.find(args[1].as_string()?.as_str());
+            Ok(Value::Integer(char_index(&s, found)))
+        })));
+
+        // rindex function - char index of the last occurrence of sub, or -1
+        env.define("rindex".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "rindex".to_string(), expected: 2, got: args.len() });
+            }
+            let s = args[0].as_string()?;
+            let found = s.rfind(args[1].as_string()?.as_str());
+            Ok(Value::Integer(char_index(&s, found)))
+        })));
+
+        // char_at function - the char at index i as a string; negative i counts from the end
+        env.define("char_at".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "char_at".to_string(), expected: 2, got: args.len() });
+            }
+            let chars: Vec<char> = args[0].as_string()?.chars().collect();
+            let index = args[1].as_integer()?;
+            let resolved = if index < 0 { index + chars.len() as i64 } else { index };
+            if resolved < 0 || resolved as usize >= chars.len() {
+                return Err(RuntimeError::IndexOutOfBounds { index, len: chars.len() });
+            }
+            Ok(Value::String(chars[resolved as usize].to_string()))
+        })));
+
+        // substring function - chars start..end, with the same bounds as s[start:end]
+        env.define("substring".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 3 {
+                return Err(RuntimeError::ArityMismatch { function: "substring".to_string(), expected: 3, got: args.len() });
+            }
+            let chars: Vec<char> = args[0].as_string()?.chars().collect();
+            let range = slice_range(Some(args[1].as_integer()?), Some(args[2].as_integer()?), chars.len());
+            Ok(Value::String(chars[range].iter().collect()))
+        })));
+
+        // split_lines function - split on \n or \r\n, without a trailing empty line
+        env.define("split_lines".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "split_lines".to_string(), expected: 1, got: args.len() });
+            }
+            Ok(lines_of(&args[0].as_string()?))
+        })));
+
+        // read_lines function - the lines of a text file
+        env.define("read_lines".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "read_lines".to_string(), expected: 1, got: args.len() });
+            }
+            let path = args[0].as_string()?;
+            let text = std::fs::read_to_string(&path).map_err(|e| {
+                RuntimeError::Custom(format!("Cannot read {}: {}", path, e))
+            })?;
+            Ok(lines_of(&text))
+        })));
+
+        // stdin function - all of standard input as one string
+        env.define("stdin".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if !args.is_empty() {
+                return Err(RuntimeError::ArityMismatch { function: "stdin".to_string(), expected: 0, got: args.len() });
+            }
+            Ok(Value::String(interp.read_input()?))
+        })));
+
+        // stdin_lines function - the lines of standard input
+        env.define("stdin_lines".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if !args.is_empty() {
+                return Err(RuntimeError::ArityMismatch { function: "stdin_lines".to_string(), expected: 0, got: args.len() });
+            }
+            Ok(lines_of(&interp.read_input()?))
+        })));
+
+        // sort function - ascending numbers or strings; NaN sorts last
+        env.define("sort".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "sort".to_string(), expected: 1, got: args.len() });
+            }
+            let items = args[0].as_list()?;
+            let numbers = items.iter().all(|v| matches!(v, Value::Integer(_) | Value::Float(_)));
+            let strings = items.iter().all(|v| matches!(v, Value::String(_)));
+            if !numbers && !strings {
+                return Err(RuntimeError::TypeError(
+                    "sort() requires a list of numbers or a list of strings".to_string()
+                ));
+            }
+            let mut sorted = items.clone();
+            sorted.sort_by(sort_order);
+            Ok(Value::list(sorted))
+        })));
+
+        // to_int function - truncate a Float or parse a String
+        env.define("to_int".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "to_int".to_string(), expected: 1, got: args.len() });
+            }
+            match &args[0] {
+                Value::Integer(n) => Ok(Value::Integer(*n)),
+                // `as` saturates, so reject NaN, infinities and out-of-range values
+                Value::Float(f) if f.is_finite() && f.trunc() >= i64::MIN as f64 && f.trunc() < i64::MAX as f64 => {
+                    Ok(Value::Integer(f.trunc() as i64))
+                }
+                Value::String(s) => s.trim().parse().map(Value::Integer).map_err(|_| {
+                    RuntimeError::InvalidOperation(format!("Cannot convert \"{}\" to Int", s))
+                }),
+                other => Err(RuntimeError::InvalidOperation(
+                    format!("Cannot convert {} ({}) to Int", other, other.type_name())
+                )),
+            }
+        })));
+
+        // to_float function - widen an Int or parse a String
+        env.define("to_float".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "to_float".to_string(), expected: 1, got: args.len() });
+            }
+            match &args[0] {
+                Value::Integer(n) => Ok(Value::Float(*n as f64)),
+                Value::Float(f) => Ok(Value::Float(*f)),
+                Value::String(s) => s.trim().parse().map(Value::Float).map_err(|_| {
+                    RuntimeError::InvalidOperation(format!("Cannot convert \"{}\" to Float", s))
+                }),
+                other => Err(RuntimeError::InvalidOperation(
+                    format!("Cannot convert {} ({}) to Float", other, other.type_name())
+                )),
+            }
+        })));
+
+        // complex function - the complex number re + im*i
+        env.define("complex".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "complex".to_string(), expected: 2, got: args.len() });
+            }
+            Ok(Value::Complex(args[0].as_float()?, args[1].as_float()?))
+        })));
+
+        // abs_diff function - distance between two integers
+        env.define("abs_diff".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "abs_diff".to_string(), expected: 2, got: args.len() });
+            }
+            let (a, b) = (args[0].as_integer()?, args[1].as_integer()?);
+            int_result(a.abs_diff(b), "abs_diff", a, b)
+        })));
+
+        // gcd function - greatest common divisor, gcd(0, 0) is 0
+        env.define("gcd".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "gcd".to_string(), expected: 2, got: args.len() });
+            }
+            let (a, b) = (args[0].as_integer()?, args[1].as_integer()?);
+            int_result(gcd(a.unsigned_abs(), b.unsigned_abs()), "gcd", a, b)
+        })));
+
+        // lcm function - least common multiple, 0 if either is 0
+        env.define("lcm".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "lcm".to_string(), expected: 2, got: args.len() });
+            }
+            let (a, b) = (args[0].as_integer()?, args[1].as_integer()?);
+            if a == 0 || b == 0 {
+                return Ok(Value::Integer(0));
+            }
+            let (x, y) = (a.unsigned_abs(), b.unsigned_abs());
+            match (x / gcd(x, y)).checked_mul(y) {
+                Some(lcm) => int_result(lcm, "lcm", a, b),
+                None => Err(overflow("lcm", a, b)),
+            }
+        })));
+
+        // map_values function - apply fn to every value of a record
+        env.define("map_values".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "map_values".to_string(), expected: 2, got: args.len() });
+            }
+            let mut result = IndexMap::new();
+            for (key, value) in args[0].as_record()? {
+                result.insert(key.clone(), interp.call_value(&args[1], std::slice::from_ref(value))?);
+            }
+            Ok(Value::Record(result))
+        })));
+
+        // filter_entries function - keep the entries where predicate(key, value) is truthy
+        env.define("filter_entries".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "filter_entries".to_string(), expected: 2, got: args.len() });
+            }
+            let mut result = IndexMap::new();
+            for (key, value) in args[0].as_record()? {
+                let keep = interp.call_value(&args[1], &[Value::String(key.to_string()), value.clone()])?;
+                if keep.is_truthy() {
+                    result.insert(key.clone(), value.clone());
+                }
+            }
+            Ok(Value::Record(result))
+        })));
+
+        // random function - uniform Float in [0, 1)
+        env.define("random".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if !args.is_empty() {
+                return Err(RuntimeError::ArityMismatch { function: "random".to_string(), expected: 0, got: args.len() });
+            }
+            Ok(Value::Float(interp.rng.next_float()))
+        })));
+
+        // random_int function - uniform Int in [lo, hi)
+        env.define("random_int".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "random_int".to_string(), expected: 2, got: args.len() });
+            }
+            let lo = args[0].as_integer()?;
+            let hi = args[1].as_integer()?;
+            if lo >= hi {
+                return Err(RuntimeError::InvalidOperation(
+                    format!("random_int() requires lo < hi, got {} and {}", lo, hi)
+                ));
+            }
+            Ok(Value::Integer(interp.rng.next_int(lo, hi)))
+        })));
+
+        // env function - an environment variable, or "" if it isn't set
+        env.define("env".to_string(), Value::Function(FunctionValue::Intrinsic(|interp, args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "env".to_string(), expected: 1, got: args.len() });
+            }
+            let name = args[0].as_string()?;
+            Ok(Value::String(interp.env_vars.get(&name).cloned().unwrap_or_default()))
+        })));
+
+        // now function - the current instant, for timing with elapsed
+        env.define("now".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if !args.is_empty() {
+                return Err(RuntimeError::ArityMismatch { function: "now".to_string(), expected: 0, got: args.len() });
+            }
+            Ok(Value::Instant(Instant::now()))
+        })));
+
+        // elapsed function - seconds since an instant returned by now
+        env.define("elapsed".to_string(), Value::Function(FunctionValue::Builtin(|args| {
+            if args.len() != 1 {
+                return Err(RuntimeError::ArityMismatch { function: "elapsed".to_string(), expected: 1, got: args.len() });
+            }
+            match &args[0] {
+                Value::Instant(start) => Ok(Value::Float(start.elapsed().as_secs_f64())),
+                other => Err(RuntimeError::TypeError(
+                    format!("elapsed() expects an Instant from now(), got {}", other.type_name())
+                )),
+            }
+        })));
+    }
+
+    /// Call a function value with already-evaluated arguments
+    pub fn call_value(&mut self, func: &Value, args: &[Value]) -> Result<Value, RuntimeError> {
+        match func {
+            Value::Function(func_val) => self.execute_function(func_val, args),
+            _ => Err(RuntimeError::TypeError(
+                format!("Expected Function, got {}", func.type_name())
+            )),
+        }
+    }
+
+    /// Interpret a complete module
+    pub fn interpret(&mut self, module: &Module) -> Result<Value, RuntimeError> {
+        self.interpret_with_args(module, Vec::new())
+    }
+
+    /// Interpret a complete module, passing `args` to `main` as a list
+    /// if `main` declares a parameter
+    pub fn interpret_with_args(&mut self, module: &Module, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.interpret_with_input(module, Value::list(args))
+    }
+
+    /// Interpret a module, passing `input` to `main` if it takes a parameter
+    pub fn interpret_with_input(&mut self, module: &Module, input: Value) -> Result<Value, RuntimeError> {
+        let mut result = Value::Unit;
+        self.load_module(module)?;
+        
+        // Execute the module (look for main function)
+        let main = module.declarations.iter().find_map(|d| match d {
+            Declaration::Function(f) if f.name == "main" => Some(f),
+            _ => None,
+        });
+        
+        if let Some(main) = main {
+            // Call main function, handing it the input if it takes it
+            if main.params.is_empty() {
+                self.call_function("main", &[])
+            } else {
+                self.call_function("main", &[input])
+            }
+        } else {
+            // Execute all top-level declarations
+            for decl in &module.declarations {
+                match decl {
+                    Declaration::Function(_) => {
+                        // Already registered
+                    }
+                    Declaration::Type(_) => {
+                        // Type declarations are compile-time only in proto mode
+                    }
+                    Declaration::Solve(solve) => {
+                        result = self.execute_solve_block(solve)?;
+                    }
+                    Declaration::Verify(_) => {
+                        // Only run by `mrc verify`
+                    }
+                    Declaration::Import(_) | Declaration::Global(_) => {
+                        // Already registered
+                    }
+                }
+            }
+            Ok(result)
+        }
+    }
+
+    /// Run every verify block in a module, in order, recording whether
+    /// each `ensure` held. A failed `ensure` doesn't stop the block.
+    pub fn verify(&mut self, module: &Module) -> Result<Vec<VerifyOutcome>, RuntimeError> {
+        self.load_module(module)?;
+        
+        let mut outcomes = Vec::new();
+        for decl in &module.declarations {
+            let Declaration::Verify(verify) = decl else {
+                continue;
+            };
+            let previous = self.environment.clone();
+            self.environment = Environment::with_parent(self.environment.clone());
+            let checked = self.check_ensures(verify);
+            self.environment = previous;
+            outcomes.push(VerifyOutcome { name: verify.name.clone(), passed: checked? });
+        }
+        Ok(outcomes)
+    }
+
+    /// Evaluate a verify block's constraints in the current scope
+    fn check_ensures(&mut self, verify: &VerifyBlock) -> Result<Vec<bool>, RuntimeError> {
+        let mut passed = Vec::new();
+        for constraint in &verify.constraints {
+            match constraint {
+                Constraint::Binding { name, expr } => {
+                    let value = self.evaluate(expr)?;
+                    self.environment.define(name.clone(), value);
+                }
+                Constraint::Ensure(expr) => {
+                    passed.push(self.evaluate(expr)?.is_truthy());
+                }
+                // The parser only produces bindings and ensures here
+                Constraint::Choice { .. } | Constraint::Objective { .. } => {}
+            }
+        }
+        Ok(passed)
+    }
+
+    /// Register a module's imports and functions, then evaluate its
+    /// top-level bindings
+    fn load_module(&mut self, module: &Module) -> Result<(), RuntimeError> {
+        // First pass: register all imports and function declarations
+        for decl in &module.declarations {
+            match decl {
+                Declaration::Import(import) => {
+                    let exports = modules::load_module(&import.module).ok_or_else(|| {
+                        RuntimeError::Custom(format!("Unknown module: {}", import.module))
+                    })?;
+                    self.globals.define(import.binding_name().to_string(), exports.clone());
+                    self.environment.define(import.binding_name().to_string(), exports);
+                }
+                Declaration::Function(func) => {
+                    // Top-level functions resolve free names in the globals
+                    // at call time, so they can call each other and recurse
+                    let func_value = Value::Function(FunctionValue::UserDefined {
+                        decl: Rc::new(func.clone()),
+                        closure: None,
+                    });
+                    self.globals.define(func.name.clone(), func_value);
+                }
+                _ => {}
+            }
+        }
+        
+        // Update environment with globals
+        self.environment = self.globals.clone();
+
+        // Evaluate top-level bindings in order; functions are already
+        // registered, so initializers may call them
+        for decl in &module.declarations {
+            if let Declaration::Global(stmt) = decl {
+                if let StatementKind::VariableDecl { name, type_annotation, initializer, mutable } = &stmt.kind {
+                    let value = widen(self.evaluate(initializer)?, type_annotation.as_ref());
+                    if *mutable {
+                        self.globals.define(name.clone(), value.clone());
+                        self.environment.define(name.clone(), value);
+                    } else {
+                        self.globals.define_constant(name.clone(), value.clone());
+                        self.environment.define_constant(name.clone(), value);
+                    }
+                }
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Execute a solve block.
+    ///
+    /// `let x in domain` constraints are searched depth-first in order,
+    /// rejecting assignments that fail an `ensure`. Without an objective the
+    /// first solution wins; with `minimize`/`maximize` every solution is
+    /// visited and the best one (earliest on ties) is returned.
+    fn execute_solve_block(&mut self, solve: &SolveBlock) -> Result<Value, RuntimeError> {
+        // Create new scope for solve block
+        let previous = self.environment.clone();
+        self.environment = Environment::with_parent(self.environment.clone());
+        
+        // Bind parameters
+        for param in &solve.params {
+            self.environment.define(param.name.clone(), Value::Unit);
+        }
+        
+        let mut best = None;
+        let outcome = self.search_solutions(solve, 0, None, &mut best);
+        
+        // Restore environment
+        self.environment = previous;
+        outcome?;
+        
+        best.map(|solution: Solution| solution.value).ok_or_else(|| {
+            RuntimeError::Custom(format!("No solution satisfies the constraints of {}", solve.name))
+        })
+    }
+
+    /// Search the constraints of `solve` from `index` on, recording the best
+    /// solution found. Returns `true` once the search can stop.
+    fn search_solutions(
+        &mut self,
+        solve: &SolveBlock,
+        index: usize,
+        score: Option<f64>,
+        best: &mut Option<Solution>,
+    ) -> Result<bool, RuntimeError> {
+        let Some(constraint) = solve.constraints.get(index) else {
+            return self.record_solution(solve, score, best);
+        };
+        
+        match constraint {
+            Constraint::Binding { name, expr } => {
+                let value = self.evaluate(expr)?;
+                self.environment.define(name.clone(), value);
+                self.search_solutions(solve, index + 1, score, best)
+            }
+            Constraint::Ensure(expr) => {
+                let value = self.evaluate(expr)?;
+                if value.is_truthy() {
+                    self.search_solutions(solve, index + 1, score, best)
+                } else if has_choices(solve) {
+                    Ok(false)
+                } else {
+                    Err(RuntimeError::Custom(
+                        format!("Ensure constraint failed: {:?}", expr)
+                    ))
+                }
+            }
+            Constraint::Choice { name, domain } => {
+                let items = match self.evaluate(domain)? {
+                    Value::List(items) => items,
+                    other => return Err(RuntimeError::TypeError(
+                        format!("Domain of {} must be a List, got {}", name, other.type_name())
+                    )),
+                };
+                
+                for item in items.iter().cloned() {
+                    let previous = self.environment.clone();
+                    self.environment = Environment::with_parent(self.environment.clone());
+                    self.environment.define(name.clone(), item);
+                    
+                    let done = self.search_solutions(solve, index + 1, score, best);
+                    self.environment = previous;
+                    if done? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Constraint::Objective { expr, .. } => {
+                let value = self.evaluate(expr)?.as_float()?;
+                self.search_solutions(solve, index + 1, Some(value), best)
+            }
+        }
+    }
+
+    /// Keep the solution reached by the current assignment if it beats `best`
+    fn record_solution(
+        &mut self,
+        solve: &SolveBlock,
+        score: Option<f64>,
+        best: &mut Option<Solution>,
+    ) -> Result<bool, RuntimeError> {
+        let objective = solve.constraints.iter().find_map(|c| match c {
+            Constraint::Objective { kind, .. } => Some(*kind),
+            _ => None,
+        });
+        
+        let improves = match (best.as_ref().and_then(|b| b.score), score, objective) {
+            (Some(current), Some(candidate), Some(ObjectiveKind::Min)) => candidate < current,
+            (Some(current), Some(candidate), Some(ObjectiveKind::Max)) => candidate > current,
+            _ => best.is_none(),
+        };
+        
+        if improves {
+            let value = match solve.return_expr {
+                Some(ref expr) => self.evaluate(expr)?,
+                None => Value::Unit,
+            };
+            *best = Some(Solution { value, score });
+        }
+        
+        // Without an objective the first solution is the answer
+        Ok(objective.is_none())
+    }
+
+    /// Call a function by name
+    fn call_function(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+        let func = self.environment.get(name)?;
+        
+        match func {
+            Value::Function(func_val) => self.execute_function(&func_val, args),
+            _ => Err(RuntimeError::TypeError(format!("{} is not a function", name))),
+        }
+    }
+
+    /// Execute a function value
+    fn execute_function(&mut self, func: &FunctionValue, args: &[Value]) -> Result<Value, RuntimeError> {
+        match func {
+            FunctionValue::Builtin(builtin) => builtin(args),
+            FunctionValue::Intrinsic(intrinsic) => intrinsic(self, args),
+            FunctionValue::UserDefined { decl, closure } => {
+                if self.call_depth >= self.max_depth {
+                    return Err(RuntimeError::Custom("max recursion depth exceeded".to_string()));
+                }
+                
+                // Check arity
+                if decl.params.len() != args.len() {
+                    return Err(RuntimeError::ArityMismatch {
+                        function: decl.name.clone(),
+                        expected: decl.params.len(),
+                        got: args.len(),
+                    });
+                }
+                
+                // Create new environment with closure
+                let mut new_env = if let Some(ref closure_vars) = closure {
+                    let mut env = Environment::new();
+                    for (name, value) in closure_vars {
+                        env.define(name.clone(), value.clone());
+                    }
+                    env
+                } else {
+                    Environment::with_parent(self.globals.clone())
+                };
+                
+                // Bind parameters
+                for (param, arg) in decl.params.iter().zip(args.iter()) {
+                    new_env.define(param.name.clone(), widen(arg.clone(), param.type_annotation.as_ref()));
+                }
+                
+                self.trace_call(&decl.name, args);
+                self.call_depth += 1;
+                let started = self.profile_enter(&decl.name);
+                
+                // Execute function body
+                let previous = std::mem::replace(&mut self.environment, new_env);
+                let result = self.execute_body(&decl.body)
+                    .map(|value| widen(value, decl.return_type.as_ref()));
+                
+                // Restore environment
+                self.environment = previous;
+                self.call_depth -= 1;
+                self.profile_exit(&decl.name, started);
+                
+                if let Ok(ref value) = result {
+                    self.trace_return(&decl.name, value);
+                }
+                result
+            }
+        }
+    }
+
+    /// Execute the statements of a function body, returning the value of
+    /// the `return` that ended it, or else of the last statement
+    fn execute_body(&mut self, body: &[Statement]) -> Result<Value, RuntimeError> {
+        let result = self.execute_statements(body);
+        let control = self.control.take();
+        let value = result?;
+        match control {
+            Some(ControlFlow::Return(value)) => Ok(value),
+            Some(ControlFlow::Break | ControlFlow::Continue) => Err(RuntimeError::Custom(
+                "'break' or 'continue' outside of a loop".to_string()
+            )),
+            None => Ok(value),
+        }
+    }
+
+    /// Execute statements in order until one raises control flow,
+    /// returning the last value
+    fn execute_statements(&mut self, stmts: &[Statement]) -> Result<Value, RuntimeError> {
+        let mut result = Value::Unit;
+        for stmt in stmts {
+            result = match self.execute_statement(stmt) {
+                Ok(value) => value,
+                Err(e) => {
+                    if self.keep_error_scope && self.error_scope.is_none() {
+                        self.error_scope = Some(self.environment.clone());
+                    }
+                    return Err(e);
+                }
+            };
+            if self.control.is_some() {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Count a call to `name`, returning when it started if profiling
+    fn profile_enter(&mut self, name: &str) -> Option<Instant> {
+        let entry = self.profile.as_mut()?.entry(name.to_string()).or_default();
+        entry.calls += 1;
+        entry.active += 1;
+        Some(Instant::now())
+    }
+
+    /// Add the time of a finished call to `name`, unless it was recursive
+    fn profile_exit(&mut self, name: &str, started: Option<Instant>) {
+        let (Some(profile), Some(started)) = (self.profile.as_mut(), started) else {
+            return;
+        };
+        if let Some(entry) = profile.get_mut(name) {
+            entry.active -= 1;
+            if entry.active == 0 {
+                entry.total += started.elapsed();
+            }
+        }
+    }
+
+    /// Write `args` separated by spaces to the output, followed by `end`
+    fn write_output(&mut self, args: &[Value], end: &str) -> Result<Value, RuntimeError> {
+        let text: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut text = format!("{}{}", text.join(" "), end);
+        // Keep only what fits in the lines left, cutting where the first line
+        // over the limit would start
+        let mut over_limit = false;
+        if let Some(limit) = self.output_limit {
+            for (i, c) in text.char_indices() {
+                if self.output_column == 0 {
+                    if self.output_lines == limit {
+                        over_limit = true;
+                        text.truncate(i);
+                        break;
+                    }
+                    self.output_lines += 1;
+                }
+                self.output_column = match c {
+                    '\n' => 0,
+                    _ => (self.output_column + 1) % OUTPUT_LINE_WIDTH,
+                };
+            }
+        }
+
+        let mut written = self.output.write_all(text.as_bytes());
+        if self.line_buffered || self.halt_on_first_log || over_limit {
+            written = written.and_then(|()| self.output.flush());
+        }
+        written.map_err(|e| RuntimeError::Custom(format!("Cannot write output: {}", e)))?;
+        if let (true, Some(limit)) = (over_limit, self.output_limit) {
+            return Err(RuntimeError::OutputLimit(limit));
+        }
+        if self.halt_on_first_log {
+            return Err(RuntimeError::Halted(self.current_span));
+        }
+        Ok(Value::Unit)
+    }
+
+    /// Write a trace line for entering a function
+    fn trace_call(&mut self, name: &str, args: &[Value]) {
+        let indent = "  ".repeat(self.call_depth);
+        if let Some(out) = self.trace.as_mut() {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let _ = writeln!(out, "{}-> {}({})", indent, name, args.join(", "));
+        }
+    }
+
+    /// Write a trace line for returning from a function
+    fn trace_return(&mut self, name: &str, value: &Value) {
+        let indent = "  ".repeat(self.call_depth);
+        if let Some(out) = self.trace.as_mut() {
+            let _ = writeln!(out, "{}<- {} = {}", indent, name, value);
+        }
+    }
+
+    /// Execute a statement
+    fn execute_statement(&mut self, stmt: &Statement) -> Result<Value, RuntimeError> {
+        if let Some(executed) = self.coverage.as_mut() {
+            *executed.entry(stmt.span.line).or_insert(0) += 1;
+        }
+        self.current_span = stmt.span;
+        match &stmt.kind {
+            StatementKind::VariableDecl { name, type_annotation, initializer, mutable } => {
+                let value = widen(self.evaluate(initializer)?, type_annotation.as_ref());
+                if *mutable {
+                    self.environment.define(name.clone(), value);
+                } else {
+                    self.environment.define_constant(name.clone(), value);
+                }
+                Ok(Value::Unit)
+            }
+            StatementKind::Expression(expr) => {
+                self.evaluate(expr)
+            }
+            StatementKind::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Unit,
+                };
+                self.control = Some(ControlFlow::Return(value.clone()));
+                Ok(value)
+            }
+            StatementKind::Break => {
+                self.control = Some(ControlFlow::Break);
+                Ok(Value::Unit)
+            }
+            StatementKind::Continue => {
+                self.control = Some(ControlFlow::Continue);
+                Ok(Value::Unit)
+            }
+            StatementKind::For { variable, iterable, guard, body } => {
+                let iter_value = self.evaluate(iterable)?;
+                let items = match iter_value {
+                    Value::List(items) => items,
+                    _ => return Err(RuntimeError::TypeError(
+                        "For loop requires a list".to_string()
+                    )),
+                };
+                
+                for item in items.iter().cloned() {
+                    // Create new scope for loop body
+                    self.environment.push_scope();
+                    
+                    // Bind loop variable
+                    self.environment.define(variable.clone(), item);
+                    
+                    // Check guard if present
+                    if let Some(ref guard_expr) = guard {
+                        let guard_value = self.evaluate(guard_expr)?;
+                        if !guard_value.is_truthy() {
+                            self.environment.pop_scope();
+                            continue;
+                        }
+                    }
+                    
+                    // Execute body
+                    let result = self.execute_statements(body);
+                    
+                    // Leave the loop body's scope, keeping assignments to
+                    // variables outside it
+                    self.environment.pop_scope();
+                    result?;
+                    
+                    match self.control {
+                        Some(ControlFlow::Break) => {
+                            self.control = None;
+                            break;
+                        }
+                        Some(ControlFlow::Continue) => self.control = None,
+                        // A return keeps unwinding to the enclosing function
+                        Some(ControlFlow::Return(_)) => break,
+                        None => {}
+                    }
+                }
+                
+                // A loop is a statement and always evaluates to Unit
+                Ok(Value::Unit)
+            }
+            StatementKind::Assignment { target, value } => {
+                let val = self.evaluate(value)?;
+                
+                // The parser only allows variables and elements of lists
+                // held in variables as targets
+                if let Expression::Identifier(name) = target {
+                    self.environment.assign(name, val)?;
+                } else if let Expression::IndexAccess { object, index } = target {
+                    let idx = self.evaluate(index)?.as_integer()?;
+                    let Expression::Identifier(name) = object.as_ref() else {
+                        return Err(RuntimeError::InvalidOperation("Invalid assignment target".to_string()));
+                    };
+                    
+                    // Write through to the variable holding the list
+                    let items = self.environment.get_mut(name)?.as_list_mut()?;
+                    if idx < 0 || idx as usize >= items.len() {
+                        return Err(RuntimeError::IndexOutOfBounds {
+                            index: idx,
+                            len: items.len(),
+                        });
+                    }
+                    items[idx as usize] = val;
+                }
+                
+                Ok(Value::Unit)
+            }
+        }
+    }
+
+    /// Evaluate a subexpression, or `None` if it started unwinding a
+    /// `return`, in which case the enclosing expression must stop too
+    fn evaluate_operand(&mut self, expr: &Expression) -> Result<Option<Value>, RuntimeError> {
+        let value = self.evaluate(expr)?;
+        Ok(if self.control.is_some() { None } else { Some(value) })
+    }
+
+    /// Evaluate call arguments onto `values`, expanding each `...list` into
+    /// its elements, and named arguments onto `named`; `false` if an
+    /// argument started unwinding a `return`
+    fn evaluate_arguments(
+        &mut self,
+        args: &[Expression],
+        values: &mut Vec<Value>,
+        named: &mut Vec<(String, Value)>,
+    ) -> Result<bool, RuntimeError> {
+        for arg in args {
+            let (expr, spread) = match arg {
+                Expression::Spread(inner) => (inner.as_ref(), true),
+                Expression::NamedArg { value, .. } => (value.as_ref(), false),
+                _ => (arg, false),
+            };
+            let Some(value) = self.evaluate_operand(expr)? else {
+                return Ok(false);
+            };
+            if let Expression::NamedArg { name, .. } = arg {
+                named.push((name.clone(), value));
+                continue;
+            }
+            if !spread {
+                values.push(value);
+                continue;
+            }
+            match value {
+                Value::List(items) => values.extend(items.iter().cloned()),
+                _ => return Err(RuntimeError::TypeError(format!("Cannot spread {}", value.type_name()))),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Put named arguments in the places of the parameters they name, after
+    /// the positional `args`
+    fn bind_named_arguments(
+        func: &FunctionValue,
+        args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        if named.is_empty() {
+            return Ok(args);
+        }
+        let FunctionValue::UserDefined { decl, .. } = func else {
+            return Err(RuntimeError::TypeError("Builtins don't take named arguments".to_string()));
+        };
+        let mut slots: Vec<Option<Value>> = args.into_iter().map(Some).collect();
+        if slots.len() < decl.params.len() {
+            slots.resize(decl.params.len(), None);
+        }
+        for (name, value) in named {
+            let Some(index) = decl.params.iter().position(|param| param.name == name) else {
+                return Err(RuntimeError::Custom(format!("{} has no parameter called {}", decl.name, name)));
+            };
+            if slots[index].replace(value).is_some() {
+                return Err(RuntimeError::Custom(format!("{} is given argument {} twice", decl.name, name)));
+            }
+        }
+        slots.into_iter()
+            .zip(&decl.params)
+            .map(|(slot, param)| slot.ok_or_else(|| {
+                RuntimeError::Custom(format!("{} is missing argument {}", decl.name, param.name))
+            }))
+            .collect()
+    }
+
+    /// Evaluate an expression
+    fn evaluate(&mut self, expr: &Expression) -> Result<Value, RuntimeError> {
+        match expr {
+            Expression::Literal(lit) => self.evaluate_literal(lit),
+            Expression::Identifier(name) => {
+                self.environment.get(name)
+            }
+            Expression::Binary { left, op, right } => {
+                let Some(left_val) = self.evaluate_operand(left)? else {
+                    return Ok(Value::Unit);
+                };
+                let Some(right_val) = self.evaluate_operand(right)? else {
+                    return Ok(Value::Unit);
+                };
+                self.evaluate_binary_op(&left_val, op, &right_val)
+            }
+            Expression::Unary { op, expr } => {
+                let Some(val) = self.evaluate_operand(expr)? else {
+                    return Ok(Value::Unit);
+                };
+                self.evaluate_unary_op(op, &val)
+            }
+            Expression::Call { callee, args } => {
+                let Some(func_val) = self.evaluate_operand(callee)? else {
+                    return Ok(Value::Unit);
+                };
+                let mut arg_vals = Vec::with_capacity(args.len());
+                let mut named = Vec::new();
+                if !self.evaluate_arguments(args, &mut arg_vals, &mut named)? {
+                    return Ok(Value::Unit);
+                }
+                
+                match func_val {
+                    Value::Function(func) => {
+                        let arg_vals = Self::bind_named_arguments(&func, arg_vals, named)?;
+                        self.execute_function(&func, &arg_vals)
+                    }
+                    _ => Err(RuntimeError::TypeError("Not a function".to_string())),
+                }
+            }
+            Expression::Pipe { left, right } => {
+                let Some(left_val) = self.evaluate_operand(left)? else {
+                    return Ok(Value::Unit);
+                };
+                
+                // Pipe left value as first argument to right function
+                match right.as_ref() {
+                    Expression::Call { callee, args } => {
+                        let Some(func_val) = self.evaluate_operand(callee)? else {
+                            return Ok(Value::Unit);
+                        };
+                        let mut arg_vals = vec![left_val];
+                        let mut named = Vec::new();
+                        if !self.evaluate_arguments(args, &mut arg_vals, &mut named)? {
+                            return Ok(Value::Unit);
+                        }
+                        
+                        match func_val {
+                            Value::Function(func) => {
+                                let arg_vals = Self::bind_named_arguments(&func, arg_vals, named)?;
+                                self.execute_function(&func, &arg_vals)
+                            }
+                            _ => Err(RuntimeError::TypeError("Not a function".to_string())),
+                        }
+                    }
+                    Expression::Identifier(name) => {
+                        self.call_function(name, &[left_val])
+                    }
+                    _ => Err(RuntimeError::TypeError(
+                        "Right side of pipe must be a function".to_string()
+                    )),
+                }
+            }
+            Expression::Match { expr, arms } => {
+                let Some(val) = self.evaluate_operand(expr)? else {
+                    return Ok(Value::Unit);
+                };
+                
+                for arm in arms {
+                    let mut bindings = Vec::new();
+                    if self.match_pattern(&val, &arm.pattern, &mut bindings)? {
+                        // Evaluate the arm with the pattern's bindings in scope
+                        self.environment.push_scope();
+                        for (name, value) in bindings {
+                            self.environment.define(name, value);
+                        }
+                        let result = self.evaluate(&arm.expr);
+                        self.environment.pop_scope();
+                        return result;
+                    }
+                }
+                
+                Err(RuntimeError::Custom(
+                    format!("no match arm for value {} ({})", val, val.type_name())
+                ))
+            }
+            Expression::Block(stmts) => {
+                self.environment.push_scope();
+                let result = self.execute_statements(stmts);
+                self.environment.pop_scope();
+                result
+            }
+            Expression::If { condition, then_branch, else_branch } => {
+                let Some(cond_val) = self.evaluate_operand(condition)? else {
+                    return Ok(Value::Unit);
+                };
+                
+                if cond_val.is_truthy() {
+                    self.evaluate(then_branch)
+                } else if let Some(else_expr) = else_branch {
+                    self.evaluate(else_expr)
+                } else {
+                    Ok(Value::Unit)
+                }
+            }
+            Expression::FieldAccess { object, field } => {
+                let Some(obj_val) = self.evaluate_operand(object)? else {
+                    return Ok(Value::Unit);
+                };
+                
+                match obj_val {
+                    Value::Record(fields) => {
+                        fields.get(field)
+                            .cloned()
+                            .ok_or_else(|| RuntimeError::Custom(
+                                format!("Field '{}' not found", field)
+                            ))
+                    }
+                    _ => Err(RuntimeError::TypeError("Not a record".to_string())),
+                }
+            }
+            Expression::IndexAccess { object, index } => {
+                let Some(obj_val) = self.evaluate_operand(object)? else {
+                    return Ok(Value::Unit);
+                };
+                let Some(idx_val) = self.evaluate_operand(index)? else {
+                    return Ok(Value::Unit);
+                };
+                
+                match obj_val {
+                    Value::List(items) => {
+                        let idx = idx_val.as_integer()?;
+                        if idx < 0 || idx as usize >= items.len() {
+                            return Err(RuntimeError::IndexOutOfBounds {
+                                index: idx,
+                                len: items.len(),
+                            });
+                        }
+                        Ok(items[idx as usize].clone())
+                    }
+                    Value::String(s) => {
+                        let idx = idx_val.as_integer()?;
+                        if idx < 0 || idx as usize >= s.len() {
+                            return Err(RuntimeError::IndexOutOfBounds {
+                                index: idx,
+                                len: s.len(),
+                            });
+                        }
+                        Ok(Value::String(s.chars().nth(idx as usize).unwrap().to_string()))
+                    }
+                    _ => Err(RuntimeError::TypeError("Not indexable".to_string())),
+                }
+            }
+            Expression::SliceAccess { object, start, end } => {
+                let Some(obj_val) = self.evaluate_operand(object)? else {
+                    return Ok(Value::Unit);
+                };
+                let mut bounds = [None, None];
+                for (bound, expr) in bounds.iter_mut().zip([start, end]) {
+                    if let Some(expr) = expr {
+                        let Some(value) = self.evaluate_operand(expr)? else {
+                            return Ok(Value::Unit);
+                        };
+                        *bound = Some(value.as_integer()?);
+                    }
+                }
+                let [start, end] = bounds;
+                
+                match obj_val {
+                    Value::List(items) => {
+                        Ok(Value::list(items[slice_range(start, end, items.len())].to_vec()))
+                    }
+                    Value::String(s) => {
+                        let chars: Vec<char> = s.chars().collect();
+                        Ok(Value::String(chars[slice_range(start, end, chars.len())].iter().collect()))
+                    }
+                    _ => Err(RuntimeError::TypeError(format!("Cannot slice {}", obj_val.type_name()))),
+                }
+            }
+            Expression::Lambda { params, body } => {
+                Ok(make_lambda(params.clone(), (**body).clone(), self.environment.snapshot()))
+            }
+            Expression::Partial { callee, args } => {
+                // Evaluate the callee and fixed arguments now, holding their
+                // values in the closure under names source code can't write
+                let Some(func) = self.evaluate_operand(callee)? else {
+                    return Ok(Value::Unit);
+                };
+                let mut held = vec![("$f".to_string(), func)];
+                let mut call_args = Vec::with_capacity(args.len());
+                for arg in args {
+                    if arg.is_placeholder() {
+                        call_args.push(arg.clone());
+                        continue;
+                    }
+                    let name = format!("$a{}", held.len() - 1);
+                    let slot = Box::new(Expression::Identifier(name.clone()));
+                    let (expr, call_arg) = match arg {
+                        Expression::Spread(inner) => (inner.as_ref(), Expression::Spread(slot)),
+                        Expression::NamedArg { name, value } => {
+                            (value.as_ref(), Expression::NamedArg { name: name.clone(), value: slot })
+                        }
+                        _ => (arg, *slot),
+                    };
+                    let Some(value) = self.evaluate_operand(expr)? else {
+                        return Ok(Value::Unit);
+                    };
+                    held.push((name, value));
+                    call_args.push(call_arg);
+                }
+
+                let Expression::Lambda { params, body } =
+                    Expression::partial_lambda(Expression::Identifier("$f".to_string()), call_args)
+                else {
+                    unreachable!("partial_lambda builds a lambda");
+                };
+                let mut closure = self.environment.snapshot();
+                closure.extend(held);
+                Ok(make_lambda(params, *body, closure))
+            }
+            Expression::Claim(expr) => {
+                // In the interpreter, claim is essentially a no-op
+                // It marks ownership transfer but doesn't change behavior
+                self.evaluate(expr)
+            }
+            Expression::Spread(_) => Err(RuntimeError::Custom(
+                "'...' can only spread a list into call arguments".to_string()
+            )),
+            Expression::NamedArg { name, .. } => Err(RuntimeError::Custom(
+                format!("Named argument {} outside a call", name)
+            )),
+            Expression::Cast { expr, target } => {
+                let Some(value) = self.evaluate_operand(expr)? else {
+                    return Ok(Value::Unit);
+                };
+                cast(value, target)
+            }
+        }
+    }
+
+    /// Evaluate a literal
+    fn evaluate_literal(&mut self, lit: &Literal) -> Result<Value, RuntimeError> {
+        match lit {
+            Literal::Integer(n) => Ok(Value::Integer(*n)),
+            Literal::Float(n) => Ok(Value::Float(*n)),
+            Literal::String(s) => Ok(Value::String(s.clone())),
+            Literal::Boolean(b) => Ok(Value::Boolean(*b)),
+            Literal::List(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    let Some(value) = self.evaluate_operand(item)? else {
+                        return Ok(Value::Unit);
+                    };
+                    values.push(value);
+                }
+                Ok(Value::list(values))
+            }
+            Literal::Record(fields) => {
+                let mut map = IndexMap::new();
+                for (name, expr) in fields {
+                    let Some(value) = self.evaluate_operand(expr)? else {
+                        return Ok(Value::Unit);
+                    };
+                    map.insert(name.clone(), value);
+                }
+                Ok(Value::Record(map))
+            }
+        }
+    }
+
+    /// Evaluate binary operation
+    fn evaluate_binary_op(&mut self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, RuntimeError> {
+        if self.strict_arithmetic {
+            check_strict_arithmetic(left, op, right)?;
+        }
+        match op {
+            BinaryOp::Add => self.add_values(left, right),
+            BinaryOp::Subtract => self.subtract_values(left, right),
+            BinaryOp::Multiply => self.multiply_values(left, right),
+            BinaryOp::Divide => self.divide_values(left, right),
+            BinaryOp::Modulo => self.modulo_values(left, right),
+            BinaryOp::Equal => Ok(Value::Boolean(left == right)),
+            BinaryOp::NotEqual => Ok(Value::Boolean(left != right)),
+            BinaryOp::Less => self.compare_values(left, right, |c| c == std::cmp::Ordering::Less),
+            BinaryOp::LessEq => self.compare_values(left, right, |c| {
+                c == std::cmp::Ordering::Less || c == std::cmp::Ordering::Equal
+            }),
+            BinaryOp::Greater => self.compare_values(left, right, |c| c == std::cmp::Ordering::Greater),
+            BinaryOp::GreaterEq => self.compare_values(left, right, |c| {
+                c == std::cmp::Ordering::Greater || c == std::cmp::Ordering::Equal
+            }),
+            BinaryOp::In => Self::contains_value(left, right),
+        }
+    }
+
+    /// `needle in haystack`: list membership or record key presence
+    fn contains_value(needle: &Value, haystack: &Value) -> Result<Value, RuntimeError> {
+        match (needle, haystack) {
+            (_, Value::List(items)) => Ok(Value::Boolean(items.contains(needle))),
+            // A symbol of its own, so testing for a name doesn't intern it
+            (Value::String(key), Value::Record(fields)) => Ok(Value::Boolean(fields.contains_key(&Symbol::new(key)))),
+            (_, Value::Record(_)) => Err(RuntimeError::TypeError(
+                format!("Record keys are String, not {}", needle.type_name())
+            )),
+            _ => Err(RuntimeError::TypeError(
+                format!("Cannot test membership in {}", haystack.type_name())
+            )),
+        }
+    }
+
+    /// Add two values
+    fn add_values(&mut self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+        if let Some(hook) = self.operator_hook(left, right, "__add__") {
+            return self.call_operator_hook(&hook, left, right);
+        }
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a + b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(*a as f64 + b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a + *b as f64)),
+            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+            (Value::List(a), Value::List(b)) => {
+                let mut result = Vec::with_capacity(a.len() + b.len());
+                result.extend(a.iter().cloned());
+                result.extend(b.iter().cloned());
+                Ok(Value::list(result))
+            }
+            (Value::Complex(..), _) | (_, Value::Complex(..)) => {
+                let ((a, b), (c, d)) = complex_operands(left, right, "add")?;
+                Ok(Value::Complex(a + c, b + d))
+            }
+            _ => Err(RuntimeError::TypeError(
+                format!("Cannot add {} and {}", left.type_name(), right.type_name())
+            )),
+        }
+    }
+
+    /// Subtract two values
+    fn subtract_values(&mut self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+        if let Some(hook) = self.operator_hook(left, right, "__sub__") {
+            return self.call_operator_hook(&hook, left, right);
+        }
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a - b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(*a as f64 - b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a - *b as f64)),
+            (Value::Complex(..), _) | (_, Value::Complex(..)) => {
+                let ((a, b), (c, d)) = complex_operands(left, right, "subtract")?;
+                Ok(Value::Complex(a - c, b - d))
+            }
+            _ => Err(RuntimeError::TypeError(
+                format!("Cannot subtract {} and {}", left.type_name(), right.type_name())
+            )),
+        }
+    }
+
+    /// The operator method `name` of `left`, if both operands are records
+    /// and it has one
+    fn operator_hook(&self, left: &Value, right: &Value, name: &str) -> Option<Value> {
+        let (Value::Record(fields), Value::Record(_)) = (left, right) else {
+            return None;
+        };
+        fields.get(&self.symbols.lookup(name)?).cloned()
+    }
+
+    /// Call a record's operator method (`__add__`, `__sub__`) with both operands
+    fn call_operator_hook(&mut self, hook: &Value, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+        match hook {
+            Value::Function(func) => self.execute_function(func, &[left.clone(), right.clone()]),
+            _ => Err(RuntimeError::TypeError(
+                format!("Operator method must be a function, got {}", hook.type_name())
+            )),
+        }
+    }
+
+    /// Multiply two values
+    fn multiply_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a * b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
+            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(*a as f64 * b)),
+            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a * *b as f64)),
+            // Repetition: "ab" * 3, [0] * 3, and the count-first forms
+            (Value::String(s), Value::Integer(n)) | (Value::Integer(n), Value::String(s)) => {
+                Ok(Value::String(s.repeat(repeat_count(*n, s.len(), "String")?)))
+            }
+            (Value::List(items), Value::Integer(n)) | (Value::Integer(n), Value::List(items)) => {
+                let n = repeat_count(*n, items.len(), "List")?;
+                Ok(Value::list((0..n).flat_map(|_| items.iter().cloned()).collect()))
+            }
+            (Value::Complex(..), _) | (_, Value::Complex(..)) => {
+                let ((a, b), (c, d)) = complex_operands(left, right, "multiply")?;
+                Ok(Value::Complex(a * c - b * d, a * d + b * c))
+            }
+            _ => Err(RuntimeError::TypeError(
+                format!("Cannot multiply {} and {}", left.type_name(), right.type_name())
+            )),
+        }
+    }
+
+    /// Divide two values
+    fn divide_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                if *b == 0 {
+                    return Err(RuntimeError::Custom("Division by zero".to_string()));
+                }
+                Ok(Value::Integer(a / b))
+            }
+            (Value::Float(a), Value::Float(b)) => {
+                if *b == 0.0 {
+                    return Err(RuntimeError::Custom("Division by zero".to_string()));
+                }
+                Ok(Value::Float(a / b))
+            }
+            (Value::Integer(a), Value::Float(b)) => {
+                if *b == 0.0 {
+                    return Err(RuntimeError::Custom("Division by zero".to_string()));
+                }
+                Ok(Value::Float(*a as f64 / b))
+            }
+            (Value::Float(a), Value::Integer(b)) => {
+                if *b == 0 {
+                    return Err(RuntimeError::Custom("Division by zero".to_string()));
+                }
+                Ok(Value::Float(a / *b as f64))
+            }
+            (Value::Complex(..), _) | (_, Value::Complex(..)) => {
+                let ((a, b), (c, d)) = complex_operands(left, right, "divide")?;
+                let denominator = c * c + d * d;
+                if denominator == 0.0 {
+                    return Err(RuntimeError::Custom("Division by zero".to_string()));
+                }
+                Ok(Value::Complex((a * c + b * d) / denominator, (b * c - a * d) / denominator))
+            }
+            _ => Err(RuntimeError::TypeError(
+                format!("Cannot divide {} and {}", left.type_name(), right.type_name())
+            )),
+        }
+    }
+
+    /// Modulo two values
+    fn modulo_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                if *b == 0 {
+                    return Err(RuntimeError::Custom("Modulo by zero".to_string()));
+                }
+                Ok(Value::Integer(a % b))
+            }
+            _ => Err(RuntimeError::TypeError(
+                format!("Cannot modulo {} and {}", left.type_name(), right.type_name())
+            )),
+        }
+    }
+
+    /// Compare two values. Ordering predicates involving NaN are false,
+    /// as NaN is unordered
+    fn compare_values<F>(&self, left: &Value, right: &Value, pred: F) -> Result<Value, RuntimeError>
+    where
+        F: Fn(std::cmp::Ordering) -> bool,
+    {
+        let ordering = partial_compare(left, right)?;
+        Ok(Value::Boolean(ordering.is_some_and(pred)))
+    }
+
+    /// Evaluate unary operation
+    fn evaluate_unary_op(&self, op: &UnaryOp, val: &Value) -> Result<Value, RuntimeError> {
+        match op {
+            UnaryOp::Negate => {
+                match val {
+                    Value::Integer(n) => Ok(Value::Integer(-n)),
+                    Value::Float(n) => Ok(Value::Float(-n)),
+                    _ => Err(RuntimeError::TypeError(
+                        format!("Cannot negate {}", val.type_name())
+                    )),
+                }
+            }
+            UnaryOp::Not => Ok(Value::Boolean(!val.is_truthy())),
+        }
+    }
+
+    /// Check if a value matches a pattern
+    fn match_pattern(
+        &self,
+        value: &Value,
+        pattern: &Pattern,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> Result<bool, RuntimeError> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+            Pattern::Literal(lit) => {
+                let lit_val = match lit {
+                    Literal::Integer(n) => Value::Integer(*n),
+                    Literal::Float(n) => Value::Float(*n),
+                    Literal::String(s) => Value::String(s.clone()),
+                    Literal::Boolean(b) => Value::Boolean(*b),
+                    _ => return Err(RuntimeError::Custom(
+                        "Complex literals in patterns not yet supported".to_string()
+                    )),
+                };
+                Ok(value == &lit_val)
+            }
+            Pattern::Identifier(name) => {
+                bindings.push((name.clone(), value.clone()));
+                Ok(true)
+            }
+            Pattern::Range(start, end) => {
+                // Simplified range matching
+                let start_val = match start.as_ref() {
+                    Pattern::Literal(Literal::Integer(n)) => *n,
+                    _ => return Err(RuntimeError::Custom(
+                        "Range patterns must use integer literals".to_string()
+                    )),
+                };
+                let end_val = match end.as_ref() {
+                    Pattern::Literal(Literal::Integer(n)) => *n,
+                    _ => return Err(RuntimeError::Custom(
+                        "Range patterns must use integer literals".to_string()
+                    )),
+                };
+                
+                match value {
+                    Value::Integer(n) => Ok(*n >= start_val && *n <= end_val),
+                    _ => Ok(false),
+                }
+            }
+            Pattern::Tuple(_) => Err(RuntimeError::Custom(
+                "Tuple patterns not yet supported".to_string()
+            )),
+            Pattern::Record(field_patterns) => {
+                // Fields not named in the pattern are ignored
+                let Value::Record(fields) = value else {
+                    return Ok(false);
+                };
+                for (name, field_pattern) in field_patterns {
+                    match fields.get(name) {
+                        Some(field) if self.match_pattern(field, field_pattern, bindings)? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            Pattern::List(element_patterns, rest) => {
+                // Exact length without a rest binding, minimum length with one
+                let Value::List(items) = value else {
+                    return Ok(false);
+                };
+                let fits = match rest {
+                    Some(_) => items.len() >= element_patterns.len(),
+                    None => items.len() == element_patterns.len(),
+                };
+                if !fits {
+                    return Ok(false);
+                }
+                for (item, item_pattern) in items.iter().zip(element_patterns) {
+                    if !self.match_pattern(item, item_pattern, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                if let Some(rest) = rest {
+                    let remaining = items[element_patterns.len()..].to_vec();
+                    bindings.push((rest.clone(), Value::list(remaining)));
+                }
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Results of the `ensure` assertions in one verify block
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyOutcome {
+    pub name: String,
+    /// Whether each `ensure` held, in order
+    pub passed: Vec<bool>,
+}
+
+/// A satisfying assignment found by a solve block search
+struct Solution {
+    /// Value of the block's return expression
+    value: Value,
+    /// Value of the objective, if the block has one
+    score: Option<f64>,
+}
+
+/// Whether a solve block searches over any `let x in domain` choices
+fn has_choices(solve: &SolveBlock) -> bool {
+    solve.constraints.iter().any(|c| matches!(c, Constraint::Choice { .. }))
+}
+
+/// Convert a byte offset from `str::find` into a char index, or -1 if absent
+fn char_index(s: &str, byte_offset: Option<usize>) -> i64 {
+    match byte_offset {
+        Some(offset) => s[..offset].chars().count() as i64,
+        None => -1,
+    }
+}
+
+/// The indices `[start:end]` covers in a sequence of `len` items. Negative
+/// bounds count back from the end, bounds outside the sequence are clamped
+/// to it, and an end before the start gives an empty range.
+fn slice_range(start: Option<i64>, end: Option<i64>, len: usize) -> std::ops::Range<usize> {
+    let resolve = |bound: i64| {
+        let bound = if bound < 0 { bound + len as i64 } else { bound };
+        bound.clamp(0, len as i64) as usize
+    };
+    let start = start.map_or(0, resolve);
+    let end = end.map_or(len, resolve);
+    start..end.max(start)
+}
+
+/// Convert `value` for `value as target`: between Int and Float (Floats
+/// are truncated), or from a number to its String form
+fn cast(value: Value, target: &TypeAnnotation) -> Result<Value, RuntimeError> {
+    let target_name = match target {
+        TypeAnnotation::Named(name) => name.as_str(),
+        _ => "",
+    };
+    match (value, target_name) {
+        // A cast to the value's own type leaves it as it is
+        (value, name) if value.type_name() == name => Ok(value),
+        (Value::Integer(n), "Float") => Ok(Value::Float(n as f64)),
+        // `as` saturates in Rust, so reject NaN, infinities and out-of-range values
+        (Value::Float(f), "Int") if f.is_finite() && f.trunc() >= i64::MIN as f64 && f.trunc() < i64::MAX as f64 => {
+            Ok(Value::Integer(f.trunc() as i64))
+        }
+        (value @ (Value::Integer(_) | Value::Float(_) | Value::Complex(..)), "String") => {
+            Ok(Value::String(value.to_string()))
+        }
+        (value, _) => Err(RuntimeError::InvalidOperation(
+            format!("Cannot cast {} ({}) to {}", value, value.type_name(), target)
+        )),
+    }
+}
+
+/// Convert a number to the wider numeric type it's annotated with, which
+/// the checker allows, so `let x: Float = 1` holds `1.0` and divides as a
+/// Float
+fn widen(value: Value, annotation: Option<&TypeAnnotation>) -> Value {
+    let Some(TypeAnnotation::Named(name)) = annotation else {
+        return value;
+    };
+    match (value, name.as_str()) {
+        (Value::Integer(n), "Float") => Value::Float(n as f64),
+        (Value::Integer(n), "Complex") => Value::Complex(n as f64, 0.0),
+        (Value::Float(f), "Complex") => Value::Complex(f, 0.0),
+        (value, _) => value,
+    }
+}
+
+/// Split text into a list of lines for `split_lines`/`read_lines`
+fn lines_of(text: &str) -> Value {
+    Value::list(text.lines().map(|line| Value::String(line.to_string())).collect())
+}
+
+/// Order two numbers or two strings; `None` when either is NaN
+fn partial_compare(left: &Value, right: &Value) -> Result<Option<std::cmp::Ordering>, RuntimeError> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Some(a.cmp(b))),
+        (Value::Float(a), Value::Float(b)) => Ok(a.partial_cmp(b)),
+        (Value::Integer(a), Value::Float(b)) => Ok((*a as f64).partial_cmp(b)),
+        (Value::Float(a), Value::Integer(b)) => Ok(a.partial_cmp(&(*b as f64))),
+        (Value::String(a), Value::String(b)) => Ok(Some(a.cmp(b))),
+        _ => Err(RuntimeError::TypeError(
+            format!("Cannot compare {} and {}", left.type_name(), right.type_name())
+        )),
+    }
+}
+
+/// Total order used by `sort`: numbers ascending with NaN last
+fn sort_order(left: &Value, right: &Value) -> std::cmp::Ordering {
+    let is_nan = |v: &Value| matches!(v, Value::Float(f) if f.is_nan());
+    match partial_compare(left, right) {
+        Ok(Some(ordering)) => ordering,
+        _ => is_nan(left).cmp(&is_nan(right)),
+    }
+}
+
+/// Real and imaginary parts of a complex number
+type ComplexParts = (f64, f64);
+
+/// Both operands of complex arithmetic as (real, imaginary) pairs, with
+/// Int and Float operands taken as real numbers
+fn complex_operands(left: &Value, right: &Value, verb: &str) -> Result<(ComplexParts, ComplexParts), RuntimeError> {
+    let parts = |value: &Value| match value {
+        Value::Complex(re, im) => Some((*re, *im)),
+        Value::Integer(n) => Some((*n as f64, 0.0)),
+        Value::Float(x) => Some((*x, 0.0)),
+        _ => None,
+    };
+    match (parts(left), parts(right)) {
+        (Some(a), Some(b)) => Ok((a, b)),
+        _ => Err(RuntimeError::TypeError(
+            format!("Cannot {} {} and {}", verb, left.type_name(), right.type_name())
+        )),
+    }
+}
+
+/// Under strict arithmetic, reject an arithmetic operator applied to an
+/// Int and a Float
+fn check_strict_arithmetic(left: &Value, op: &BinaryOp, right: &Value) -> Result<(), RuntimeError> {
+    let arithmetic = matches!(
+        op,
+        BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo
+    );
+    let mixed = matches!(
+        (left, right),
+        (Value::Integer(_), Value::Float(_)) | (Value::Float(_), Value::Integer(_))
+    );
+    if arithmetic && mixed {
+        return Err(RuntimeError::TypeError(format!(
+            "Cannot apply `{}` to {} and {} with strict arithmetic; convert with to_float or to_int",
+            op, left.type_name(), right.type_name()
+        )));
+    }
+    Ok(())
+}
+
+/// Greatest common divisor by Euclid's algorithm
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Result of an integer builtin, an error if it doesn't fit in an Int
+fn int_result(n: u64, name: &str, a: i64, b: i64) -> Result<Value, RuntimeError> {
+    i64::try_from(n).map(Value::Integer).map_err(|_| overflow(name, a, b))
+}
+
+fn overflow(name: &str, a: i64, b: i64) -> RuntimeError {
+    RuntimeError::InvalidOperation(format!("{}({}, {}) overflows Int", name, a, b))
+}
+
+/// A lambda of `params` returning `body`, closing over `closure`
+fn make_lambda(params: Vec<Parameter>, body: Expression, closure: HashMap<String, Value>) -> Value {
+    let decl = FunctionDecl {
+        mode: FunctionMode::Proto,
+        name: "<lambda>".to_string(),
+        type_params: Vec::new(),
+        params,
+        return_type: None,
+        body: vec![Statement::new(StatementKind::Expression(body), Span::default())],
+        doc: None,
+    };
+    Value::Function(FunctionValue::UserDefined {
+        decl: Rc::new(decl),
+        closure: Some(closure),
+    })
+}
+
+/// Validate the count of a repetition of a String or List of length `len`
+fn repeat_count(n: i64, len: usize, type_name: &str) -> Result<usize, RuntimeError> {
+    if n < 0 {
+        return Err(RuntimeError::InvalidOperation(
+            format!("Cannot repeat a {} a negative number of times ({})", type_name, n)
+        ));
+    }
+    match len.checked_mul(n as usize) {
+        // Repeating nothing is nothing, however many times
+        Some(0) => Ok(0),
+        Some(total) if total <= MAX_LIST_LENGTH => Ok(n as usize),
+        _ => Err(RuntimeError::InvalidOperation(
+            format!("Repeating a {} of length {} {} times is over the limit of {}", type_name, len, n, MAX_LIST_LENGTH)
+        )),
+    }
+}
+
+/// Pad `args[0]` with the single-character `args[2]` up to `args[1]` chars,
+/// putting `left(padding)` of the padding before it and the rest after.
+/// Strings already at least that wide are returned unchanged.
+fn pad(args: &[Value], name: &str, left: fn(usize) -> usize) -> Result<Value, RuntimeError> {
+    if args.len() != 3 {
+        return Err(RuntimeError::ArityMismatch { function: name.to_string(), expected: 3, got: args.len() });
+    }
+    let s = args[0].as_string()?;
+    let width = args[1].as_integer()?;
+    let fill = args[2].as_string()?;
+    let mut fill_chars = fill.chars();
+    let (Some(fill_char), None) = (fill_chars.next(), fill_chars.next()) else {
+        return Err(RuntimeError::InvalidOperation(
+            format!("{}() requires a single-character fill, got \"{}\"", name, fill)
+        ));
+    };
+    if width < 0 {
+        return Err(RuntimeError::InvalidOperation(
+            format!("{}() requires a non-negative width, got {}", name, width)
+        ));
+    }
+
+    let padding = (width as usize).saturating_sub(s.chars().count());
+    let before = left(padding);
+    let mut out = String::with_capacity(s.len() + padding * fill_char.len_utf8());
+    out.extend(std::iter::repeat_n(fill_char, before));
+    out.push_str(&s);
+    out.extend(std::iter::repeat_n(fill_char, padding - before));
+    Ok(Value::String(out))
+}
+
+/// The element of the list `args[0]` whose key from the function `args[1]`
+/// compares as `wanted` against every other key. Keys are ordered like `<`,
+/// and an element only replaces the best so far if strictly better, so ties
+/// keep the first. NaN keys never win.
+fn extreme_by(
+    interp: &mut Interpreter,
+    args: &[Value],
+    name: &str,
+    wanted: std::cmp::Ordering,
+) -> Result<Value, RuntimeError> {
+    if args.len() != 2 {
+        return Err(RuntimeError::ArityMismatch { function: name.to_string(), expected: 2, got: args.len() });
+    }
+    let items = args[0].as_list()?;
+    if items.is_empty() {
+        return Err(RuntimeError::InvalidOperation(format!("{}() of an empty list", name)));
+    }
+    let mut best: Option<(&Value, Value)> = None;
+    for item in items.iter() {
+        let key = interp.call_value(&args[1], std::slice::from_ref(item))?;
+        let better = match &best {
+            None => true,
+            Some((_, best_key)) => partial_compare(&key, best_key)? == Some(wanted)
+                || matches!(best_key, Value::Float(f) if f.is_nan()),
+        };
+        if better {
+            best = Some((item, key));
+        }
+    }
+    Ok(best.map(|(item, _)| item.clone()).unwrap_or(Value::Unit))
+}
+
+/// Validate a list length argument for list-building builtins
+fn list_length(value: &Value, name: &str) -> Result<usize, RuntimeError> {
+    let n = value.as_integer()?;
+    if n < 0 {
+        return Err(RuntimeError::InvalidOperation(
+            format!("{}() requires a non-negative length, got {}", name, n)
+        ));
+    }
+    if n as u64 > MAX_LIST_LENGTH as u64 {
+        return Err(RuntimeError::InvalidOperation(
+            format!("{}() length {} is over the limit of {}", name, n, MAX_LIST_LENGTH)
+        ));
+    }
+    Ok(n as usize)
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn run_source(source: &str) -> Result<Value, RuntimeError> {
+        run_with(source, |interpreter, ast| interpreter.interpret(ast))
+    }
+
+    /// Parse `source` and hand it to `run` with a fresh interpreter, for
+    /// tests that set the interpreter up first or look at it afterwards
+    fn run_with<T>(source: &str, run: impl FnOnce(&mut Interpreter, &Module) -> T) -> T {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(lexer.tokenize().unwrap());
+        let ast = parser.parse().unwrap();
+        run(&mut Interpreter::new(), &ast)
+    }
+
+    /// Run `source` with tracing on, returning its result and the trace
+    fn run_traced(source: &str) -> (Value, String) {
+        let buffer = SharedBuffer::default();
+        let result = run_with(source, |interpreter, ast| {
+            interpreter.set_trace(Box::new(buffer.clone()));
+            interpreter.interpret(ast).unwrap()
+        });
+        (result, buffer.contents())
+    }
+
+    /// A writer whose contents can be read back after the interpreter is done
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let source = r#"
+            proto main() {
+                return 1 + 2 * 3
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Integer(7));
+    }
+
+    #[test]
+    fn test_variables() {
+        let source = r#"
+            proto main() {
+                let x = 10
+                let y = 20
+                return x + y
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Integer(30));
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let source = r#"
+            proto main() {
+                if true {
+                    return 42
+                } else {
+                    return 0
+                }
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Integer(42));
+    }
+
+    #[test]
+    fn test_match_expression() {
+        let source = r#"
+            proto main() {
+                return match 5 {
+                    _ => 42
+                }
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Integer(42));
+    }
+
+    #[test]
+    fn test_function_call() {
+        // Note: Currently functions must be defined before they are called
+        // due to single-pass interpretation
+        let source = r#"
+            proto main() {
+                return 42
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Integer(42));
+    }
+
+    #[test]
+    fn test_trace_recursive_calls() {
+        let source = r#"
+            proto fib(n: Int) -> Int {
+                return if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+            }
+
+            proto main() {
+                return fib(3)
+            }
+        "#;
+
+        let (result, trace) = run_traced(source);
+        assert_eq!(result, Value::Integer(2));
+        let expected = "\
+-> main()
+  -> fib(3)
+    -> fib(2)
+      -> fib(1)
+      <- fib = 1
+      -> fib(0)
+      <- fib = 0
+    <- fib = 1
+    -> fib(1)
+    <- fib = 1
+  <- fib = 2
+<- main = 2
+";
+        assert_eq!(trace, expected);
+    }
+
+    #[test]
+    fn test_solve_minimize() {
+        let source = r#"
+            solve cheapest_mix() {
+                let x in range(0, 10)
+                let y in range(0, 10)
+                ensure x + y >= 7
+                ensure x >= 2
+                minimize 3 * x + 2 * y
+                return [x, y]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![Value::Integer(2), Value::Integer(5)]));
+    }
+
+    #[test]
+    fn test_solve_maximize_and_first_solution() {
+        let source = r#"
+            solve largest_square() {
+                let n in range(1, 20)
+                ensure n * n < 50
+                maximize n
+                return n
+            }
+        "#;
+        assert_eq!(run_source(source).unwrap(), Value::Integer(7));
+
+        let source = r#"
+            solve first_even() {
+                let n in [3, 5, 8, 10]
+                ensure n % 2 == 0
+                return n
+            }
+        "#;
+        assert_eq!(run_source(source).unwrap(), Value::Integer(8));
+    }
+
+    #[test]
+    fn test_solve_without_solution() {
+        let source = r#"
+            solve impossible() {
+                let n in range(0, 3)
+                ensure n > 5
+                return n
+            }
+        "#;
+
+        let err = run_source(source).unwrap_err();
+        assert_eq!(err.to_string(), "No solution satisfies the constraints of impossible");
+    }
+
+    #[test]
+    fn test_record_operator_hooks() {
+        let source = r#"
+            proto vec2(x, y) {
+                let add = (a, b) => vec2(a.x + b.x, a.y + b.y)
+                let sub = (a, b) => vec2(a.x - b.x, a.y - b.y)
+                return { x: x, y: y, __add__: add, __sub__: sub }
+            }
+
+            proto main() {
+                let sum = vec2(1, 2) + vec2(10, 20)
+                let diff = vec2(5, 5) - vec2(1, 2)
+                return [sum.x, sum.y, diff.x, diff.y]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [11, 22, 4, 3];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Integer).collect()));
+
+        // Records without the method still can't be added
+        let err = run_source("proto main() { return { x: 1 } + { x: 2 } }").unwrap_err();
+        assert_eq!(err, RuntimeError::TypeError("Cannot add Record and Record".to_string()));
+    }
+
+    #[test]
+    fn test_record_pattern() {
+        let source = r#"
+            proto classify(point) {
+                return match point {
+                    { x: 0, y } => y,
+                    { x, y: 0 } => x * 10,
+                    { inner: { z } } => z * 100,
+                    _ => -1
+                }
+            }
+
+            proto main() {
+                let a = classify({ x: 0, y: 7 })
+                let b = classify({ x: 3, y: 0, label: "extra" })
+                let c = classify({ inner: { z: 2 } })
+                let d = classify({ x: 1, y: 1 })
+                let e = classify(5)
+                return [a, b, c, d, e]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [7, 30, 200, -1, -1];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Integer).collect()));
+    }
+
+    #[test]
+    fn test_list_pattern() {
+        let source = r#"
+            proto describe(xs) {
+                return match xs {
+                    [] => "empty",
+                    [x] => "one",
+                    [1, ..rest] => "starts with one",
+                    _ => "other"
+                }
+            }
+
+            proto main() {
+                return [describe([]), describe([9]), describe([1, 2, 3]), describe([2, 3])]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = ["empty", "one", "starts with one", "other"];
+        assert_eq!(result, Value::list(expected.iter().map(|s| Value::String(s.to_string())).collect()));
+    }
+
+    #[test]
+    fn test_list_pattern_bindings() {
+        let source = r#"
+            proto main() {
+                let single = match [5] { [x] => x, _ => 0 }
+                let parts = match [1, 2, 3] { [first, ..rest] => [first, rest], _ => [] }
+                let exact = match [1, 2] { [a, b, ..rest] => rest, _ => [9] }
+                return [single, parts, exact]
+            }
+        "#;
+
+        let ints = |ns: &[i64]| Value::list(ns.iter().copied().map(Value::Integer).collect());
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Integer(5),
+            Value::list(vec![Value::Integer(1), ints(&[2, 3])]),
+            ints(&[]),
+        ]));
+    }
+
+    #[test]
+    fn test_match_failure_reports_value() {
+        let source = r#"
+            proto main() {
+                return match 7 {
+                    1 => "one",
+                    2 => "two"
+                }
+            }
+        "#;
+
+        let err = run_source(source).unwrap_err();
+        assert_eq!(err.to_string(), "no match arm for value 7 (Int)");
+    }
+
+    #[test]
+    fn test_negative_literal_pattern() {
+        let source = r#"
+            proto main() {
+                return match 0 - 1 {
+                    1 => "one",
+                    -1 => "minus one",
+                    _ => "other"
+                }
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::String("minus one".to_string()));
+    }
+
+    #[test]
+    fn test_negative_range_pattern() {
+        let source = r#"
+            proto main() {
+                return match -3 {
+                    -5..-1 => "negative",
+                    0..5 => "positive",
+                    _ => "other"
+                }
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::String("negative".to_string()));
+    }
+
+    #[test]
+    fn test_main_receives_args() {
+        let source = r#"
+            proto main(args) {
+                return [len(args), args[0] + args[2], args[1]]
+            }
+        "#;
+        
+        let args = ["1", "two", "3.0"].iter().map(|a| Value::from_arg(a)).collect();
+        let result = run_with(source, |interpreter, ast| interpreter.interpret_with_args(ast, args)).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Integer(3),
+            Value::Float(4.0),
+            Value::String("two".to_string()),
+        ]));
+    }
+
+    #[test]
+    fn test_block_ending_in_for_is_unit() {
+        let source = r#"
+            proto main() {
+                let x = {
+                    for i in [1, 2, 3] {
+                        i * 2
+                    }
+                }
+                return x
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Unit);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let source = r#"
+            proto main() {
+                return repeat(0, 3)
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![Value::Integer(0); 3]));
+    }
+
+    #[test]
+    fn test_pipe_then_arithmetic() {
+        let source = r#"
+            proto double(n) {
+                return n * 2
+            }
+
+            proto main() {
+                return 5 |> double |> double + 1
+            }
+        "#;
+
+        assert_eq!(run_source(source).unwrap(), Value::Integer(21));
+    }
+
+    #[test]
+    fn test_ternary_matches_if() {
+        let source = r#"
+            proto sign_if(n) {
+                return if n > 0 { 1 } else { if n < 0 { -1 } else { 0 } }
+            }
+
+            proto sign(n) {
+                return n > 0 ? 1 : n < 0 ? -1 : 0
+            }
+
+            proto main() {
+                let ternary = [sign(-5), sign(0), sign(7)]
+                let branches = [sign_if(-5), sign_if(0), sign_if(7)]
+                return ternary + branches
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [-1, 0, 1, -1, 0, 1];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Integer).collect()));
+    }
+
+    #[test]
+    fn test_fill() {
+        let source = r#"
+            proto main() {
+                return fill(3, i => i * i)
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Integer(4),
+        ]));
+    }
+
+    #[test]
+    fn test_sequence_repetition() {
+        let source = r#"
+            proto main() {
+                return ["-" * 5, 2 * "ab", [0] * 3, 2 * [1, 2], "x" * 0]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let ints = |ns: &[i64]| Value::list(ns.iter().copied().map(Value::Integer).collect());
+        assert_eq!(result, Value::list(vec![
+            Value::String("-----".to_string()),
+            Value::String("abab".to_string()),
+            ints(&[0, 0, 0]),
+            ints(&[1, 2, 1, 2]),
+            Value::String(String::new()),
+        ]));
+    }
+
+    #[test]
+    fn test_negative_repetition() {
+        let err = run_source("proto main() { return \"ab\" * -1 }").unwrap_err();
+        assert_eq!(err, RuntimeError::InvalidOperation(
+            "Cannot repeat a String a negative number of times (-1)".to_string()
+        ));
+
+        let err = run_source("proto main() { return [1] * -2 }").unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_oversized_repetition() {
+        let err = run_source("proto main() { return \"ab\" * 9223372036854775807 }").unwrap_err();
+        assert_eq!(err, RuntimeError::InvalidOperation(
+            "Repeating a String of length 2 9223372036854775807 times is over the limit of 4194304".to_string()
+        ));
+
+        let err = run_source("proto main() { return 4611686018427387904 * [1] }").unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidOperation(_)));
+
+        // Empty sequences stay empty however often they repeat
+        assert_eq!(run_source("proto main() { return len([] * 4611686018427387904) }"), Ok(Value::Integer(0)));
+
+        // One element past the limit is already too many
+        for source in [
+            "proto main() { return repeat(0, 4194305) }",
+            "proto main() { return fill(4194305, i => i) }",
+            "proto main() { return [0] * 4194305 }",
+            "proto main() { return \"ab\" * 2097153 }",
+        ] {
+            let err = run_source(source).unwrap_err();
+            assert!(matches!(err, RuntimeError::InvalidOperation(_)), "{}: {:?}", source, err);
+        }
+        assert_eq!(run_source("proto main() { return len(\"ab\" * 2097152) }"), Ok(Value::Integer(4194304)));
+    }
+
+    #[test]
+    fn test_split_lines() {
+        // String literals can span lines, so the text holds real line breaks
+        let source = "proto main() { return [split_lines(\"a\r\nb\n\nc\n\"), split_lines(\"\")] }";
+
+        let strings = |items: &[&str]| Value::list(
+            items.iter().map(|s| Value::String(s.to_string())).collect()
+        );
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![strings(&["a", "b", "", "c"]), strings(&[])]));
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let path = std::env::temp_dir().join(format!("morph_read_lines_{}.txt", std::process::id()));
+        std::fs::write(&path, "first\r\nsecond\n").unwrap();
+
+        let source = format!("proto main() {{ return read_lines({:?}) }}", path.display().to_string());
+        let result = run_source(&source);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap(), Value::list(vec![
+            Value::String("first".to_string()),
+            Value::String("second".to_string()),
+        ]));
+
+        let err = run_source("proto main() { return read_lines(\"/no/such/file\") }").unwrap_err();
+        assert!(matches!(err, RuntimeError::Custom(ref msg) if msg.starts_with("Cannot read /no/such/file")));
+    }
+
+    #[test]
+    fn test_nan_comparisons() {
+        let source = r#"
+            import math
+
+            proto main() {
+                let nan = math.sqrt(-1.0)
+                return [nan < 1.0, nan >= 1.0, 1 > nan, nan <= nan, nan == nan, nan != nan]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [false, false, false, false, false, true];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Boolean).collect()));
+    }
+
+    #[test]
+    fn test_sort() {
+        let source = r#"
+            import math
+
+            proto main() {
+                let nan = math.sqrt(-1.0)
+                return [sort([3.0, nan, 1, 2.5, nan, -4]), sort(["pear", "apple"])]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let lists = result.as_list().unwrap();
+        let numbers = lists[0].as_list().unwrap();
+        assert_eq!(numbers[..4], [Value::Integer(-4), Value::Integer(1), Value::Float(2.5), Value::Float(3.0)]);
+        assert!(numbers[4..].iter().all(|v| matches!(v, Value::Float(f) if f.is_nan())));
+        assert_eq!(lists[1], Value::list(vec![
+            Value::String("apple".to_string()),
+            Value::String("pear".to_string()),
+        ]));
+
+        let err = run_source("proto main() { return sort([1, \"a\"]) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::TypeError(_)));
+    }
+
+    #[test]
+    fn test_strict_arithmetic() {
+        let run = |source: &str, strict: bool| run_with(source, |interpreter, ast| {
+            interpreter.set_strict_arithmetic(strict);
+            interpreter.interpret(ast)
+        });
+
+        let source = "proto main() { return 1 + 2.0 }";
+        assert_eq!(run(source, false), Ok(Value::Float(3.0)));
+        assert_eq!(run(source, true), Err(RuntimeError::TypeError(
+            "Cannot apply `+` to Int and Float with strict arithmetic; convert with to_float or to_int".to_string()
+        )));
+
+        // Same-typed operands and explicit conversions are fine
+        let source = "proto main() { return [to_float(1) * 2.0, 7 % 2, \"a\" + \"b\"] }";
+        assert_eq!(run(source, true), Ok(Value::list(vec![
+            Value::Float(2.0),
+            Value::Integer(1),
+            Value::String("ab".to_string()),
+        ])));
+    }
+
+    #[test]
+    fn test_in_operator() {
+        let source = r#"
+            proto main() {
+                let point = { x: 1, y: 2 }
+                return [2 in [1, 2, 3], 4 in [1, 2, 3], "x" in point, "z" in point]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Boolean(true),
+            Value::Boolean(false),
+        ]));
+
+        let err = run_source("proto main() { return 1 in \"123\" }").unwrap_err();
+        assert_eq!(err, RuntimeError::TypeError("Cannot test membership in String".to_string()));
+
+        let err = run_source("proto main() { return 1 in { x: 1 } }").unwrap_err();
+        assert_eq!(err, RuntimeError::TypeError("Record keys are String, not Int".to_string()));
+    }
+
+    #[test]
+    fn test_numeric_conversions() {
+        let source = r#"
+            proto main() {
+                return [to_int(3.9), to_int(-3.9), to_int("42"), to_int(7), to_float(5), to_float("2.5")]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Integer(3),
+            Value::Integer(-3),
+            Value::Integer(42),
+            Value::Integer(7),
+            Value::Float(5.0),
+            Value::Float(2.5),
+        ]));
+    }
+
+    #[test]
+    fn test_numeric_conversion_errors() {
+        let err = run_source("proto main() { return to_int(\"4x\") }").unwrap_err();
+        assert_eq!(err, RuntimeError::InvalidOperation("Cannot convert \"4x\" to Int".to_string()));
+
+        let err = run_source("proto main() { return to_int(100000000000000000000.0) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidOperation(_)));
+
+        let err = run_source("proto main() { return to_float(true) }").unwrap_err();
+        assert_eq!(err, RuntimeError::InvalidOperation("Cannot convert true (Bool) to Float".to_string()));
+    }
+
+    #[test]
+    fn test_json_input() {
+        let source = r#"
+            proto main(config) {
+                return [config.name, config.retries + 1, config.ratio, len(config.tags), config.extra]
+            }
+        "#;
+        let json = r#"{"name": "job", "retries": 2, "ratio": 0.5, "tags": ["a", "b"], "extra": null}"#;
+
+        let input = Value::from_json(serde_json::from_str(json).unwrap());
+        assert_eq!(input.to_string(), "{ name: job, retries: 2, ratio: 0.5, tags: [a, b], extra: () }");
+
+        let result = run_with(source, |interpreter, ast| interpreter.interpret_with_input(ast, input)).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::String("job".to_string()),
+            Value::Integer(3),
+            Value::Float(0.5),
+            Value::Integer(2),
+            Value::Unit,
+        ]));
+    }
+
+    #[test]
+    fn test_integer_builtins() {
+        let source = r#"
+            proto main() {
+                let gcds = [gcd(12, 18), gcd(-12, 18), gcd(9, 28), gcd(0, 0), gcd(0, 5)]
+                let lcms = [lcm(4, 6), lcm(9, 28), lcm(-3, 5), lcm(0, 7)]
+                return gcds + lcms + [abs_diff(3, 10), abs_diff(10, -3)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [6, 6, 1, 0, 5, 12, 252, 15, 0, 7, 13];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Integer).collect()));
+
+        let err = run_source("proto main() { return lcm(4611686018427387903, 4611686018427387902) }").unwrap_err();
+        assert_eq!(err, RuntimeError::InvalidOperation(
+            "lcm(4611686018427387903, 4611686018427387902) overflows Int".to_string()
+        ));
+
+        let err = run_source("proto main() { return abs_diff(9223372036854775807, -1) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_map_values_and_filter_entries() {
+        let source = r#"
+            proto main() {
+                let scores = { alice: 3, bob: 8, carol: 5 }
+                let doubled = map_values(scores, n => n * 2)
+                let high = filter_entries(scores, (name, n) => n > 4)
+                return [doubled, high]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let record = |entries: &[(&str, i64)]| Value::Record(
+            entries.iter().map(|(k, v)| (Symbol::new(k), Value::Integer(*v))).collect()
+        );
+        assert_eq!(result, Value::list(vec![
+            record(&[("alice", 6), ("bob", 16), ("carol", 10)]),
+            record(&[("bob", 8), ("carol", 5)]),
+        ]));
+    }
+
+    #[test]
+    fn test_record_callbacks_run_in_insertion_order() {
+        let source = r#"
+            proto visit(n) {
+                return n
+            }
+
+            proto main() {
+                let visited = map_values({ b: 2, c: 3, a: 1 }, visit)
+                return visited.c
+            }
+        "#;
+
+        let (result, trace) = run_traced(source);
+        assert_eq!(result, Value::Integer(3));
+        let calls: Vec<&str> = trace.lines().filter(|line| line.contains("-> visit")).collect();
+        assert_eq!(calls, vec!["  -> visit(2)", "  -> visit(3)", "  -> visit(1)"]);
+    }
+
+    #[test]
+    fn test_record_display_keeps_field_order() {
+        let source = r#"
+            proto main() {
+                let point = { z: 3, x: 1, y: 2 }
+                return [point, map_values(point, n => n * 10)]
+            }
+        "#;
+
+        for _ in 0..5 {
+            let result = run_source(source).unwrap();
+            assert_eq!(result.to_string(), "[{ z: 3, x: 1, y: 2 }, { z: 30, x: 10, y: 20 }]");
+        }
+    }
+
+    #[test]
+    fn test_zip_with() {
+        let source = r#"
+            proto main() {
+                return zip_with([1, 2, 3], [10, 20], (a, b) => a + b)
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![Value::Integer(11), Value::Integer(22)]));
+    }
+
+    #[test]
+    fn test_zip3() {
+        let source = r#"
+            proto main() {
+                let names = ["a", "b", "c"]
+                return [zip3([1, 2, 3], names, [true, false, true]), zip3([1, 2, 3], names, [1.5]), zip3([], names, names)]
+            }
+        "#;
+        assert_eq!(run_source(source).unwrap().to_string(), "[[[1, a, true], [2, b, false], [3, c, true]], [[1, a, 1.5]], []]");
+
+        let err = run_source("proto main() { return zip3([1], [2], 3) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::TypeError(_)));
+    }
+
+    #[test]
+    fn test_flat_map() {
+        let source = r#"
+            proto main() {
+                return flat_map([1, 2, 3], n => repeat(n, n))
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [1, 2, 2, 3, 3, 3];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Integer).collect()));
+
+        // The function must return a list
+        let err = run_source("proto main() { return flat_map([1], n => n) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::TypeError(_)));
+    }
+
+    #[test]
+    fn test_fold_right_and_scan() {
+        let source = r#"
+            proto add(a, b) {
+                return a + b
+            }
+
+            proto main() {
+                let digits = fold_right(["a", "b", "c"], "", (item, acc) => acc + item)
+                let sums = scan([1, 2, 3], 0, add)
+                let differences = fold_right([1, 2, 3], 0, (item, acc) => item - acc)
+                return [digits, sums, differences, scan([], 0, add), fold_right([], 5, add)]
+            }
+        "#;
+        // 1 - (2 - (3 - 0)) = 2
+        assert_eq!(run_source(source).unwrap().to_string(), "[cba, [1, 3, 6], 2, [], 5]");
+
+        let err = run_source("proto main() { return scan([1], 0) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::ArityMismatch { expected: 3, got: 2, .. }));
+    }
+
+    #[test]
+    fn test_repeat_negative_length() {
+        let source = r#"
+            proto main() {
+                return repeat("x", -1)
+            }
+        "#;
+        
+        let result = run_source(source);
+        assert!(matches!(result, Err(RuntimeError::InvalidOperation(_))));
+
+        let error = run_source("proto main() { return repeat(0, 4611686018427387904) }").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid operation: repeat() length 4611686018427387904 is over the limit of 4194304");
+        let error = run_source("proto main() { return fill(9223372036854775807, i => i) }").unwrap_err();
+        assert!(matches!(error, RuntimeError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_take_and_drop() {
+        let source = r#"
+            proto main() {
+                let items = [1, 2, 3, 4]
+                return [take(items, 2), drop(items, 2), take(items, 10), drop(items, 10), take(items, 0)]
+            }
+        "#;
+
+        let ints = |ns: &[i64]| Value::list(ns.iter().copied().map(Value::Integer).collect());
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            ints(&[1, 2]),
+            ints(&[3, 4]),
+            ints(&[1, 2, 3, 4]),
+            ints(&[]),
+            ints(&[]),
+        ]));
+
+        let err = run_source("proto main() { return take([1], -1) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_chunk() {
+        let source = r#"
+            proto main() {
+                return [chunk([1, 2, 3, 4, 5], 2), chunk([1, 2], 5), chunk([], 3)]
+            }
+        "#;
+
+        let ints = |ns: &[i64]| Value::list(ns.iter().copied().map(Value::Integer).collect());
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::list(vec![ints(&[1, 2]), ints(&[3, 4]), ints(&[5])]),
+            Value::list(vec![ints(&[1, 2])]),
+            Value::list(vec![]),
+        ]));
+
+        for size in ["0", "-2"] {
+            let source = format!("proto main() {{ return chunk([1, 2], {}) }}", size);
+            let err = run_source(&source).unwrap_err();
+            assert!(matches!(err, RuntimeError::InvalidOperation(_)));
+        }
+    }
+
+    #[test]
+    fn test_list_reads_share_storage() {
+        let source = r#"
+            proto main() {
+                let xs = range(0, 10000)
+                let ys = xs
+                let zs = ys
+                return [xs, ys, zs]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let copies = result.as_list().unwrap();
+        let (Value::List(first), Value::List(last)) = (&copies[0], &copies[2]) else {
+            panic!("Expected lists");
+        };
+        assert!(std::rc::Rc::ptr_eq(first, last));
+    }
+
+    #[test]
+    fn test_list_copy_on_write() {
+        let original = Value::list(vec![Value::Integer(1), Value::Integer(2)]);
+        let mut copy = original.clone();
+        copy.as_list_mut().unwrap()[0] = Value::Integer(10);
+
+        assert_eq!(original, Value::list(vec![Value::Integer(1), Value::Integer(2)]));
+        assert_eq!(copy, Value::list(vec![Value::Integer(10), Value::Integer(2)]));
+
+        // An unshared list is mutated in place
+        let ptr = copy.as_list().unwrap().as_ptr();
+        copy.as_list_mut().unwrap()[1] = Value::Integer(20);
+        assert_eq!(copy.as_list().unwrap().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_string_predicates() {
+        let source = r#"
+            proto main() {
+                let s = "hello world"
+                let found = [contains(s, "lo w"), contains(s, "xyz")]
+                let prefix = [starts_with(s, "hell"), starts_with(s, "world")]
+                let suffix = [ends_with(s, "world"), ends_with(s, "hello")]
+                let member = [contains([1, 2, 3], 2), contains([1, 2, 3], 4)]
+                return found + prefix + suffix + member
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [true, false, true, false, true, false, true, false];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Boolean).collect()));
+
+        let err = run_source("proto main() { return contains(5, 1) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::TypeError(_)));
+    }
+
+    #[test]
+    fn test_seeded_random_is_deterministic() {
+        let source = r#"
+            proto main() {
+                return [random(), random(), random_int(1, 7), random_int(1, 7), random_int(-5, 5)]
+            }
+        "#;
+
+        let run_seeded = |seed| run_with(source, |interpreter, ast| {
+            interpreter.set_seed(seed);
+            interpreter.interpret(ast).unwrap()
+        });
+
+        let first = run_seeded(42);
+        assert_eq!(first, run_seeded(42));
+        assert_ne!(first, run_seeded(43));
+
+        let values = first.as_list().unwrap();
+        for value in &values[..2] {
+            let f = value.as_float().unwrap();
+            assert!((0.0..1.0).contains(&f));
+        }
+        for value in &values[2..4] {
+            assert!((1..7).contains(&value.as_integer().unwrap()));
         }
+        assert!((-5..5).contains(&values[4].as_integer().unwrap()));
+
+        let err = run_source("proto main() { return random_int(3, 3) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidOperation(_)));
     }
 
-    /// Evaluate an expression
-    fn evaluate(&mut self, expr: &Expression) -> Result<Value, RuntimeError> {
-        match expr {
-            Expression::Literal(lit) => self.evaluate_literal(lit),
-            Expression::Identifier(name) => {
-                self.environment.get(name)
+    #[test]
+    fn test_block_statement_scope() {
+        let source = r#"
+            proto main() {
+                let x = 1
+                { let tmp = 41; log(tmp) }
+                return x
             }
-            Expression::Binary { left, op, right } => {
-                let left_val = self.evaluate(left)?;
-                let right_val = self.evaluate(right)?;
-                self.evaluate_binary_op(&left_val, op, &right_val)
+        "#;
+        assert_eq!(run_source(source).unwrap(), Value::Integer(1));
+
+        let source = r#"
+            proto main() {
+                {
+                    let tmp = 41
+                }
+                return tmp
             }
-            Expression::Unary { op, expr } => {
-                let val = self.evaluate(expr)?;
-                self.evaluate_unary_op(op, &val)
+        "#;
+        let err = run_source(source).unwrap_err();
+        assert_eq!(err, RuntimeError::UndefinedVariable("tmp".to_string()));
+    }
+
+    #[test]
+    fn test_index_and_rindex() {
+        let source = r#"
+            proto main() {
+                let s = "abcabc"
+                let ascii = [index(s, "bc"), rindex(s, "bc"), index(s, "x"), rindex(s, "x")]
+                let t = "héllo wörld"
+                let wide = [index(t, "l"), rindex(t, "l"), index(t, "ö"), index(t, "o w")]
+                return ascii + wide
             }
-            Expression::Call { callee, args } => {
-                let func_val = self.evaluate(callee)?;
-                let arg_vals: Result<Vec<_>, _> = args.iter()
-                    .map(|a| self.evaluate(a))
-                    .collect();
-                let arg_vals = arg_vals?;
-                
-                match func_val {
-                    Value::Function(func) => self.execute_function(&func, &arg_vals),
-                    _ => Err(RuntimeError::TypeError("Not a function".to_string())),
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [1, 4, -1, -1, 2, 9, 7, 4];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Integer).collect()));
+
+        let err = run_source("proto main() { return index(\"abc\", 1) }").unwrap_err();
+        assert!(matches!(err, RuntimeError::TypeError(_)));
+    }
+
+    #[test]
+    fn test_sandbox_omits_impure_builtins() {
+        let run_sandboxed = |source: &str| run_with(source, |_, ast| Interpreter::new_sandboxed().interpret(ast));
+
+        let err = run_sandboxed("proto main() { log(\"hi\") }").unwrap_err();
+        assert_eq!(err, RuntimeError::UndefinedVariable("log".to_string()));
+
+        let err = run_sandboxed("proto main() { return random() }").unwrap_err();
+        assert_eq!(err, RuntimeError::UndefinedVariable("random".to_string()));
+
+        // Pure builtins are still there
+        assert_eq!(run_sandboxed("proto main() { return len(range(0, 4)) }").unwrap(), Value::Integer(4));
+    }
+
+    #[test]
+    fn test_global_bindings_include_top_level_let() {
+        let source = r#"
+            let limit = 3 * 4
+
+            proto main() {
+                return limit + 1
+            }
+        "#;
+
+        run_with(source, |interpreter, ast| {
+            assert_eq!(interpreter.interpret(ast).unwrap(), Value::Integer(13));
+            let globals = interpreter.global_bindings();
+            assert_eq!(globals, vec![("limit".to_string(), Value::Integer(12))]);
+        });
+    }
+
+    #[test]
+    fn test_profile_counts_recursive_calls() {
+        let source = r#"
+            proto fib(n: Int) -> Int {
+                return if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+            }
+
+            proto main() {
+                return fib(5)
+            }
+        "#;
+
+        let report = run_with(source, |interpreter, ast| {
+            interpreter.enable_profile();
+            assert_eq!(interpreter.interpret(ast).unwrap(), Value::Integer(5));
+            interpreter.profile_report()
+        });
+        let calls: HashMap<&str, u64> = report.iter()
+            .map(|(name, profile)| (name.as_str(), profile.calls))
+            .collect();
+        assert_eq!(calls, HashMap::from([("main", 1), ("fib", 15)]));
+        // main encloses every fib call, so it is at least as hot
+        let total = |name: &str| report.iter().find(|(n, _)| n == name).unwrap().1.total;
+        assert!(total("main") >= total("fib"));
+        assert!(report.iter().all(|(_, profile)| profile.active == 0));
+    }
+
+    #[test]
+    fn test_list() {
+        let source = r#"
+            proto main() {
+                let items = [1, 2, 3]
+                return items[0]
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Integer(1));
+    }
+
+    #[test]
+    fn test_import_alias() {
+        let source = r#"
+            import math as m
+            proto main() {
+                return m.sqrt(16.0) + m.abs(-2)
+            }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Float(6.0));
+    }
+
+    #[test]
+    fn test_unknown_module() {
+        let source = r#"
+            import nope as n
+            proto main() {
+                return 1
+            }
+        "#;
+        
+        let result = run_source(source);
+        assert_eq!(result, Err(RuntimeError::Custom("Unknown module: nope".to_string())));
+    }
+
+    #[test]
+    fn test_arity_mismatch_names_function() {
+        let source = r#"
+            proto add(a, b) {
+                return a + b
+            }
+            proto main() {
+                return add(1)
+            }
+        "#;
+
+        let err = run_source(source).unwrap_err();
+        assert_eq!(err, RuntimeError::ArityMismatch { function: "add".to_string(), expected: 2, got: 1 });
+        assert_eq!(err.to_string(), "add expects 2 arguments, got 1");
+
+        let err = run_source("proto main() { return len([1], [2]) }").unwrap_err();
+        assert_eq!(err.to_string(), "len expects 1 arguments, got 2");
+
+        let err = run_source("import math as m\nproto main() { return m.sqrt() }").unwrap_err();
+        assert_eq!(err.to_string(), "math.sqrt expects 1 arguments, got 0");
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let source = r#"
+            proto main() {
+                let items = [1, 2, 3]
+                var total = 0
+                for item in items {
+                    total = total + item
                 }
+                return total
             }
-            Expression::Pipe { left, right } => {
-                let left_val = self.evaluate(left)?;
-                
-                // Pipe left value as first argument to right function
-                match right.as_ref() {
-                    Expression::Call { callee, args } => {
-                        let func_val = self.evaluate(callee)?;
-                        let mut arg_vals = vec![left_val];
-                        for arg in args {
-                            arg_vals.push(self.evaluate(arg)?);
-                        }
-                        
-                        match func_val {
-                            Value::Function(func) => self.execute_function(&func, &arg_vals),
-                            _ => Err(RuntimeError::TypeError("Not a function".to_string())),
-                        }
+        "#;
+        
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::Integer(6));
+    }
+
+    #[test]
+    fn test_assignment() {
+        let source = r#"
+            proto main() {
+                var xs = [1, 2]
+                let ys = xs
+                xs[0] = 10
+                xs = [xs[0], xs[1], 3]
+                return [xs, ys]
+            }
+        "#;
+        assert_eq!(run_source(source).unwrap().to_string(), "[[10, 2, 3], [1, 2]]");
+
+        // A `let` can't be changed, either directly or through an index
+        let error = run_source("proto main() {\n let x = 1\n x = 2\n return x\n}").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid operation: Cannot assign to x, which is declared with let");
+        let error = run_source("let xs = [1]\nproto main() {\n xs[0] = 2\n return xs\n}").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid operation: Cannot assign to xs, which is declared with let");
+        let error = run_source("proto main() {\n var xs = [1]\n xs[1] = 2\n}").unwrap_err();
+        assert_eq!(error, RuntimeError::IndexOutOfBounds { index: 1, len: 1 });
+    }
+
+    /// Run a program with tracing on and return the `visit(...)` calls made
+    fn visited(source: &str) -> Vec<String> {
+        let (_, trace) = run_traced(source);
+        trace.lines()
+            .filter_map(|line| line.trim().strip_prefix("-> visit"))
+            .map(|args| args.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_break() {
+        let source = r#"
+            proto visit(n) {
+                return n
+            }
+
+            proto main() {
+                for x in range(0, 10) {
+                    if x == 3 {
+                        break
                     }
-                    Expression::Identifier(name) => {
-                        self.call_function(name, &[left_val])
+                    visit(x)
+                }
+                visit(99)
+            }
+        "#;
+
+        assert_eq!(visited(source), vec!["(0)", "(1)", "(2)", "(99)"]);
+    }
+
+    #[test]
+    fn test_continue() {
+        let source = r#"
+            proto visit(n) {
+                return n
+            }
+
+            proto main() {
+                for x in range(0, 6) {
+                    if x % 2 == 1 {
+                        continue
                     }
-                    _ => Err(RuntimeError::TypeError(
-                        "Right side of pipe must be a function".to_string()
-                    )),
+                    visit(x)
+                }
+            }
+        "#;
+
+        assert_eq!(visited(source), vec!["(0)", "(2)", "(4)"]);
+    }
+
+    #[test]
+    fn test_early_return() {
+        let source = r#"
+            proto first_over(xs, limit) {
+                for x in xs {
+                    if x > limit {
+                        return x
+                    }
+                }
+                return -1
+            }
+
+            proto main() {
+                return [first_over([1, 5, 9], 4), first_over([1, 2], 4)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![Value::Integer(5), Value::Integer(-1)]));
+    }
+
+    #[test]
+    fn test_return_from_nested_block() {
+        let source = r#"
+            proto classify(n) {
+                let label = {
+                    let doubled = n * 2
+                    {
+                        if doubled > 10 {
+                            return "big"
+                        }
+                    }
+                    "small"
+                }
+                return label + "!"
+            }
+
+            proto main() {
+                return [classify(8), classify(2)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::String("big".to_string()),
+            Value::String("small!".to_string()),
+        ]));
+    }
+
+    #[test]
+    fn test_return_from_if_branch() {
+        let source = r#"
+            proto sign(n) {
+                let magnitude = if n < 0 { return -1 } else { n }
+                if magnitude == 0 {
+                    return 0
+                } else {
+                    if magnitude > 0 { return 1 }
                 }
+                return 99
+            }
+
+            proto guarded(n) {
+                let total = 1 + if n > 0 { return "early" } else { 2 }
+                return total
+            }
+
+            proto main() {
+                return [sign(-5), sign(0), sign(7), guarded(1), guarded(0)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Integer(-1),
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::String("early".to_string()),
+            Value::Integer(3),
+        ]));
+    }
+
+    #[test]
+    fn test_partial_application() {
+        let source = r#"
+            proto add(a, b) { return a + b }
+
+            proto between(low, x, high) {
+                return if x < low { false } else { x <= high }
+            }
+
+            proto main() {
+                let add5 = add(_, 5)
+                let ten = 10
+                let tenth = add(ten, _)
+                let fifty = between(_, 50, _)
+                let bumped = flat_map([1, 2, 3], x => [add(_, 1)(x)])
+                return [add5(3), tenth(1), fifty(0, 20), fifty(0, 100), bumped]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Integer(8),
+            Value::Integer(11),
+            Value::Boolean(false),
+            Value::Boolean(true),
+            Value::list(vec![Value::Integer(2), Value::Integer(3), Value::Integer(4)]),
+        ]));
+    }
+
+    #[test]
+    fn test_partial_application_evaluates_once() {
+        let source = r#"
+            proto add(a, b) { return a + b }
+
+            proto noisy(x) {
+                log("fixed", x)
+                return x
+            }
+
+            proto pick() {
+                log("callee")
+                return add
+            }
+
+            proto main() {
+                let add5 = pick()(_, noisy(5))
+                let spread = add(_, ...[noisy(1)])
+                let named = add(_, b: noisy(2))
+                return [add5(1), add5(2), add5(3), spread(1), spread(2), named(1), named(2)]
+            }
+        "#;
+
+        let sink = SharedBuffer::default();
+        let result = run_with(source, |interpreter, ast| {
+            interpreter.set_output(Box::new(sink.clone()));
+            interpreter.interpret(ast).unwrap()
+        });
+        assert_eq!(result, Value::list(
+            [6, 7, 8, 2, 3, 3, 4].into_iter().map(Value::Integer).collect()
+        ));
+        // Each fixed part ran once, however often the partial was called
+        assert_eq!(sink.contents(), "callee\nfixed 5\nfixed 1\nfixed 2\n");
+    }
+
+    #[test]
+    fn test_now_and_elapsed() {
+        let source = r#"
+            proto main() {
+                let start = now()
+                let squares = fill(10000, i => i * i)
+                let taken = elapsed(start)
+                return [taken, start, len(squares)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let items = result.as_list().unwrap();
+        assert!(items[0].as_float().unwrap() >= 0.0);
+        assert_eq!(items[1].to_string(), "<instant>");
+
+        let error = run_source("proto main() { return elapsed(1) }").unwrap_err();
+        assert!(error.to_string().contains("expects an Instant"));
+        let error = run_source("proto main() { return now() < now() }").unwrap_err();
+        assert!(error.to_string().contains("Instant"));
+    }
+
+    #[test]
+    fn test_coverage_reports_untaken_branch() {
+        let source = r#"
+proto sign(n) {
+    if n < 0 {
+        return -1
+    } else {
+        return 1
+    }
+}
+
+proto main() {
+    let positive = sign(5)
+    return positive
+}
+"#;
+
+        let report = run_with(source, |interpreter, ast| {
+            assert_eq!(interpreter.coverage_report(ast), None);
+            interpreter.enable_coverage();
+            interpreter.interpret(ast).unwrap();
+            interpreter.coverage_report(ast).unwrap()
+        });
+        assert_eq!(report.lines, vec![3, 4, 6, 11, 12]);
+        assert_eq!(report.missed, vec![4]);
+        assert_eq!(report.percent(), 80.0);
+    }
+
+    #[test]
+    fn test_coverage_json() {
+        let source = r#"
+proto sign(n) {
+    if n < 0 {
+        return -1
+    } else {
+        return 1
+    }
+}
+
+proto main() {
+    for i in range(0, 3) {
+        let s = sign(i)
+    }
+    return 0
+}
+"#;
+
+        let json = run_with(source, |interpreter, ast| {
+            interpreter.enable_coverage();
+            interpreter.interpret(ast).unwrap();
+            interpreter.coverage_report(ast).unwrap().to_json()
+        });
+        assert_eq!(json, serde_json::json!({
+            "percent": 83.33333333333333,
+            "lines": { "3": 3, "4": 0, "6": 3, "11": 1, "12": 3, "14": 1 },
+        }));
+        let keys: Vec<&String> = json["lines"].as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["3", "4", "6", "11", "12", "14"]);
+    }
+
+    #[test]
+    fn test_group_by_and_partition() {
+        let source = r#"
+            proto main() {
+                let numbers = [3, 8, 5, 2, 4, 7]
+                let by_parity = group_by(numbers, n => if n % 2 == 0 { "even" } else { "odd" })
+                let by_size = group_by(numbers, n => n > 4)
+                let split = partition(numbers, n => n < 5)
+                return [by_parity, by_size, split]
             }
-            Expression::Match { expr, arms } => {
-                let val = self.evaluate(expr)?;
-                
-                for arm in arms {
-                    if self.match_pattern(&val, &arm.pattern)? {
-                        return self.evaluate(&arm.expr);
-                    }
-                }
-                
-                Err(RuntimeError::Custom("No match arm matched".to_string()))
+        "#;
+
+        let result = run_source(source).unwrap();
+        let items = result.as_list().unwrap();
+        assert_eq!(items[0].to_string(), "{ odd: [3, 5, 7], even: [8, 2, 4] }");
+        assert_eq!(items[1].to_string(), "{ false: [3, 2, 4], true: [8, 5, 7] }");
+        assert_eq!(items[2].to_string(), "[[3, 2, 4], [8, 5, 7]]");
+
+        let error = run_source("proto main() { return group_by([1], n => [n]) }").unwrap_err();
+        assert!(error.to_string().contains("keys must be String, Int or Bool, got List"));
+    }
+
+    #[test]
+    fn test_verify_blocks() {
+        let source = r#"
+            proto double(n: Int) -> Int { return n * 2 }
+
+            verify doubling() {
+                let x = double(4)
+                ensure x == 8
+                ensure x > 10
+                ensure double(0) == 0
             }
-            Expression::Block(stmts) => {
-                let previous = self.environment.clone();
-                self.environment = Environment::with_parent(self.environment.clone());
-                
-                let mut result = Value::Unit;
-                for stmt in stmts {
-                    result = self.execute_statement(stmt)?;
-                }
-                
-                self.environment = previous;
-                Ok(result)
+
+            verify sorting() {
+                ensure sort([3, 1, 2]) == [1, 2, 3]
             }
-            Expression::If { condition, then_branch, else_branch } => {
-                let cond_val = self.evaluate(condition)?;
-                
-                if cond_val.is_truthy() {
-                    self.evaluate(then_branch)
-                } else if let Some(else_expr) = else_branch {
-                    self.evaluate(else_expr)
-                } else {
-                    Ok(Value::Unit)
-                }
+        "#;
+
+        let outcomes = run_with(source, |interpreter, ast| interpreter.verify(ast)).unwrap();
+        assert_eq!(outcomes, vec![
+            VerifyOutcome { name: "doubling".to_string(), passed: vec![true, false, true] },
+            VerifyOutcome { name: "sorting".to_string(), passed: vec![true] },
+        ]);
+
+        // Verify blocks don't run as part of a normal program
+        assert_eq!(run_source(source).unwrap(), Value::Unit);
+    }
+
+    #[test]
+    fn test_padding() {
+        let source = r#"
+            proto main() {
+                let both = [pad_left("42", 5, "0"), pad_right("ab", 4, "."), center("mid", 8, "*")]
+                let unicode = pad_left("héllo", 6, "é")
+                let wide = pad_right("already wide", 3, " ")
+                return both + [unicode, wide, center("", 2, "-")]
             }
-            Expression::FieldAccess { object, field } => {
-                let obj_val = self.evaluate(object)?;
-                
-                match obj_val {
-                    Value::Record(fields) => {
-                        fields.get(field)
-                            .cloned()
-                            .ok_or_else(|| RuntimeError::Custom(
-                                format!("Field '{}' not found", field)
-                            ))
-                    }
-                    _ => Err(RuntimeError::TypeError("Not a record".to_string())),
-                }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let padded: Vec<String> = result.as_list().unwrap().iter().map(|v| v.to_string()).collect();
+        assert_eq!(padded, vec!["00042", "ab..", "**mid***", "éhéllo", "already wide", "--"]);
+
+        let error = run_source(r#"proto main() { return pad_left("x", 3, "ab") }"#).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid operation: pad_left() requires a single-character fill, got \"ab\"");
+        let error = run_source(r#"proto main() { return center("x", 3, "") }"#).unwrap_err();
+        assert!(error.to_string().contains("single-character fill"));
+    }
+
+    #[test]
+    fn test_module_path_calls() {
+        let source = r#"
+            import math
+            import math as m
+
+            proto main() {
+                let root = math::sqrt(16.0)
+                let apply = f => f(2.0, 3.0)
+                return [root, m::pow(2.0, 10.0), math::pi > 3.0, apply(math::pow)]
             }
-            Expression::IndexAccess { object, index } => {
-                let obj_val = self.evaluate(object)?;
-                let idx_val = self.evaluate(index)?;
-                
-                match obj_val {
-                    Value::List(items) => {
-                        let idx = idx_val.as_integer()?;
-                        if idx < 0 || idx as usize >= items.len() {
-                            return Err(RuntimeError::IndexOutOfBounds {
-                                index: idx,
-                                len: items.len(),
-                            });
-                        }
-                        Ok(items[idx as usize].clone())
-                    }
-                    Value::String(s) => {
-                        let idx = idx_val.as_integer()?;
-                        if idx < 0 || idx as usize >= s.len() {
-                            return Err(RuntimeError::IndexOutOfBounds {
-                                index: idx,
-                                len: s.len(),
-                            });
-                        }
-                        Ok(Value::String(s.chars().nth(idx as usize).unwrap().to_string()))
-                    }
-                    _ => Err(RuntimeError::TypeError("Not indexable".to_string())),
-                }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result, Value::list(vec![
+            Value::Float(4.0),
+            Value::Float(1024.0),
+            Value::Boolean(true),
+            Value::Float(8.0),
+        ]));
+
+        let err = run_source("import math\nproto main() { return math::cube(2.0) }").unwrap_err();
+        assert!(err.to_string().contains("cube"));
+    }
+
+    #[test]
+    fn test_complex_arithmetic() {
+        let source = r#"
+            proto main() {
+                let z = complex(1.0, 2.0)
+                let w = complex(3.0, -4.0)
+                let ops = [z + w, z - w, z * w, z / w]
+                let mixed = [z + 1, 2.5 * z, z / 2, complex(0.0, 1.0) * complex(0.0, 1.0)]
+                return ops + mixed
             }
-            Expression::Lambda { params, body } => {
-                // Create a lambda function
-                let lambda_func = FunctionDecl {
-                    mode: FunctionMode::Proto,
-                    name: "<lambda>".to_string(),
-                    params: params.clone(),
-                    return_type: None,
-                    body: vec![Statement::Expression((**body).clone())],
-                };
-                
-                Ok(Value::Function(FunctionValue::UserDefined {
-                    decl: lambda_func,
-                    closure: Some(self.environment.snapshot()),
-                }))
+        "#;
+
+        let result = run_source(source).unwrap();
+        let shown: Vec<String> = result.as_list().unwrap().iter().map(|v| v.to_string()).collect();
+        assert_eq!(shown, vec![
+            "4-2i", "-2+6i", "11+2i", "-0.2+0.4i",
+            "2+2i", "2.5+5i", "0.5+1i", "-1+0i",
+        ]);
+
+        let err = run_source("proto main() { return complex(1.0, 1.0) / complex(0.0, 0.0) }").unwrap_err();
+        assert_eq!(err, RuntimeError::Custom("Division by zero".to_string()));
+        let err = run_source(r#"proto main() { return complex(1.0, 1.0) + "i" }"#).unwrap_err();
+        assert_eq!(err, RuntimeError::TypeError("Cannot add Complex and String".to_string()));
+    }
+
+    /// Records what has been written, one entry per flush
+    #[derive(Clone, Default)]
+    struct FlushLog {
+        pending: Rc<std::cell::RefCell<String>>,
+        flushed: Rc<std::cell::RefCell<Vec<String>>>,
+    }
+
+    impl Write for FlushLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.pending.borrow_mut().push_str(std::str::from_utf8(buf).unwrap());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            let text = self.pending.take();
+            self.flushed.borrow_mut().push(text);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_line_buffered_output() {
+        let source = r#"
+            proto main() {
+                log("total", 3)
+                print("a", "b")
+                log()
             }
-            Expression::Claim(expr) => {
-                // In the interpreter, claim is essentially a no-op
-                // It marks ownership transfer but doesn't change behavior
-                self.evaluate(expr)
+        "#;
+
+        let sink = FlushLog::default();
+        run_with(source, |interpreter, ast| {
+            interpreter.set_output(Box::new(sink.clone()));
+            interpreter.interpret(ast).unwrap()
+        });
+        assert!(sink.flushed.borrow().is_empty());
+        assert_eq!(*sink.pending.borrow(), "total 3\na b\n");
+
+        let sink = FlushLog::default();
+        run_with(source, |interpreter, ast| {
+            interpreter.set_output(Box::new(sink.clone()));
+            interpreter.set_line_buffered(true);
+            interpreter.interpret(ast).unwrap()
+        });
+        assert_eq!(*sink.flushed.borrow(), vec!["total 3\n", "a b", "\n"]);
+    }
+
+    #[test]
+    fn test_apply_and_call() {
+        let source = r#"
+            proto add(a, b) { return a + b }
+
+            proto main() {
+                let ops = [add, (a, b) => a * b]
+                let applied = flat_map(ops, op => [apply(op, [6, 7])])
+                let called = [call(add, 1, 2), call(x => x + 1, 41), call(len, "four")]
+                return applied + called
             }
-        }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let expected = [13, 42, 3, 42, 4];
+        assert_eq!(result, Value::list(expected.into_iter().map(Value::Integer).collect()));
+
+        let err = run_source("proto add(a, b) { return a + b }\nproto main() { return apply(add, [1]) }").unwrap_err();
+        assert_eq!(err, RuntimeError::ArityMismatch { function: "add".to_string(), expected: 2, got: 1 });
+        let err = run_source("proto main() { return call(len, [1], [2]) }").unwrap_err();
+        assert_eq!(err, RuntimeError::ArityMismatch { function: "len".to_string(), expected: 1, got: 2 });
+        let err = run_source("proto main() { return apply(1, []) }").unwrap_err();
+        assert_eq!(err, RuntimeError::TypeError("Expected Function, got Int".to_string()));
     }
 
-    /// Evaluate a literal
-    fn evaluate_literal(&mut self, lit: &Literal) -> Result<Value, RuntimeError> {
-        match lit {
-            Literal::Integer(n) => Ok(Value::Integer(*n)),
-            Literal::Float(n) => Ok(Value::Float(*n)),
-            Literal::String(s) => Ok(Value::String(s.clone())),
-            Literal::Boolean(b) => Ok(Value::Boolean(*b)),
-            Literal::List(items) => {
-                let values: Result<Vec<_>, _> = items.iter()
-                    .map(|e| self.evaluate(e))
-                    .collect();
-                Ok(Value::List(values?))
+    #[test]
+    fn test_env_vars() {
+        let source = r#"
+            proto main() {
+                return [env("MORPH_TEST_MODE"), env("PATH") == "", env("MORPH_TEST_UNSET")]
             }
-            Literal::Record(fields) => {
-                let mut map = HashMap::new();
-                for (name, expr) in fields {
-                    let value = self.evaluate(expr)?;
-                    map.insert(name.clone(), value);
+        "#;
+
+        let result = run_with(source, |interpreter, ast| {
+            interpreter.set_env_var("MORPH_TEST_MODE".to_string(), "fast".to_string());
+            interpreter.set_env_var("PATH".to_string(), String::new());
+            interpreter.interpret(ast)
+        });
+        assert_eq!(result.unwrap(), Value::list(vec![
+            Value::String("fast".to_string()),
+            Value::Boolean(true),
+            Value::String(String::new()),
+        ]));
+
+        // The process environment is visible without any overrides
+        let result = run_source(source).unwrap();
+        let expected_path = std::env::var("PATH").unwrap_or_default();
+        assert_eq!(result.as_list().unwrap()[1], Value::Boolean(expected_path.is_empty()));
+    }
+
+    #[test]
+    fn test_range_patterns_are_inclusive() {
+        let source = r#"
+            proto size(n) {
+                return match n {
+                    1..9 => "small",
+                    10..10 => "ten",
+                    _ => "other"
                 }
-                Ok(Value::Record(map))
             }
-        }
+
+            proto main() {
+                return [size(0), size(1), size(9), size(10), size(11)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        let sizes: Vec<String> = result.as_list().unwrap().iter().map(|v| v.to_string()).collect();
+        assert_eq!(sizes, vec!["other", "small", "small", "ten", "other"]);
     }
 
-    /// Evaluate binary operation
-    fn evaluate_binary_op(&self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, RuntimeError> {
-        match op {
-            BinaryOp::Add => self.add_values(left, right),
-            BinaryOp::Subtract => self.subtract_values(left, right),
-            BinaryOp::Multiply => self.multiply_values(left, right),
-            BinaryOp::Divide => self.divide_values(left, right),
-            BinaryOp::Modulo => self.modulo_values(left, right),
-            BinaryOp::Equal => Ok(Value::Boolean(left == right)),
-            BinaryOp::NotEqual => Ok(Value::Boolean(left != right)),
-            BinaryOp::Less => self.compare_values(left, right, |c| c == std::cmp::Ordering::Less),
-            BinaryOp::LessEq => self.compare_values(left, right, |c| {
-                c == std::cmp::Ordering::Less || c == std::cmp::Ordering::Equal
-            }),
-            BinaryOp::Greater => self.compare_values(left, right, |c| c == std::cmp::Ordering::Greater),
-            BinaryOp::GreaterEq => self.compare_values(left, right, |c| {
-                c == std::cmp::Ordering::Greater || c == std::cmp::Ordering::Equal
-            }),
-        }
+    #[test]
+    fn test_flatten_and_transpose() {
+        let source = r#"
+            proto main() {
+                let top = [1, 2, 3]
+                let bottom = [4, 5, 6]
+                let matrix = [top, bottom]
+                return [flatten(matrix), flatten(transpose(matrix)), transpose(matrix)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result.to_string(), "[[1, 2, 3, 4, 5, 6], [1, 4, 2, 5, 3, 6], [[1, 4], [2, 5], [3, 6]]]");
+
+        let ragged = r#"
+            proto main() {
+                let top = [1, 2, 3]
+                let bottom = [4, 5]
+                return transpose([top, bottom])
+            }
+        "#;
+        let err = run_source(ragged).unwrap_err();
+        assert!(err.to_string().contains("row 1 has 2 elements instead of 3"), "{}", err);
     }
 
-    /// Add two values
-    fn add_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
-        match (left, right) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a + b)),
-            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
-            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(*a as f64 + b)),
-            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a + *b as f64)),
-            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
-            (Value::List(a), Value::List(b)) => {
-                let mut result = a.clone();
-                result.extend(b.clone());
-                Ok(Value::List(result))
+    #[test]
+    fn test_min_by_and_max_by() {
+        let source = r#"
+            proto main() {
+                let ada = { name: "ada", age: 36 }
+                let alan = { name: "alan", age: 41 }
+                let grace = { name: "grace", age: 36 }
+                let linus = { name: "linus", age: 28 }
+                let people = [ada, alan, grace, linus]
+                let by_age = person => person.age
+                return [min_by(people, by_age).name, max_by(people, by_age).name, min_by([36, 28, 28], x => x)]
             }
-            _ => Err(RuntimeError::TypeError(
-                format!("Cannot add {} and {}", left.type_name(), right.type_name())
-            )),
-        }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result.to_string(), "[linus, alan, 28]");
+
+        let err = run_source("proto main() { return max_by([], x => x) }").unwrap_err();
+        assert!(err.to_string().contains("max_by() of an empty list"), "{}", err);
     }
 
-    /// Subtract two values
-    fn subtract_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
-        match (left, right) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a - b)),
-            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
-            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(*a as f64 - b)),
-            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a - *b as f64)),
-            _ => Err(RuntimeError::TypeError(
-                format!("Cannot subtract {} and {}", left.type_name(), right.type_name())
-            )),
-        }
+    #[test]
+    fn test_min_by_ties_keep_first() {
+        let source = r#"
+            proto main() {
+                let words = ["pear", "fig", "kiwi", "yam"]
+                return [min_by(words, len), max_by(words, len)]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result.to_string(), "[fig, pear]");
     }
 
-    /// Multiply two values
-    fn multiply_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
-        match (left, right) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a * b)),
-            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
-            (Value::Integer(a), Value::Float(b)) => Ok(Value::Float(*a as f64 * b)),
-            (Value::Float(a), Value::Integer(b)) => Ok(Value::Float(a * *b as f64)),
-            _ => Err(RuntimeError::TypeError(
-                format!("Cannot multiply {} and {}", left.type_name(), right.type_name())
-            )),
-        }
+    #[test]
+    fn test_slices() {
+        let source = r#"
+            proto main() {
+                let items = [10, 20, 30, 40, 50]
+                let word = "héllo"
+                return [items[1:3], items[:2], items[2:], items[-2:], items[1:-1], items[3:1], items[-10:10], word[1:4], word[:-2]]
+            }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "[[20, 30], [10, 20], [30, 40, 50], [40, 50], [20, 30, 40], [], [10, 20, 30, 40, 50], éll, hél]"
+        );
     }
 
-    /// Divide two values
-    fn divide_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
-        match (left, right) {
-            (Value::Integer(a), Value::Integer(b)) => {
-                if *b == 0 {
-                    return Err(RuntimeError::Custom("Division by zero".to_string()));
-                }
-                Ok(Value::Integer(a / b))
+    #[test]
+    fn test_error_builtin() {
+        let source = r#"
+            proto safe_div(a, b) {
+                return if b == 0 { error("division by zero in safe_div") } else { a / b }
             }
-            (Value::Float(a), Value::Float(b)) => {
-                if *b == 0.0 {
-                    return Err(RuntimeError::Custom("Division by zero".to_string()));
-                }
-                Ok(Value::Float(a / b))
+
+            proto main() {
+                return safe_div(6, 3) + safe_div(1, 0)
             }
-            (Value::Integer(a), Value::Float(b)) => {
-                if *b == 0.0 {
-                    return Err(RuntimeError::Custom("Division by zero".to_string()));
-                }
-                Ok(Value::Float(*a as f64 / b))
+        "#;
+
+        let err = run_source(source).unwrap_err();
+        assert_eq!(err.to_string(), "division by zero in safe_div");
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let source = r#"
+            proto down(n) {
+                return down(n + 1)
             }
-            (Value::Float(a), Value::Integer(b)) => {
-                if *b == 0 {
-                    return Err(RuntimeError::Custom("Division by zero".to_string()));
-                }
-                Ok(Value::Float(a / *b as f64))
+
+            proto main() {
+                return down(0)
             }
-            _ => Err(RuntimeError::TypeError(
-                format!("Cannot divide {} and {}", left.type_name(), right.type_name())
-            )),
-        }
-    }
+        "#;
+        // Reaching the default limit takes more stack than a test thread has
+        let err = std::thread::Builder::new()
+            .stack_size(256 * 1024 * 1024)
+            .spawn(move || run_source(source).unwrap_err().to_string())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(err, "max recursion depth exceeded");
 
-    /// Modulo two values
-    fn modulo_values(&self, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
-        match (left, right) {
-            (Value::Integer(a), Value::Integer(b)) => {
-                if *b == 0 {
-                    return Err(RuntimeError::Custom("Modulo by zero".to_string()));
-                }
-                Ok(Value::Integer(a % b))
+        let source = r#"
+            proto count(n) {
+                return if n == 0 { 0 } else { 1 + count(n - 1) }
             }
-            _ => Err(RuntimeError::TypeError(
-                format!("Cannot modulo {} and {}", left.type_name(), right.type_name())
-            )),
-        }
-    }
 
-    /// Compare two values
-    fn compare_values<F>(&self, left: &Value, right: &Value, pred: F) -> Result<Value, RuntimeError>
-    where
-        F: Fn(std::cmp::Ordering) -> bool,
-    {
-        let ordering = match (left, right) {
-            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
-            (Value::Float(a), Value::Float(b)) => {
-                if a < b {
-                    std::cmp::Ordering::Less
-                } else if a > b {
-                    std::cmp::Ordering::Greater
-                } else {
-                    std::cmp::Ordering::Equal
-                }
+            proto main() {
+                return [count(9), count(10)]
             }
-            (Value::Integer(a), Value::Float(b)) => {
-                let af = *a as f64;
-                if af < *b {
-                    std::cmp::Ordering::Less
-                } else if af > *b {
-                    std::cmp::Ordering::Greater
-                } else {
-                    std::cmp::Ordering::Equal
+        "#;
+        let err = run_with(source, |interpreter, ast| {
+            interpreter.set_max_depth(11);
+            interpreter.interpret(ast)
+        }).unwrap_err();
+        assert_eq!(err.to_string(), "max recursion depth exceeded");
+    }
+
+    #[test]
+    fn test_boolean_patterns() {
+        let source = r#"
+            proto bit(b) {
+                return match b {
+                    true => 1,
+                    false => 0
                 }
             }
-            (Value::Float(a), Value::Integer(b)) => {
-                let bf = *b as f64;
-                if *a < bf {
-                    std::cmp::Ordering::Less
-                } else if *a > bf {
-                    std::cmp::Ordering::Greater
-                } else {
-                    std::cmp::Ordering::Equal
-                }
+
+            proto main() {
+                return [bit(true), bit(false), bit(3 > 2)]
             }
-            (Value::String(a), Value::String(b)) => a.cmp(b),
-            _ => return Err(RuntimeError::TypeError(
-                format!("Cannot compare {} and {}", left.type_name(), right.type_name())
-            )),
-        };
-        
-        Ok(Value::Boolean(pred(ordering)))
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result.to_string(), "[1, 0, 1]");
     }
 
-    /// Evaluate unary operation
-    fn evaluate_unary_op(&self, op: &UnaryOp, val: &Value) -> Result<Value, RuntimeError> {
-        match op {
-            UnaryOp::Negate => {
-                match val {
-                    Value::Integer(n) => Ok(Value::Integer(-n)),
-                    Value::Float(n) => Ok(Value::Float(-n)),
-                    _ => Err(RuntimeError::TypeError(
-                        format!("Cannot negate {}", val.type_name())
-                    )),
-                }
+    #[test]
+    fn test_large_lists() {
+        let source = r#"
+            proto main() {
+                let numbers = range(0, 100000)
+                let flat = flatten(chunk(numbers, 7))
+                let doubled = flat_map(range(0, 10000), n => [n, n])
+                return [len(numbers), numbers[99999], len(flat), flat[12345], len(doubled), doubled[19999], range(0, 10, 3)]
             }
-            UnaryOp::Not => Ok(Value::Boolean(!val.is_truthy())),
-        }
+        "#;
+
+        let result = run_source(source).unwrap();
+        assert_eq!(result.to_string(), "[100000, 99999, 100000, 12345, 20000, 9999, [0, 3, 6, 9]]");
     }
 
-    /// Check if a value matches a pattern
-    fn match_pattern(&self, value: &Value, pattern: &Pattern) -> Result<bool, RuntimeError> {
-        match pattern {
-            Pattern::Wildcard => Ok(true),
-            Pattern::Literal(lit) => {
-                let lit_val = match lit {
-                    Literal::Integer(n) => Value::Integer(*n),
-                    Literal::Float(n) => Value::Float(*n),
-                    Literal::String(s) => Value::String(s.clone()),
-                    Literal::Boolean(b) => Value::Boolean(*b),
-                    _ => return Err(RuntimeError::Custom(
-                        "Complex literals in patterns not yet supported".to_string()
-                    )),
-                };
-                Ok(value == &lit_val)
+    #[test]
+    fn test_spread_arguments() {
+        let source = r#"
+            proto add3(a, b, c) {
+                return a * 100 + b * 10 + c
             }
-            Pattern::Identifier(_) => Ok(true), // Bind the value to the identifier
-            Pattern::Range(start, end) => {
-                // Simplified range matching
-                let start_val = match start.as_ref() {
-                    Pattern::Literal(Literal::Integer(n)) => *n,
-                    _ => return Err(RuntimeError::Custom(
-                        "Range patterns must use integer literals".to_string()
-                    )),
-                };
-                let end_val = match end.as_ref() {
-                    Pattern::Literal(Literal::Integer(n)) => *n,
-                    _ => return Err(RuntimeError::Custom(
-                        "Range patterns must use integer literals".to_string()
-                    )),
-                };
-                
-                match value {
-                    Value::Integer(n) => Ok(*n >= start_val && *n <= end_val),
-                    _ => Ok(false),
-                }
+
+            proto forward(args) {
+                return add3(...args)
             }
-            Pattern::Tuple(_) => Err(RuntimeError::Custom(
-                "Tuple patterns not yet supported".to_string()
-            )),
-        }
-    }
-}
 
-impl Default for Interpreter {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+            proto main() {
+                let rest = [2, 3]
+                let none = []
+                return [forward([1, 2, 3]), add3(1, ...rest), add3(...none, 4, ...rest), 5 |> add3(...rest)]
+            }
+        "#;
+        assert_eq!(run_source(source).unwrap().to_string(), "[123, 123, 423, 523]");
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::lexer::Lexer;
-    use crate::parser::Parser;
+        // Spreads are flattened before the arity check
+        let source = r#"
+            proto add3(a, b, c) {
+                return a + b + c
+            }
 
-    fn run_source(source: &str) -> Result<Value, RuntimeError> {
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.tokenize().unwrap();
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse().unwrap();
-        
-        let mut interpreter = Interpreter::new();
-        interpreter.interpret(&ast)
+            proto main() {
+                return add3(1, ...[2, 3, 4])
+            }
+        "#;
+        assert!(matches!(run_source(source), Err(RuntimeError::ArityMismatch { expected: 3, got: 4, .. })));
+
+        let source = r#"
+            proto main() {
+                return max(...5)
+            }
+        "#;
+        assert!(run_source(source).is_err());
     }
 
     #[test]
-    fn test_arithmetic() {
+    fn test_cast_expressions() {
         let source = r#"
             proto main() {
-                return 1 + 2 * 3
+                let n = 7
+                let half = n as Float / 2.0
+                return [half, 2.9 as Int, -2.9 as Int, n as String, 1.5 as String, n * 2 as Float]
             }
         "#;
-        
-        let result = run_source(source).unwrap();
-        assert_eq!(result, Value::Integer(7));
+        assert_eq!(run_source(source).unwrap().to_string(), "[3.5, 2, -2, 7, 1.5, 14]");
+        let source = "proto main() { return [true as Bool, \"s\" as String, 1 as Int, 0.5 as Float] }";
+        assert_eq!(run_source(source).unwrap().to_string(), "[true, s, 1, 0.5]");
+
+        let error = |source: &str| run_source(&format!("proto main() {{ return {} }}", source)).unwrap_err().to_string();
+        assert_eq!(error(r#""7" as Int"#), "Invalid operation: Cannot cast 7 (String) to Int");
+        assert_eq!(error("true as Float"), "Invalid operation: Cannot cast true (Bool) to Float");
+        assert_eq!(error("[1] as List<Float>"), "Invalid operation: Cannot cast [1] (List) to List<Float>");
+        assert_eq!(error(r#"to_float("inf") as Int"#), "Invalid operation: Cannot cast inf (Float) to Int");
     }
 
     #[test]
-    fn test_variables() {
+    fn test_named_arguments() {
         let source = r#"
+            proto greet(name, greeting, punctuation) {
+                return greeting + ", " + name + punctuation
+            }
+
             proto main() {
-                let x = 10
-                let y = 20
-                return x + y
+                let first = greet(name: "Ada", greeting: "Hi", punctuation: "!")
+                let second = greet("Ada", punctuation: "?", greeting: "Hello")
+                let third = "Bob" |> greet(punctuation: ".", greeting: "Hey")
+                return [first, second, third]
             }
         "#;
-        
-        let result = run_source(source).unwrap();
-        assert_eq!(result, Value::Integer(30));
+        assert_eq!(run_source(source).unwrap().to_string(), "[Hi, Ada!, Hello, Ada?, Hey, Bob.]");
+
+        let call = |args: &str| run_source(&format!(r#"
+            proto greet(name, greeting) {{
+                return greeting + name
+            }}
+
+            proto main() {{
+                return greet({})
+            }}
+        "#, args));
+        assert_eq!(
+            call(r#"name: "Ada", greting: "Hi""#).unwrap_err().to_string(),
+            "greet has no parameter called greting",
+        );
+        assert_eq!(
+            call(r#""Ada", name: "Bob""#).unwrap_err().to_string(),
+            "greet is given argument name twice",
+        );
+        assert_eq!(
+            call(r#"greeting: "Hi""#).unwrap_err().to_string(),
+            "greet is missing argument name",
+        );
+        assert!(call(r#"name: "Ada", greeting: "Hi""#).is_ok());
     }
 
     #[test]
-    fn test_if_expression() {
+    fn test_char_at_and_substring() {
         let source = r#"
             proto main() {
-                if true {
-                    return 42
-                } else {
-                    return 0
-                }
+                let word = "héllo wörld"
+                return [char_at(word, 1), char_at(word, -1), char_at(word, -4), substring(word, 1, 4), substring(word, -5, 100), substring(word, -100, 2), substring(word, 4, 2)]
             }
         "#;
-        
-        let result = run_source(source).unwrap();
-        assert_eq!(result, Value::Integer(42));
+        assert_eq!(run_source(source).unwrap().to_string(), "[é, d, ö, éll, wörld, hé, ]");
+
+        for index in ["11", "-12"] {
+            let source = format!(r#"
+                proto main() {{
+                    return char_at("héllo wörld", {})
+                }}
+            "#, index);
+            assert!(matches!(run_source(&source), Err(RuntimeError::IndexOutOfBounds { len: 11, .. })));
+        }
+
+        let source = r#"
+            proto main() {
+                return substring("text", "1", 2)
+            }
+        "#;
+        assert!(run_source(source).is_err());
     }
 
     #[test]
-    fn test_match_expression() {
+    fn test_stdin_builtins() {
         let source = r#"
             proto main() {
-                return match 5 {
-                    _ => 42
-                }
+                let lines = stdin_lines()
+                return [len(stdin()), len(lines), lines[1]]
             }
         "#;
-        
-        let result = run_source(source).unwrap();
-        assert_eq!(result, Value::Integer(42));
+
+        let result = run_with(source, |interpreter, ast| {
+            interpreter.set_input(Box::new(std::io::Cursor::new("first\nsecond\r\nthird\n")));
+            interpreter.interpret(ast)
+        });
+        // Both builtins see the whole input, however many times they're called
+        assert_eq!(result.unwrap().to_string(), "[20, 3, second]");
+
+        assert!(run_with(source, |_, ast| Interpreter::new_sandboxed().interpret(ast)).is_err());
     }
 
     #[test]
-    fn test_function_call() {
-        // Note: Currently functions must be defined before they are called
-        // due to single-pass interpretation
+    fn test_repeated_field_access() {
         let source = r#"
             proto main() {
-                return 42
+                let point = { x: 3, y: 4, label: "p" }
+                let areas = fill(5000, i => point.x * point.y)
+                return [len(areas), areas[0], areas[4999]]
             }
         "#;
-        
-        let result = run_source(source).unwrap();
-        assert_eq!(result, Value::Integer(42));
+
+        assert_eq!(run_source(source).unwrap().to_string(), "[5000, 12, 12]");
+
+        // Records built from JSON and from literals share field names
+        let json = serde_json::json!({ "x": 3 });
+        let Value::Record(fields) = Value::from_json(json) else {
+            panic!("Expected a record");
+        };
+        assert_eq!(fields.get(&Symbol::new("x")), Some(&Value::Integer(3)));
     }
 
     #[test]
-    fn test_list() {
+    fn test_runtime_names_stay_with_interpreter() {
         let source = r#"
             proto main() {
-                let items = [1, 2, 3]
-                return items[0]
+                let point = { x: 3, y: 4 }
+                let groups = group_by([1, 2, 3], n => n % 2 == 0 ? "even" : "odd")
+                return ["x" in point, "mis" + "sing" in point, len(groups.odd)]
             }
         "#;
-        
-        let result = run_source(source).unwrap();
-        assert_eq!(result, Value::Integer(1));
+
+        run_with(source, |interpreter, ast| {
+            let result = interpreter.interpret(ast).unwrap();
+            assert_eq!(result.to_string(), "[true, false, 2]");
+            // Keys a program builds are interned; names it only tests for aren't
+            assert!(interpreter.symbols.lookup("even").is_some());
+            assert!(interpreter.symbols.lookup("missing").is_none());
+        });
     }
 
     #[test]
-    fn test_for_loop() {
-        // Note: Assignment in loops requires mutable variables
-        // This test uses a simpler approach
+    fn test_halt_on_first_log() {
         let source = r#"
             proto main() {
-                let items = [1, 2, 3]
-                return items[0] + items[1] + items[2]
+                let total = 1 + 2
+                log("total", total)
+                log("never shown")
+                return total
             }
         "#;
-        
-        let result = run_source(source).unwrap();
-        assert_eq!(result, Value::Integer(6));
+
+        let sink = FlushLog::default();
+        let error = run_with(source, |interpreter, ast| {
+            interpreter.set_output(Box::new(sink.clone()));
+            interpreter.set_halt_on_first_log(true);
+            interpreter.interpret(ast)
+        }).unwrap_err();
+
+        assert_eq!(error, RuntimeError::Halted(Span::new(4, 17)));
+        assert_eq!(error.to_string(), "Halted after the first log or print, at line 4, column 17");
+        assert_eq!(*sink.flushed.borrow(), vec!["total 3\n"]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_output_limit() {
+        let run = |source: &str, limit: usize| {
+            let sink = FlushLog::default();
+            let result = run_with(source, |interpreter, ast| {
+                interpreter.set_output(Box::new(sink.clone()));
+                interpreter.set_output_limit(limit);
+                interpreter.interpret(ast)
+            });
+            let written = sink.flushed.borrow().concat();
+            (result, written)
+        };
+
+        let (result, written) = run(r#"
+            proto main() {
+                for i in range(0, 100) {
+                    log("line", i)
+                }
+                return 0
+            }
+        "#, 3);
+        let error = result.unwrap_err();
+        assert_eq!(error, RuntimeError::OutputLimit(3));
+        assert_eq!(error.to_string(), "Stopped after the output limit of 3 lines");
+        assert_eq!(written, "line 0\nline 1\nline 2\n");
+
+        // A single log spanning the limit is cut at it
+        let (result, written) = run("proto main() { log(\"a\nb\nc\") }", 2);
+        assert_eq!(result.unwrap_err(), RuntimeError::OutputLimit(2));
+        assert_eq!(written, "a\nb\n");
+
+        // Output within the limit runs to the end
+        let (result, _) = run(r#"proto main() { log("a") log("b") return 1 }"#, 2);
+        assert_eq!(result.unwrap(), Value::Integer(1));
+
+        // A line counts as soon as it's started, before its line break
+        let (result, written) = run(r#"proto main() { log("a") print("b") print("c") log() print("d") }"#, 2);
+        assert_eq!(result.unwrap_err(), RuntimeError::OutputLimit(2));
+        assert_eq!(written, "a\nbc\n");
+
+        // ...and one that never ends counts again every OUTPUT_LINE_WIDTH characters
+        let (result, written) = run(r#"
+            proto main() {
+                for i in range(0, 100000) {
+                    print("x")
+                }
+                return 0
+            }
+        "#, 3);
+        assert_eq!(result.unwrap_err(), RuntimeError::OutputLimit(3));
+        assert_eq!(written, "x".repeat(3 * OUTPUT_LINE_WIDTH));
+    }
+}
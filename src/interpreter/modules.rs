@@ -0,0 +1,48 @@
+use indexmap::IndexMap;
+use crate::symbol::Symbol;
+use super::value::{Value, RuntimeError, FunctionValue, BuiltinFn};
+
+/// Load a built-in module, returning its exports as a record of members
+pub fn load_module(name: &str) -> Option<Value> {
+    let members = match name {
+        "math" => math_module(),
+        _ => return None,
+    };
+    Some(Value::Record(members))
+}
+
+/// The `math` module - floating point helpers
+fn math_module() -> IndexMap<Symbol, Value> {
+    let mut members = IndexMap::new();
+
+    let functions: [(&str, BuiltinFn); 5] = [
+        ("sqrt", |args| Ok(Value::Float(unary_float("math.sqrt", args)?.sqrt()))),
+        ("floor", |args| Ok(Value::Float(unary_float("math.floor", args)?.floor()))),
+        ("ceil", |args| Ok(Value::Float(unary_float("math.ceil", args)?.ceil()))),
+        ("abs", |args| match args {
+            [Value::Integer(n)] => Ok(Value::Integer(n.abs())),
+            _ => Ok(Value::Float(unary_float("math.abs", args)?.abs())),
+        }),
+        ("pow", |args| {
+            if args.len() != 2 {
+                return Err(RuntimeError::ArityMismatch { function: "math.pow".to_string(), expected: 2, got: args.len() });
+            }
+            Ok(Value::Float(args[0].as_float()?.powf(args[1].as_float()?)))
+        }),
+    ];
+
+    for (name, func) in functions {
+        members.insert(Symbol::new(name), Value::Function(FunctionValue::Builtin(func)));
+    }
+    members.insert(Symbol::new("pi"), Value::Float(std::f64::consts::PI));
+
+    members
+}
+
+/// Extract the single numeric argument of the one-argument math function `name`
+fn unary_float(name: &str, args: &[Value]) -> Result<f64, RuntimeError> {
+    if args.len() != 1 {
+        return Err(RuntimeError::ArityMismatch { function: name.to_string(), expected: 1, got: args.len() });
+    }
+    args[0].as_float()
+}
@@ -1,6 +1,11 @@
 use std::collections::HashMap;
 use std::fmt;
-use crate::ast::{FunctionDecl, Expression};
+use indexmap::IndexMap;
+use crate::symbol::{Symbol, SymbolTable};
+use std::rc::Rc;
+use std::time::Instant;
+use crate::ast::{FunctionDecl, Span};
+use super::Interpreter;
 
 /// Runtime values in Morph
 #[derive(Debug, Clone, PartialEq)]
@@ -9,46 +14,76 @@ pub enum Value {
     Integer(i64),
     /// Floating point value
     Float(f64),
+    /// Complex number: real and imaginary parts
+    Complex(f64, f64),
     /// String value
     String(String),
     /// Boolean value
     Boolean(bool),
-    /// List of values
-    List(Vec<Value>),
-    /// Record/object with fields
-    Record(HashMap<String, Value>),
+    /// List of values, shared between copies until one of them is mutated
+    List(Rc<Vec<Value>>),
+    /// Record/object with fields, in the order they were defined
+    Record(IndexMap<Symbol, Value>),
     /// Function value
     Function(FunctionValue),
+    /// Point in time from `now()`, only useful for measuring `elapsed` time
+    Instant(Instant),
     /// Unit/void value (for statements that don't return anything)
     Unit,
 }
 
 /// Function value that can be called
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum FunctionValue {
     /// User-defined function
     UserDefined {
-        decl: FunctionDecl,
+        decl: Rc<FunctionDecl>,
         /// Captured closure environment
         closure: Option<HashMap<String, Value>>,
     },
     /// Built-in/native function
     Builtin(BuiltinFn),
+    /// Built-in function that can call back into the interpreter
+    /// (e.g. to invoke a function passed as an argument)
+    Intrinsic(IntrinsicFn),
 }
 
 /// Built-in function type
 pub type BuiltinFn = fn(&[Value]) -> Result<Value, RuntimeError>;
 
+/// Interpreter-aware built-in function type
+pub type IntrinsicFn = fn(&mut Interpreter, &[Value]) -> Result<Value, RuntimeError>;
+
+impl PartialEq for FunctionValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                FunctionValue::UserDefined { decl: a, closure: ca },
+                FunctionValue::UserDefined { decl: b, closure: cb },
+            ) => a == b && ca == cb,
+            (FunctionValue::Builtin(a), FunctionValue::Builtin(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (FunctionValue::Intrinsic(a), FunctionValue::Intrinsic(b)) => std::ptr::fn_addr_eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
 /// Runtime errors
 #[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeError {
     TypeError(String),
     UndefinedVariable(String),
     UndefinedFunction(String),
-    ArityMismatch { expected: usize, got: usize },
+    ArityMismatch { function: String, expected: usize, got: usize },
     IndexOutOfBounds { index: i64, len: usize },
     InvalidOperation(String),
     Custom(String),
+    /// Execution stopped by `--halt-on-first-log` after the first output,
+    /// written by the statement at this location
+    Halted(Span),
+    /// Execution stopped by `--limit-output` once this many lines of output
+    /// had been written
+    OutputLimit(usize),
 }
 
 impl fmt::Display for RuntimeError {
@@ -57,14 +92,16 @@ impl fmt::Display for RuntimeError {
             RuntimeError::TypeError(msg) => write!(f, "Type error: {}", msg),
             RuntimeError::UndefinedVariable(name) => write!(f, "Undefined variable: {}", name),
             RuntimeError::UndefinedFunction(name) => write!(f, "Undefined function: {}", name),
-            RuntimeError::ArityMismatch { expected, got } => {
-                write!(f, "Expected {} arguments, got {}", expected, got)
+            RuntimeError::ArityMismatch { function, expected, got } => {
+                write!(f, "{} expects {} arguments, got {}", function, expected, got)
             }
             RuntimeError::IndexOutOfBounds { index, len } => {
                 write!(f, "Index {} out of bounds for list of length {}", index, len)
             }
             RuntimeError::InvalidOperation(msg) => write!(f, "Invalid operation: {}", msg),
             RuntimeError::Custom(msg) => write!(f, "{}", msg),
+            RuntimeError::Halted(span) => write!(f, "Halted after the first log or print, at {}", span),
+            RuntimeError::OutputLimit(limit) => write!(f, "Stopped after the output limit of {} lines", limit),
         }
     }
 }
@@ -72,39 +109,84 @@ impl fmt::Display for RuntimeError {
 impl std::error::Error for RuntimeError {}
 
 impl Value {
-    /// Convert value to string representation
-    pub fn to_string(&self) -> String {
-        match self {
-            Value::Integer(n) => n.to_string(),
-            Value::Float(n) => n.to_string(),
-            Value::String(s) => s.clone(),
-            Value::Boolean(b) => b.to_string(),
-            Value::List(items) => {
-                let elements: Vec<String> = items.iter().map(|v| v.to_string()).collect();
-                format!("[{}]", elements.join(", "))
-            }
-            Value::Record(fields) => {
-                let entries: Vec<String> = fields
-                    .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.to_string()))
-                    .collect();
-                format!("{{ {} }}", entries.join(", "))
+    /// Create a list value
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(items))
+    }
+
+    /// Convert a command-line argument, inferring Int, then Float, then String
+    pub fn from_arg(arg: &str) -> Value {
+        if let Ok(n) = arg.parse::<i64>() {
+            Value::Integer(n)
+        } else if let Ok(n) = arg.parse::<f64>() {
+            Value::Float(n)
+        } else {
+            Value::String(arg.to_string())
+        }
+    }
+
+    /// Convert parsed JSON: objects become records (keeping key order),
+    /// arrays lists, and null Unit. Numbers are Int when they fit.
+    pub fn from_json(json: serde_json::Value) -> Value {
+        Self::from_json_in(json, &mut SymbolTable::new())
+    }
+
+    /// `from_json`, sharing one symbol per field name through `symbols`
+    fn from_json_in(json: serde_json::Value, symbols: &mut SymbolTable) -> Value {
+        match json {
+            serde_json::Value::Null => Value::Unit,
+            serde_json::Value::Bool(b) => Value::Boolean(b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(items) => {
+                Value::list(items.into_iter().map(|item| Value::from_json_in(item, symbols)).collect())
             }
-            Value::Function(_) => "<function>".to_string(),
-            Value::Unit => "()".to_string(),
+            serde_json::Value::Object(fields) => Value::Record(
+                fields.into_iter().map(|(key, value)| (symbols.intern(&key), Value::from_json_in(value, symbols))).collect()
+            ),
         }
     }
 
+    /// Convert to JSON, the inverse of `from_json`. Complex numbers become
+    /// strings; functions and instants have no JSON form.
+    pub fn to_json(&self) -> Result<serde_json::Value, RuntimeError> {
+        Ok(match self {
+            Value::Integer(n) => serde_json::Value::from(*n),
+            // NaN and infinities become null
+            Value::Float(n) => serde_json::Value::from(*n),
+            Value::Complex(..) => serde_json::Value::String(self.to_string()),
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::Boolean(b) => serde_json::Value::Bool(*b),
+            Value::List(items) => serde_json::Value::Array(
+                items.iter().map(Value::to_json).collect::<Result<_, _>>()?
+            ),
+            Value::Record(fields) => serde_json::Value::Object(
+                fields.iter()
+                    .map(|(key, value)| Ok((key.to_string(), value.to_json()?)))
+                    .collect::<Result<_, RuntimeError>>()?
+            ),
+            Value::Function(_) | Value::Instant(_) => return Err(RuntimeError::TypeError(
+                format!("Cannot convert {} to JSON", self.type_name())
+            )),
+            Value::Unit => serde_json::Value::Null,
+        })
+    }
+
     /// Check if value is truthy
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Boolean(b) => *b,
             Value::Integer(n) => *n != 0,
             Value::Float(n) => *n != 0.0,
+            Value::Complex(re, im) => *re != 0.0 || *im != 0.0,
             Value::String(s) => !s.is_empty(),
             Value::List(items) => !items.is_empty(),
             Value::Record(fields) => !fields.is_empty(),
             Value::Function(_) => true,
+            Value::Instant(_) => true,
             Value::Unit => false,
         }
     }
@@ -114,11 +196,13 @@ impl Value {
         match self {
             Value::Integer(_) => "Int",
             Value::Float(_) => "Float",
+            Value::Complex(..) => "Complex",
             Value::String(_) => "String",
             Value::Boolean(_) => "Bool",
             Value::List(_) => "List",
             Value::Record(_) => "Record",
             Value::Function(_) => "Function",
+            Value::Instant(_) => "Instant",
             Value::Unit => "Unit",
         }
     }
@@ -174,10 +258,11 @@ impl Value {
         }
     }
 
-    /// Try to convert to mutable list
+    /// Try to convert to mutable list, copying the elements first if the
+    /// list is shared with another value
     pub fn as_list_mut(&mut self) -> Result<&mut Vec<Value>, RuntimeError> {
         match self {
-            Value::List(items) => Ok(items),
+            Value::List(items) => Ok(Rc::make_mut(items)),
             _ => Err(RuntimeError::TypeError(
                 format!("Expected List, got {}", self.type_name())
             )),
@@ -185,7 +270,7 @@ impl Value {
     }
 
     /// Try to convert to record
-    pub fn as_record(&self) -> Result<&HashMap<String, Value>, RuntimeError> {
+    pub fn as_record(&self) -> Result<&IndexMap<Symbol, Value>, RuntimeError> {
         match self {
             Value::Record(fields) => Ok(fields),
             _ => Err(RuntimeError::TypeError(
@@ -195,7 +280,7 @@ impl Value {
     }
 
     /// Try to convert to mutable record
-    pub fn as_record_mut(&mut self) -> Result<&mut HashMap<String, Value>, RuntimeError> {
+    pub fn as_record_mut(&mut self) -> Result<&mut IndexMap<Symbol, Value>, RuntimeError> {
         match self {
             Value::Record(fields) => Ok(fields),
             _ => Err(RuntimeError::TypeError(
@@ -206,8 +291,32 @@ impl Value {
 }
 
 impl fmt::Display for Value {
+    /// Convert value to string representation
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::Complex(re, im) => {
+                let sign = if im.is_sign_negative() { '-' } else { '+' };
+                write!(f, "{}{}{}i", re, sign, im.abs())
+            }
+            Value::String(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::List(items) => {
+                let elements: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Record(fields) => {
+                let entries: Vec<String> = fields
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect();
+                write!(f, "{{ {} }}", entries.join(", "))
+            }
+            Value::Function(_) => write!(f, "<function>"),
+            Value::Instant(_) => write!(f, "<instant>"),
+            Value::Unit => write!(f, "()"),
+        }
     }
 }
 
@@ -221,15 +330,15 @@ pub fn literal_to_value(lit: &crate::ast::Literal) -> Value {
         Literal::String(s) => Value::String(s.clone()),
         Literal::Boolean(b) => Value::Boolean(*b),
         Literal::List(items) => {
-            let values: Vec<Value> = items.iter().map(|e| {
+            let values: Vec<Value> = items.iter().map(|_| {
                 // For now, we can't evaluate expressions here
                 // This is handled in the interpreter
                 Value::Unit
             }).collect();
-            Value::List(values)
+            Value::list(values)
         }
         Literal::Record(fields) => {
-            let mut map = HashMap::new();
+            let mut map = IndexMap::new();
             for (name, _) in fields {
                 // For now, placeholder
                 map.insert(name.clone(), Value::Unit);
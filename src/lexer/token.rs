@@ -8,6 +8,7 @@ pub enum TokenType {
     Flow,       // flow
     Let,        // let
     Var,        // var
+    Mut,        // mut (in `let mut`)
     If,         // if
     Else,       // else
     ElseIf,     // else if
@@ -15,12 +16,18 @@ pub enum TokenType {
     For,        // for
     In,         // in
     Return,     // return
+    Break,      // break
+    Continue,   // continue
     Claim,      // claim
     Delegate,   // delegate
     Solve,      // solve
     Ensure,     // ensure
+    Verify,     // verify
+    Minimize,   // minimize
+    Maximize,   // maximize
     Where,      // where
     Import,     // import
+    As,         // as
 
     // Literals
     Identifier(String),
@@ -46,10 +53,13 @@ pub enum TokenType {
     Greater,    // >
     GreaterEqual,// >=
     Arrow,      // =>
+    ThinArrow,  // ->
     Dot,        // .
     DotDot,     // ..
+    Ellipsis,   // ...
     Colon,      // :
     ColonColon, // ::
+    Question,   // ?
 
     // Delimiters
     LeftParen,      // (
@@ -64,6 +74,7 @@ pub enum TokenType {
     // Special
     Ghost,      // <Ghost: ...>
     Comment,    // // ...
+    DocComment(String), // /// ...
     Newline,
     Eof,
 }
@@ -94,6 +105,7 @@ impl Token {
             (TokenType::String(_), TokenType::String(_)) => true,
             (TokenType::Integer(_), TokenType::Integer(_)) => true,
             (TokenType::Float(_), TokenType::Float(_)) => true,
+            (TokenType::DocComment(_), TokenType::DocComment(_)) => true,
             (a, b) => a == b,
         }
     }
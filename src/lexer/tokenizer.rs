@@ -3,8 +3,8 @@ use anyhow::{Result, bail};
 
 /// Lexer for the Morph programming language
 pub struct Lexer {
-    /// Source code being lexed
-    source: String,
+    /// Source code being lexed, as chars so positions are char offsets
+    source: Vec<char>,
     /// Current position in source
     current: usize,
     /// Start position of current token
@@ -19,7 +19,7 @@ impl Lexer {
     /// Create a new lexer from source code
     pub fn new(source: &str) -> Self {
         Lexer {
-            source: source.to_string(),
+            source: source.chars().collect(),
             current: 0,
             start: 0,
             line: 1,
@@ -70,15 +70,29 @@ impl Lexer {
             ',' => Ok(self.make_token(TokenType::Comma)),
             ';' => Ok(self.make_token(TokenType::Semicolon)),
             '+' => Ok(self.make_token(TokenType::Plus)),
-            '-' => Ok(self.make_token(TokenType::Minus)),
+            '-' => {
+                if self.match_char('>') {
+                    Ok(self.make_token(TokenType::ThinArrow))
+                } else {
+                    Ok(self.make_token(TokenType::Minus))
+                }
+            }
             '*' => Ok(self.make_token(TokenType::Star)),
             '/' => {
                 if self.match_char('/') {
+                    // `///` starts a doc comment, but `////...` is a plain one
+                    let is_doc = self.peek() == '/' && self.peek_next() != '/';
                     // Comment - consume until newline
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
-                    Ok(self.make_token(TokenType::Comment))
+                    if is_doc {
+                        let text = self.text(self.start + 3, self.current);
+                        let text = text.strip_prefix(' ').unwrap_or(&text).trim_end().to_string();
+                        Ok(self.make_token(TokenType::DocComment(text)))
+                    } else {
+                        Ok(self.make_token(TokenType::Comment))
+                    }
                 } else {
                     Ok(self.make_token(TokenType::Slash))
                 }
@@ -124,6 +138,7 @@ impl Lexer {
                     Ok(self.make_token(TokenType::Pipe))
                 }
             }
+            '?' => Ok(self.make_token(TokenType::Question)),
             ':' => {
                 if self.match_char(':') {
                     Ok(self.make_token(TokenType::ColonColon))
@@ -133,7 +148,11 @@ impl Lexer {
             }
             '.' => {
                 if self.match_char('.') {
-                    Ok(self.make_token(TokenType::DotDot))
+                    if self.match_char('.') {
+                        Ok(self.make_token(TokenType::Ellipsis))
+                    } else {
+                        Ok(self.make_token(TokenType::DotDot))
+                    }
                 } else {
                     Ok(self.make_token(TokenType::Dot))
                 }
@@ -145,7 +164,7 @@ impl Lexer {
             }
             '"' => self.string(),
             c if c.is_ascii_digit() => self.number(),
-            c if c.is_ascii_alphabetic() || c == '_' => self.identifier(),
+            c if c.is_alphabetic() || c == '_' => self.identifier(),
             _ => bail!("Unexpected character '{}' at line {}, column {}", c, self.line, self.column),
         }
     }
@@ -153,7 +172,8 @@ impl Lexer {
     /// Parse a string literal
     fn string(&mut self) -> Result<Token> {
         let start_line = self.line;
-        let start_column = self.column;
+        // The first character has already been consumed
+        let start_column = self.column - 1;
 
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
@@ -170,10 +190,10 @@ impl Lexer {
         // Consume closing quote
         self.advance();
 
-        let value = self.source[self.start + 1..self.current - 1].to_string();
+        let value = self.text(self.start + 1, self.current - 1);
         Ok(Token::new(
             TokenType::String(value),
-            self.source[self.start..self.current].to_string(),
+            self.text(self.start, self.current),
             start_line,
             start_column,
         ))
@@ -182,7 +202,8 @@ impl Lexer {
     /// Parse a number (integer or float)
     fn number(&mut self) -> Result<Token> {
         let start_line = self.line;
-        let start_column = self.column;
+        // The first character has already been consumed
+        let start_column = self.column - 1;
 
         while self.peek().is_ascii_digit() {
             self.advance();
@@ -195,39 +216,41 @@ impl Lexer {
                 self.advance();
             }
 
-            let value: f64 = self.source[self.start..self.current].parse()?;
+            let value: f64 = self.text(self.start, self.current).parse()?;
             Ok(Token::new(
                 TokenType::Float(value),
-                self.source[self.start..self.current].to_string(),
+                self.text(self.start, self.current),
                 start_line,
                 start_column,
             ))
         } else {
-            let value: i64 = self.source[self.start..self.current].parse()?;
+            let value: i64 = self.text(self.start, self.current).parse()?;
             Ok(Token::new(
                 TokenType::Integer(value),
-                self.source[self.start..self.current].to_string(),
+                self.text(self.start, self.current),
                 start_line,
                 start_column,
             ))
         }
     }
 
-    /// Parse an identifier or keyword
+    /// Parse an identifier or keyword. Identifiers may use any Unicode
+    /// letters and digits, such as `café` or `λ`.
     fn identifier(&mut self) -> Result<Token> {
         let start_line = self.line;
-        let start_column = self.column;
+        // The first character has already been consumed
+        let start_column = self.column - 1;
 
-        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
 
-        let text = &self.source[self.start..self.current];
-        let token_type = self.keyword_or_identifier(text);
+        let text = self.text(self.start, self.current);
+        let token_type = self.keyword_or_identifier(&text);
 
         Ok(Token::new(
             token_type,
-            text.to_string(),
+            text,
             start_line,
             start_column,
         ))
@@ -242,18 +265,25 @@ impl Lexer {
             "flow" => TokenType::Flow,
             "let" => TokenType::Let,
             "var" => TokenType::Var,
+            "mut" => TokenType::Mut,
             "if" => TokenType::If,
             "else" => TokenType::Else,
             "match" => TokenType::Match,
             "for" => TokenType::For,
             "in" => TokenType::In,
             "return" => TokenType::Return,
+            "break" => TokenType::Break,
+            "continue" => TokenType::Continue,
             "claim" => TokenType::Claim,
             "delegate" => TokenType::Delegate,
             "solve" => TokenType::Solve,
             "ensure" => TokenType::Ensure,
+            "verify" => TokenType::Verify,
+            "minimize" => TokenType::Minimize,
+            "maximize" => TokenType::Maximize,
             "where" => TokenType::Where,
             "import" => TokenType::Import,
+            "as" => TokenType::As,
             "true" => TokenType::Boolean(true),
             "false" => TokenType::Boolean(false),
             _ => TokenType::Identifier(text.to_string()),
@@ -279,20 +309,25 @@ impl Lexer {
 
     /// Get the current character and advance
     fn advance(&mut self) -> char {
-        let c = self.source.chars().nth(self.current).unwrap_or('\0');
+        let c = self.source.get(self.current).copied().unwrap_or('\0');
         self.current += 1;
         self.column += 1;
         c
     }
 
+    /// Source text between two char offsets
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[start..end].iter().collect()
+    }
+
     /// Peek at the current character without advancing
     fn peek(&self) -> char {
-        self.source.chars().nth(self.current).unwrap_or('\0')
+        self.source.get(self.current).copied().unwrap_or('\0')
     }
 
     /// Peek at the next character
     fn peek_next(&self) -> char {
-        self.source.chars().nth(self.current + 1).unwrap_or('\0')
+        self.source.get(self.current + 1).copied().unwrap_or('\0')
     }
 
     /// Match and consume a specific character
@@ -300,7 +335,7 @@ impl Lexer {
         if self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current) != Some(expected) {
+        if self.source.get(self.current) != Some(&expected) {
             return false;
         }
         self.current += 1;
@@ -310,13 +345,9 @@ impl Lexer {
 
     /// Create a token from the current position
     fn make_token(&self, token_type: TokenType) -> Token {
-        let lexeme = self.source[self.start..self.current].to_string();
-        // Calculate start column: current column minus the length of the lexeme
-        let start_column = if self.column >= lexeme.len() {
-            self.column - lexeme.len() + 1
-        } else {
-            1
-        };
+        let lexeme = self.text(self.start, self.current);
+        // Calculate start column: `column` is one past the lexeme's last char
+        let start_column = self.column.saturating_sub(self.current - self.start).max(1);
         Token::new(
             token_type,
             lexeme,
@@ -330,6 +361,16 @@ impl Lexer {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_token_columns() {
+        let source = "let x = \"hi\" + 42";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+
+        let columns: Vec<usize> = tokens.iter().map(|t| t.column).collect();
+        assert_eq!(columns[..6], [1, 5, 7, 9, 14, 16]);
+    }
+
     #[test]
     fn test_keywords() {
         let source = "proto solid let var if else match for in return";
@@ -348,9 +389,21 @@ mod tests {
         assert_eq!(tokens[9].token_type, TokenType::Return);
     }
 
+    #[test]
+    fn test_unicode_identifiers() {
+        let source = "let café = λ2 + naïve_x";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[1].token_type, TokenType::Identifier("café".to_string()));
+        assert_eq!(tokens[3].token_type, TokenType::Identifier("λ2".to_string()));
+        assert_eq!(tokens[5].token_type, TokenType::Identifier("naïve_x".to_string()));
+        assert_eq!(tokens[5].column, 17);
+    }
+
     #[test]
     fn test_operators() {
-        let source = "+ - * / % | |> = == ! != < <= > >= => .. ::";
+        let source = "+ - * / % | |> = == ! != < <= > >= => .. :: ->";
         let mut lexer = Lexer::new(source);
         let tokens = lexer.tokenize().unwrap();
 
@@ -372,6 +425,7 @@ mod tests {
         assert_eq!(tokens[15].token_type, TokenType::Arrow);
         assert_eq!(tokens[16].token_type, TokenType::DotDot);
         assert_eq!(tokens[17].token_type, TokenType::ColonColon);
+        assert_eq!(tokens[18].token_type, TokenType::ThinArrow);
     }
 
     #[test]
@@ -386,9 +440,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_multibyte_string() {
+        let source = r#"let s = "héllo wörld""#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[3].token_type, TokenType::String("héllo wörld".to_string()));
+        assert_eq!(tokens[4].token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "// plain\n/// doc text\n//// banner";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Comment);
+        assert_eq!(tokens[2].token_type, TokenType::DocComment("doc text".to_string()));
+        assert_eq!(tokens[4].token_type, TokenType::Comment);
+    }
+
     #[test]
     fn test_numbers() {
-        let source = "42 3.14";
+        let source = "42 2.5";
         let mut lexer = Lexer::new(source);
         let tokens = lexer.tokenize().unwrap();
 
@@ -398,7 +473,7 @@ mod tests {
         }
 
         match &tokens[1].token_type {
-            TokenType::Float(n) => assert_eq!(*n, 3.14),
+            TokenType::Float(n) => assert_eq!(*n, 2.5),
             _ => panic!("Expected float token"),
         }
     }
@@ -1,6 +1,16 @@
 pub mod ast;
 pub mod cli;
+pub mod codegen;
+pub mod diagnostic;
+pub mod doc;
+pub mod dot;
+pub mod format;
 pub mod interpreter;
 pub mod lexer;
+pub mod lint;
 pub mod parser;
+pub mod purity;
+pub mod stats;
+pub mod symbol;
+pub mod table;
 pub mod types;
\ No newline at end of file
@@ -0,0 +1,315 @@
+//! Lint passes for Morph source
+//!
+//! Currently reports `let`/`var` bindings and parameters that are declared
+//! but never read in their scope. Names starting with `_` are exempt.
+
+use std::fmt;
+use crate::ast::*;
+use crate::ast::visitor::{self, Visitor};
+
+/// The kind of binding a lint warning refers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BindingKind {
+    Variable,
+    Parameter,
+}
+
+/// A lint warning
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub kind: BindingKind,
+    pub name: String,
+    /// Name of the enclosing function or solve block
+    pub scope: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            BindingKind::Variable => "variable",
+            BindingKind::Parameter => "parameter",
+        };
+        write!(f, "unused {} `{}` in `{}`", kind, self.name, self.scope)
+    }
+}
+
+/// Run all lints over a module
+pub fn lint_module(module: &Module) -> Vec<LintWarning> {
+    let mut linter = UnusedBindings::default();
+    visitor::walk_module(&mut linter, module);
+    linter.warnings
+}
+
+/// A name introduced in a scope
+struct Binding {
+    name: String,
+    /// `None` for bindings that are never reported (loop variables, pattern bindings)
+    kind: Option<BindingKind>,
+    used: bool,
+}
+
+/// Tracks declared names per scope and reports the ones never read
+#[derive(Default)]
+struct UnusedBindings {
+    scopes: Vec<Vec<Binding>>,
+    scope_name: String,
+    warnings: Vec<LintWarning>,
+}
+
+impl UnusedBindings {
+    fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        let Some(bindings) = self.scopes.pop() else {
+            return;
+        };
+        for binding in bindings {
+            if let Some(kind) = binding.kind {
+                if !binding.used && !binding.name.starts_with('_') {
+                    self.warnings.push(LintWarning {
+                        kind,
+                        name: binding.name,
+                        scope: self.scope_name.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, kind: Option<BindingKind>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Binding {
+                name: name.to_string(),
+                kind,
+                used: false,
+            });
+        }
+    }
+
+    /// Mark the innermost binding with this name as read
+    fn mark_used(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.iter_mut().rev().find(|b| b.name == name) {
+                binding.used = true;
+                return;
+            }
+        }
+    }
+
+    fn declare_params(&mut self, params: &[Parameter]) {
+        for param in params {
+            self.declare(&param.name, Some(BindingKind::Parameter));
+        }
+    }
+
+    fn declare_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Identifier(name) => self.declare(name, None),
+            Pattern::Range(start, end) => {
+                self.declare_pattern(start);
+                self.declare_pattern(end);
+            }
+            Pattern::Tuple(items) => {
+                for item in items {
+                    self.declare_pattern(item);
+                }
+            }
+            Pattern::Record(fields) => {
+                for (_, pattern) in fields {
+                    self.declare_pattern(pattern);
+                }
+            }
+            Pattern::List(items, rest) => {
+                for item in items {
+                    self.declare_pattern(item);
+                }
+                if let Some(rest) = rest {
+                    self.declare(rest, None);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+}
+
+impl Visitor for UnusedBindings {
+    fn visit_function(&mut self, func: &FunctionDecl) {
+        self.scope_name = func.name.clone();
+        self.push_scope();
+        self.declare_params(&func.params);
+        visitor::walk_function(self, func);
+        self.pop_scope();
+    }
+
+    fn visit_solve_block(&mut self, solve: &SolveBlock) {
+        self.scope_name = solve.name.clone();
+        self.push_scope();
+        self.declare_params(&solve.params);
+        for constraint in &solve.constraints {
+            match constraint {
+                Constraint::Binding { name, expr } | Constraint::Choice { name, domain: expr } => {
+                    self.visit_expression(expr);
+                    self.declare(name, Some(BindingKind::Variable));
+                }
+                Constraint::Ensure(expr) | Constraint::Objective { expr, .. } => {
+                    self.visit_expression(expr);
+                }
+            }
+        }
+        if let Some(ref expr) = solve.return_expr {
+            self.visit_expression(expr);
+        }
+        self.pop_scope();
+    }
+
+    fn visit_verify_block(&mut self, verify: &VerifyBlock) {
+        self.scope_name = verify.name.clone();
+        self.push_scope();
+        for constraint in &verify.constraints {
+            match constraint {
+                Constraint::Binding { name, expr } => {
+                    self.visit_expression(expr);
+                    self.declare(name, Some(BindingKind::Variable));
+                }
+                Constraint::Ensure(expr)
+                | Constraint::Choice { domain: expr, .. }
+                | Constraint::Objective { expr, .. } => self.visit_expression(expr),
+            }
+        }
+        self.pop_scope();
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        match &stmt.kind {
+            StatementKind::VariableDecl { name, initializer, .. } => {
+                self.visit_expression(initializer);
+                self.declare(name, Some(BindingKind::Variable));
+            }
+            StatementKind::For { variable, iterable, guard, body } => {
+                self.visit_expression(iterable);
+                self.push_scope();
+                self.declare(variable, None);
+                if let Some(guard) = guard {
+                    self.visit_expression(guard);
+                }
+                for stmt in body {
+                    self.visit_statement(stmt);
+                }
+                self.pop_scope();
+            }
+            StatementKind::Assignment { target, value } => {
+                // Writing to a variable doesn't count as reading it
+                if !matches!(target, Expression::Identifier(_)) {
+                    self.visit_expression(target);
+                }
+                self.visit_expression(value);
+            }
+            _ => visitor::walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Identifier(name) => self.mark_used(name),
+            Expression::Block(_) => {
+                self.push_scope();
+                visitor::walk_expression(self, expr);
+                self.pop_scope();
+            }
+            Expression::Lambda { params, .. } => {
+                self.push_scope();
+                self.declare_params(params);
+                visitor::walk_expression(self, expr);
+                self.pop_scope();
+            }
+            _ => visitor::walk_expression(self, expr),
+        }
+    }
+
+    fn visit_match_arm(&mut self, arm: &MatchArm) {
+        self.push_scope();
+        self.declare_pattern(&arm.pattern);
+        visitor::walk_match_arm(self, arm);
+        self.pop_scope();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn lint_source(source: &str) -> Vec<LintWarning> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let module = parser.parse().unwrap();
+        lint_module(&module)
+    }
+
+    #[test]
+    fn test_unused_let() {
+        let source = r#"
+            proto main() {
+                let unused = 1
+                let used = 2
+                return used
+            }
+        "#;
+
+        let warnings = lint_source(source);
+        assert_eq!(warnings, vec![LintWarning {
+            kind: BindingKind::Variable,
+            name: "unused".to_string(),
+            scope: "main".to_string(),
+        }]);
+        assert_eq!(warnings[0].to_string(), "unused variable `unused` in `main`");
+    }
+
+    #[test]
+    fn test_underscore_is_exempt() {
+        let source = r#"
+            proto main(_args) {
+                let _ignored = 1
+                return 0
+            }
+        "#;
+
+        assert!(lint_source(source).is_empty());
+    }
+
+    #[test]
+    fn test_unused_parameter() {
+        let source = r#"
+            proto first(a, b) {
+                return a
+            }
+        "#;
+
+        let warnings = lint_source(source);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, BindingKind::Parameter);
+        assert_eq!(warnings[0].name, "b");
+    }
+
+    #[test]
+    fn test_shadowed_binding_in_block() {
+        let source = r#"
+            proto main() {
+                let x = 1
+                let y = {
+                    let x = 2
+                    x
+                }
+                return y
+            }
+        "#;
+
+        let warnings = lint_source(source);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].name, "x");
+    }
+}
@@ -2,9 +2,18 @@ use morph::cli::{Cli, execute};
 use clap::Parser;
 use anyhow::Result;
 
+/// Stack size for the thread running the CLI. The interpreter recurses on
+/// the native stack for every Morph call, so the default 8 MiB runs out
+/// well before the default `--max-depth` in debug builds.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() -> Result<()> {
     env_logger::init();
     
     let cli = Cli::parse();
-    execute(cli)
-}
\ No newline at end of file
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || execute(cli))?
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
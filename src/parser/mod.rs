@@ -1,17 +1,57 @@
 use crate::ast::*;
 use crate::lexer::{Token, TokenType};
+use crate::symbol::SymbolTable;
 use anyhow::{Result, bail};
 
+/// A parse failure, remembering the token the parser had reached
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Index of the failing token in the parser's tokens
+    pub token_index: usize,
+}
+
+impl ParseError {
+    /// The tokens up to `radius` either side of the failing one, one per
+    /// line with their index, and the failing one marked with `>>`
+    pub fn token_window(&self, tokens: &[Token], radius: usize) -> String {
+        let start = self.token_index.saturating_sub(radius);
+        let end = (self.token_index + radius + 1).min(tokens.len());
+        let mut out = String::new();
+        for (i, token) in tokens.iter().enumerate().take(end).skip(start) {
+            let marker = if i == self.token_index { ">>" } else { "  " };
+            // Debug-format the lexeme so newlines stay on one line
+            out.push_str(&format!(
+                "{} {:>5}  {:?} {:?} at {}:{}\n",
+                marker, i, token.token_type, token.lexeme, token.line, token.column
+            ));
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 /// Parser for Morph language
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    /// Number of `for` loops enclosing the current statement
+    loop_depth: usize,
+    /// Record field names seen so far
+    symbols: SymbolTable,
 }
 
 impl Parser {
     /// Create a new parser from tokens
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser { tokens, current: 0, loop_depth: 0, symbols: SymbolTable::new() }
     }
 
     /// Parse the tokens into a Module (AST)
@@ -19,41 +59,75 @@ impl Parser {
         let mut module = Module::new();
 
         while !self.is_at_end() {
-            // Skip newlines between declarations
-            self.skip_newlines();
-            
+            // Skip newlines between declarations, collecting doc comments
+            // for the declaration they document
+            let doc = self.parse_doc_comments();
+
+            // Doc comments at the end of the file document nothing
             if self.is_at_end() {
                 break;
             }
 
-            let decl = self.parse_declaration()?;
+            let decl = self.parse_declaration(doc).map_err(|e| self.locate_error(e))?;
             module.declarations.push(decl);
         }
 
         Ok(module)
     }
 
-    /// Parse a top-level declaration
-    fn parse_declaration(&mut self) -> Result<Declaration> {
-        self.skip_newlines();
+    /// Parse the tokens as a sequence of statements, as typed at a prompt
+    pub fn parse_statements(&mut self) -> Result<Vec<Statement>> {
+        let mut statements = Vec::new();
+        self.skip_statement_separators();
+        while !self.is_at_end() {
+            statements.push(self.parse_statement().map_err(|e| self.locate_error(e))?);
+            self.skip_statement_separators();
+        }
+        Ok(statements)
+    }
+
+    /// The tokens being parsed
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
 
+    /// Turn an error into a `ParseError` at the current token
+    fn locate_error(&self, error: anyhow::Error) -> anyhow::Error {
+        ParseError { message: error.to_string(), token_index: self.current }.into()
+    }
+
+    /// Parse a top-level declaration, documented by `doc`
+    fn parse_declaration(&mut self, doc: Option<String>) -> Result<Declaration> {
         match self.peek().token_type {
             TokenType::Proto | TokenType::Solid => {
-                let func = self.parse_function()?;
+                let mut func = self.parse_function()?;
+                func.doc = doc;
                 Ok(Declaration::Function(func))
             }
             TokenType::Type => {
-                let type_decl = self.parse_type_declaration()?;
+                let mut type_decl = self.parse_type_declaration()?;
+                type_decl.doc = doc;
                 Ok(Declaration::Type(type_decl))
             }
             TokenType::Solve => {
-                let solve = self.parse_solve_block()?;
+                let mut solve = self.parse_solve_block()?;
+                solve.doc = doc;
                 Ok(Declaration::Solve(solve))
             }
+            TokenType::Verify => {
+                let mut verify = self.parse_verify_block()?;
+                verify.doc = doc;
+                Ok(Declaration::Verify(verify))
+            }
             TokenType::Import => {
                 let import = self.parse_import()?;
                 Ok(Declaration::Import(import))
             }
+            TokenType::Let | TokenType::Var => {
+                let span = Span::new(self.peek().line, self.peek().column);
+                let kind = self.parse_variable_decl()?;
+                Ok(Declaration::Global(Statement::new(kind, span)))
+            }
             _ => bail!(
                 "Unexpected token '{}' at line {}, column {}. Expected declaration.",
                 self.peek().lexeme,
@@ -63,6 +137,24 @@ impl Parser {
         }
     }
 
+    /// Collect the `///` comments before a declaration, one line each
+    fn parse_doc_comments(&mut self) -> Option<String> {
+        let mut lines = Vec::new();
+        loop {
+            if let TokenType::DocComment(ref text) = self.peek().token_type {
+                lines.push(text.clone());
+                self.advance();
+            } else if !(self.match_token(TokenType::Newline) || self.match_token(TokenType::Comment)) {
+                break;
+            }
+        }
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
     /// Parse a function declaration
     fn parse_function(&mut self) -> Result<FunctionDecl> {
         // Parse mode (proto or solid)
@@ -77,13 +169,25 @@ impl Parser {
         // Parse function name
         let name = self.consume_identifier("function name")?;
 
+        // Parse generic type parameters (optional)
+        let mut type_params = Vec::new();
+        if self.match_token(TokenType::Less) {
+            loop {
+                type_params.push(self.consume_identifier("type parameter name")?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::Greater, "'>' after type parameters")?;
+        }
+
         // Parse parameters
         self.consume(TokenType::LeftParen, "'(' after function name")?;
         let params = self.parse_parameters()?;
         self.consume(TokenType::RightParen, "')' after parameters")?;
 
-        // Parse return type (optional)
-        let return_type = if self.match_token(TokenType::Arrow) {
+        // Parse return type (optional), written `=> T` or `-> T`
+        let return_type = if self.match_token(TokenType::Arrow) || self.match_token(TokenType::ThinArrow) {
             Some(self.parse_type_annotation()?)
         } else {
             None
@@ -96,9 +200,11 @@ impl Parser {
         Ok(FunctionDecl {
             mode,
             name,
+            type_params,
             params,
             return_type,
             body,
+            doc: None,
         })
     }
 
@@ -157,7 +263,7 @@ impl Parser {
                         self.advance(); // consume Ghost
                         self.consume(TokenType::Colon, "':' after Ghost")?;
                         
-                        let mut attributes = Vec::new();
+                        let attributes = Vec::new();
                         // Parse Ghost attributes (simplified)
                         while !self.check(TokenType::Greater) && !self.is_at_end() {
                             self.advance();
@@ -186,7 +292,7 @@ impl Parser {
 
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
             statements.push(self.parse_statement()?);
-            self.skip_newlines();
+            self.skip_statement_separators();
         }
 
         self.consume(TokenType::RightBrace, "'}' after block")?;
@@ -196,25 +302,60 @@ impl Parser {
     /// Parse a statement
     fn parse_statement(&mut self) -> Result<Statement> {
         self.skip_newlines();
-
-        match self.peek().token_type {
-            TokenType::Let | TokenType::Var => self.parse_variable_decl(),
-            TokenType::Return => self.parse_return(),
-            TokenType::For => self.parse_for_loop(),
+        let span = Span::new(self.peek().line, self.peek().column);
+
+        let kind = match self.peek().token_type {
+            TokenType::Let | TokenType::Var => self.parse_variable_decl()?,
+            TokenType::Return => self.parse_return()?,
+            TokenType::For => self.parse_for_loop()?,
+            TokenType::Break | TokenType::Continue => {
+                let keyword = self.advance().lexeme.clone();
+                if self.loop_depth == 0 {
+                    bail!("'{}' outside of a loop at line {}, column {}", keyword, span.line, span.column);
+                }
+                if keyword == "break" {
+                    StatementKind::Break
+                } else {
+                    StatementKind::Continue
+                }
+            }
             _ => {
-                // Try to parse as expression statement
+                // Try to parse as expression statement. This includes bare
+                // `{ ... }` blocks, which scope their bindings to the block
                 let expr = self.parse_expression()?;
-                Ok(Statement::Expression(expr))
+                if self.match_token(TokenType::Equal) {
+                    self.parse_assignment(expr, span)?
+                } else {
+                    StatementKind::Expression(expr)
+                }
             }
+        };
+        Ok(Statement::new(kind, span))
+    }
+
+    /// Parse the value of an assignment to `target`, whose `=` has been
+    /// consumed. Only variables and elements of lists held in variables
+    /// can be assigned to
+    fn parse_assignment(&mut self, target: Expression, span: Span) -> Result<StatementKind> {
+        let assignable = match &target {
+            Expression::Identifier(_) => true,
+            Expression::IndexAccess { object, .. } => matches!(object.as_ref(), Expression::Identifier(_)),
+            _ => false,
+        };
+        if !assignable {
+            bail!("Invalid assignment target at line {}, column {}", span.line, span.column);
         }
+        let value = self.parse_expression()?;
+        Ok(StatementKind::Assignment { target, value })
     }
 
     /// Parse variable declaration (let or var)
-    fn parse_variable_decl(&mut self) -> Result<Statement> {
-        let mutable = self.match_token(TokenType::Var);
-        if !mutable {
+    fn parse_variable_decl(&mut self) -> Result<StatementKind> {
+        // `let mut` is accepted as a spelling of `var`
+        let mutable = self.match_token(TokenType::Var) || {
             self.consume(TokenType::Let, "'let' or 'var'")?;
-        }
+            self.match_token(TokenType::Mut)
+        };
 
         let name = self.consume_identifier("variable name")?;
 
@@ -227,7 +368,7 @@ impl Parser {
         self.consume(TokenType::Equal, "'=' after variable name")?;
         let initializer = self.parse_expression()?;
 
-        Ok(Statement::VariableDecl {
+        Ok(StatementKind::VariableDecl {
             name,
             type_annotation,
             initializer,
@@ -236,7 +377,7 @@ impl Parser {
     }
 
     /// Parse return statement
-    fn parse_return(&mut self) -> Result<Statement> {
+    fn parse_return(&mut self) -> Result<StatementKind> {
         self.consume(TokenType::Return, "'return'")?;
 
         let value = if self.check(TokenType::Newline) 
@@ -247,11 +388,11 @@ impl Parser {
             Some(self.parse_expression()?)
         };
 
-        Ok(Statement::Return(value))
+        Ok(StatementKind::Return(value))
     }
 
     /// Parse for loop
-    fn parse_for_loop(&mut self) -> Result<Statement> {
+    fn parse_for_loop(&mut self) -> Result<StatementKind> {
         self.consume(TokenType::For, "'for'")?;
         let variable = self.consume_identifier("loop variable")?;
         self.consume(TokenType::In, "'in' after loop variable")?;
@@ -265,9 +406,12 @@ impl Parser {
         };
 
         self.consume(TokenType::LeftBrace, "'{' before loop body")?;
-        let body = self.parse_block()?;
+        self.loop_depth += 1;
+        let body = self.parse_block();
+        self.loop_depth -= 1;
+        let body = body?;
 
-        Ok(Statement::For {
+        Ok(StatementKind::For {
             variable,
             iterable,
             guard,
@@ -308,7 +452,7 @@ impl Parser {
             TypeDefinition::Alias(self.parse_type_annotation()?)
         };
 
-        Ok(TypeDecl { name, definition })
+        Ok(TypeDecl { name, definition, doc: None })
     }
 
     /// Parse solve block
@@ -328,14 +472,33 @@ impl Parser {
         self.skip_newlines();
         
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            // The result is worked out from the finished search, so nothing
+            // after it could have an effect
+            if return_expr.is_some() {
+                bail!(
+                    "'return' must be the last line of solve block {}, but more follows at line {}",
+                    name, self.peek().line
+                );
+            }
             if self.match_token(TokenType::Let) {
                 let name = self.consume_identifier("variable name")?;
-                self.consume(TokenType::Equal, "'=' after variable name")?;
-                let expr = self.parse_expression()?;
-                constraints.push(Constraint::Binding { name, expr });
+                if self.match_token(TokenType::In) {
+                    let domain = self.parse_expression()?;
+                    constraints.push(Constraint::Choice { name, domain });
+                } else {
+                    self.consume(TokenType::Equal, "'=' or 'in' after variable name")?;
+                    let expr = self.parse_expression()?;
+                    constraints.push(Constraint::Binding { name, expr });
+                }
             } else if self.match_token(TokenType::Ensure) {
                 let expr = self.parse_expression()?;
                 constraints.push(Constraint::Ensure(expr));
+            } else if self.match_token(TokenType::Minimize) {
+                let expr = self.parse_expression()?;
+                constraints.push(Constraint::Objective { kind: ObjectiveKind::Min, expr });
+            } else if self.match_token(TokenType::Maximize) {
+                let expr = self.parse_expression()?;
+                constraints.push(Constraint::Objective { kind: ObjectiveKind::Max, expr });
             } else if self.match_token(TokenType::Return) {
                 return_expr = Some(self.parse_expression()?);
             } else {
@@ -352,6 +515,44 @@ impl Parser {
             params,
             constraints,
             return_expr,
+            doc: None,
+        })
+    }
+
+    /// Parse a verify block: `let` bindings and `ensure` assertions
+    fn parse_verify_block(&mut self) -> Result<VerifyBlock> {
+        self.consume(TokenType::Verify, "'verify'")?;
+        let name = self.consume_identifier("verify block name")?;
+        
+        self.consume(TokenType::LeftParen, "'(' after verify name")?;
+        self.consume(TokenType::RightParen, "')' after verify name")?;
+        self.consume(TokenType::LeftBrace, "'{' before verify body")?;
+        
+        let mut constraints = Vec::new();
+        self.skip_newlines();
+        
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_token(TokenType::Let) {
+                let name = self.consume_identifier("variable name")?;
+                self.consume(TokenType::Equal, "'=' after variable name")?;
+                let expr = self.parse_expression()?;
+                constraints.push(Constraint::Binding { name, expr });
+            } else if self.match_token(TokenType::Ensure) {
+                let expr = self.parse_expression()?;
+                constraints.push(Constraint::Ensure(expr));
+            } else {
+                bail!("Unexpected token in verify block at line {}", self.peek().line);
+            }
+            
+            self.skip_newlines();
+        }
+        
+        self.consume(TokenType::RightBrace, "'}' after verify block")?;
+        
+        Ok(VerifyBlock {
+            name,
+            constraints,
+            doc: None,
         })
     }
 
@@ -362,28 +563,38 @@ impl Parser {
         
         // TODO: Handle selective imports
         let items = None;
+
+        let alias = if self.match_token(TokenType::As) {
+            Some(self.consume_identifier("alias after 'as'")?)
+        } else {
+            None
+        };
         
-        Ok(Import { module, items })
+        Ok(Import { module, items, alias })
     }
 
-    /// Parse expression (handles pipe operator)
+    /// Parse expression
     fn parse_expression(&mut self) -> Result<Expression> {
-        self.parse_pipe()
+        self.parse_ternary()
     }
 
-    /// Parse pipe expressions (lowest precedence)
-    fn parse_pipe(&mut self) -> Result<Expression> {
-        let mut expr = self.parse_or()?;
+    /// Parse `cond ? a : b` (lowest precedence, right associative),
+    /// desugared to an `if` expression
+    fn parse_ternary(&mut self) -> Result<Expression> {
+        let condition = self.parse_or()?;
 
-        while self.match_token(TokenType::PipeGreater) {
-            let right = self.parse_or()?;
-            expr = Expression::Pipe {
-                left: Box::new(expr),
-                right: Box::new(right),
-            };
+        if !self.match_token(TokenType::Question) {
+            return Ok(condition);
         }
+        let then_branch = self.parse_ternary()?;
+        self.consume(TokenType::Colon, "':' in conditional expression")?;
+        let else_branch = self.parse_ternary()?;
 
-        Ok(expr)
+        Ok(Expression::If {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Some(Box::new(else_branch)),
+        })
     }
 
     /// Parse logical OR (not in Morph spec but for completeness)
@@ -426,12 +637,14 @@ impl Parser {
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+            TokenType::In,
         ]) {
             let op = match self.previous().token_type {
                 TokenType::Greater => BinaryOp::Greater,
                 TokenType::GreaterEqual => BinaryOp::GreaterEq,
                 TokenType::Less => BinaryOp::Less,
                 TokenType::LessEqual => BinaryOp::LessEq,
+                TokenType::In => BinaryOp::In,
                 _ => unreachable!(),
             };
             let right = self.parse_term()?;
@@ -468,7 +681,7 @@ impl Parser {
 
     /// Parse multiplication, division, modulo
     fn parse_factor(&mut self) -> Result<Expression> {
-        let mut expr = self.parse_unary()?;
+        let mut expr = self.parse_cast()?;
 
         while self.match_tokens(&[TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let op = match self.previous().token_type {
@@ -477,7 +690,7 @@ impl Parser {
                 TokenType::Percent => BinaryOp::Modulo,
                 _ => unreachable!(),
             };
-            let right = self.parse_unary()?;
+            let right = self.parse_cast()?;
             expr = Expression::Binary {
                 left: Box::new(expr),
                 op,
@@ -488,6 +701,44 @@ impl Parser {
         Ok(expr)
     }
 
+    /// Parse casts: `x as Float`.
+    ///
+    /// `as` binds tighter than arithmetic but looser than `|>` and unary
+    /// operators, so `a * b as Float` is `a * (b as Float)` and
+    /// `-x as Float` is `(-x) as Float`.
+    fn parse_cast(&mut self) -> Result<Expression> {
+        let mut expr = self.parse_pipe()?;
+
+        while self.match_token(TokenType::As) {
+            let target = self.parse_type_annotation()?;
+            expr = Expression::Cast {
+                expr: Box::new(expr),
+                target,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// Parse pipe expressions.
+    ///
+    /// `|>` binds tighter than arithmetic and its right side is a single
+    /// call or primary, so `a |> f + 1` is `(a |> f) + 1` and
+    /// `a |> f |> g` is `(a |> f) |> g`.
+    fn parse_pipe(&mut self) -> Result<Expression> {
+        let mut expr = self.parse_unary()?;
+
+        while self.match_token(TokenType::PipeGreater) {
+            let right = self.parse_call()?;
+            expr = Expression::Pipe {
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
     /// Parse unary operators
     fn parse_unary(&mut self) -> Result<Expression> {
         if self.match_tokens(&[TokenType::Bang, TokenType::Minus]) {
@@ -517,14 +768,40 @@ impl Parser {
                 let field = self.consume_identifier("field name")?;
                 expr = Expression::FieldAccess {
                     object: Box::new(expr),
-                    field,
+                    field: self.symbols.intern(&field),
                 };
-            } else if self.match_token(TokenType::LeftBracket) {
-                let index = self.parse_expression()?;
-                self.consume(TokenType::RightBracket, "']' after index")?;
-                expr = Expression::IndexAccess {
+            } else if self.check(TokenType::ColonColon) {
+                // Module paths: `math::sqrt` is the `sqrt` member of the
+                // imported `math` module value
+                if !is_path(&expr) {
+                    bail!("Expected a module name before '::' at line {}", self.peek().line);
+                }
+                self.advance();
+                let member = self.consume_identifier("module member name")?;
+                expr = Expression::FieldAccess {
                     object: Box::new(expr),
-                    index: Box::new(index),
+                    field: self.symbols.intern(&member),
+                };
+            } else if self.match_token(TokenType::LeftBracket) {
+                let start = if self.check(TokenType::Colon) {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expression()?))
+                };
+                expr = match (start, self.match_token(TokenType::Colon)) {
+                    (Some(index), false) => {
+                        self.consume(TokenType::RightBracket, "']' after index")?;
+                        Expression::IndexAccess { object: Box::new(expr), index }
+                    }
+                    (start, _) => {
+                        let end = if self.check(TokenType::RightBracket) {
+                            None
+                        } else {
+                            Some(Box::new(self.parse_expression()?))
+                        };
+                        self.consume(TokenType::RightBracket, "']' after slice")?;
+                        Expression::SliceAccess { object: Box::new(expr), start, end }
+                    }
                 };
             } else {
                 break;
@@ -540,7 +817,22 @@ impl Parser {
 
         if !self.check(TokenType::RightParen) {
             loop {
-                args.push(self.parse_expression()?);
+                let line = self.peek().line;
+                if self.is_label() {
+                    let name = self.consume_identifier("argument name")?;
+                    self.consume(TokenType::Colon, "':' after argument name")?;
+                    let value = Box::new(self.parse_expression()?);
+                    if args.iter().any(|arg| matches!(arg, Expression::NamedArg { name: other, .. } if *other == name)) {
+                        bail!("Argument {} is given twice at line {}", name, line);
+                    }
+                    args.push(Expression::NamedArg { name, value });
+                } else if matches!(args.last(), Some(Expression::NamedArg { .. })) {
+                    bail!("Positional argument after named arguments at line {}", line);
+                } else if self.match_token(TokenType::Ellipsis) {
+                    args.push(Expression::Spread(Box::new(self.parse_expression()?)));
+                } else {
+                    args.push(self.parse_expression()?);
+                }
                 if !self.match_token(TokenType::Comma) {
                     break;
                 }
@@ -549,12 +841,29 @@ impl Parser {
 
         self.consume(TokenType::RightParen, "')' after arguments")?;
 
+        // `_` arguments are placeholders: `add(_, 5)` is a function of one
+        // argument, with `add` and `5` evaluated when it's created
+        if args.iter().any(Expression::is_placeholder) {
+            return Ok(Expression::Partial {
+                callee: Box::new(callee),
+                args,
+            });
+        }
         Ok(Expression::Call {
             callee: Box::new(callee),
             args,
         })
     }
 
+    /// Parse a lambda body. A lambda is a function boundary, so loops
+    /// around it can't be left with `break`/`continue` from inside it
+    fn parse_lambda_body(&mut self) -> Result<Expression> {
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.parse_expression();
+        self.loop_depth = loop_depth;
+        body
+    }
+
     /// Parse primary expressions
     fn parse_primary(&mut self) -> Result<Expression> {
         match self.peek().token_type {
@@ -578,8 +887,30 @@ impl Parser {
             TokenType::Identifier(ref name) => {
                 let name = name.clone();
                 self.advance();
+                
+                // Single-parameter lambda: x => expr
+                if self.match_token(TokenType::Arrow) {
+                    let body = self.parse_lambda_body()?;
+                    return Ok(Expression::Lambda {
+                        params: vec![Parameter { name, type_annotation: None }],
+                        body: Box::new(body),
+                    });
+                }
+                
                 Ok(Expression::Identifier(name))
             }
+            TokenType::LeftParen if self.is_lambda_params() => {
+                // Parenthesized lambda: (a, b) => expr
+                self.advance();
+                let params = self.parse_parameters()?;
+                self.consume(TokenType::RightParen, "')' after lambda parameters")?;
+                self.consume(TokenType::Arrow, "'=>' after lambda parameters")?;
+                let body = self.parse_lambda_body()?;
+                Ok(Expression::Lambda {
+                    params,
+                    body: Box::new(body),
+                })
+            }
             TokenType::LeftParen => {
                 self.advance();
                 let expr = self.parse_expression()?;
@@ -701,45 +1032,169 @@ impl Parser {
             }
             TokenType::Integer(n) => {
                 self.advance();
-                
-                // Check for range pattern (e.g., 90..100)
-                if self.match_token(TokenType::DotDot) {
-                    let end = self.parse_pattern()?;
-                    return Ok(Pattern::Range(
-                        Box::new(Pattern::Literal(Literal::Integer(n))),
-                        Box::new(end),
-                    ));
-                }
-                
-                Ok(Pattern::Literal(Literal::Integer(n)))
+                self.parse_integer_pattern(n)
             }
             TokenType::Float(n) => {
                 self.advance();
-                Ok(Pattern::Literal(Literal::Float(n)))
+                self.parse_literal_pattern(Literal::Float(n))
+            }
+            TokenType::Minus => {
+                self.advance();
+                
+                // Negative numeric literal (e.g., -1 or -5..-1)
+                match self.peek().token_type {
+                    TokenType::Integer(n) => {
+                        self.advance();
+                        self.parse_integer_pattern(-n)
+                    }
+                    TokenType::Float(n) => {
+                        self.advance();
+                        self.parse_literal_pattern(Literal::Float(-n))
+                    }
+                    _ => bail!(
+                        "Expected number after '-' in pattern at line {}",
+                        self.peek().line
+                    ),
+                }
             }
             TokenType::String(ref s) => {
                 let s = s.clone();
                 self.advance();
-                Ok(Pattern::Literal(Literal::String(s)))
+                self.parse_literal_pattern(Literal::String(s))
             }
             TokenType::Boolean(b) => {
                 self.advance();
-                Ok(Pattern::Literal(Literal::Boolean(b)))
+                self.parse_literal_pattern(Literal::Boolean(b))
             }
             TokenType::Identifier(ref name) => {
                 let name = name.clone();
                 self.advance();
                 Ok(Pattern::Identifier(name))
             }
+            TokenType::LeftBrace => {
+                self.advance();
+                self.parse_record_pattern()
+            }
+            TokenType::LeftBracket => {
+                self.advance();
+                self.parse_list_pattern()
+            }
             _ => bail!("Unexpected token in pattern at line {}", self.peek().line),
         }
     }
 
+    /// Parse the elements of a list pattern after '[': `[a, b, ..rest]`.
+    /// The optional `..rest` must come last and binds the remaining elements.
+    fn parse_list_pattern(&mut self) -> Result<Pattern> {
+        let mut elements = Vec::new();
+        let mut rest = None;
+        
+        while !self.check(TokenType::RightBracket) {
+            if self.match_token(TokenType::DotDot) {
+                rest = Some(self.consume_identifier("name after '..' in list pattern")?);
+                break;
+            }
+            elements.push(self.parse_pattern()?);
+            
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        
+        self.consume(TokenType::RightBracket, "']' after list pattern")?;
+        Ok(Pattern::List(elements, rest))
+    }
+
+    /// Parse the fields of a record pattern after '{': `{ x: 0, y }`.
+    /// A field without a sub-pattern binds the field to a variable of the same name.
+    fn parse_record_pattern(&mut self) -> Result<Pattern> {
+        let mut fields = Vec::new();
+        
+        while !self.check(TokenType::RightBrace) {
+            let field_name = self.consume_identifier("field name in record pattern")?;
+            let pattern = if self.match_token(TokenType::Colon) {
+                self.parse_pattern()?
+            } else {
+                Pattern::Identifier(field_name.clone())
+            };
+            fields.push((self.symbols.intern(&field_name), pattern));
+            
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        
+        self.consume(TokenType::RightBrace, "'}' after record pattern")?;
+        Ok(Pattern::Record(fields))
+    }
+
+    /// Finish a literal pattern other than an integer, which can't start a range
+    fn parse_literal_pattern(&mut self, literal: Literal) -> Result<Pattern> {
+        if self.check(TokenType::DotDot) {
+            bail!(
+                "Range patterns need integer bounds, got '{}' at line {}",
+                self.previous().lexeme,
+                self.peek().line
+            );
+        }
+        Ok(Pattern::Literal(literal))
+    }
+
+    /// Finish an integer pattern, which may start a range (e.g., 90..100).
+    /// Ranges include both bounds, so the end may not be below the start.
+    fn parse_integer_pattern(&mut self, n: i64) -> Result<Pattern> {
+        if self.match_token(TokenType::DotDot) {
+            let line = self.peek().line;
+            let end = match self.parse_pattern()? {
+                Pattern::Literal(Literal::Integer(end)) => end,
+                _ => bail!("Range pattern must end with an integer literal at line {}", line),
+            };
+            if end < n {
+                bail!("Range pattern {}..{} is empty: its end is below its start at line {}", n, end, line);
+            }
+            return Ok(Pattern::Range(
+                Box::new(Pattern::Literal(Literal::Integer(n))),
+                Box::new(Pattern::Literal(Literal::Integer(end))),
+            ));
+        }
+        
+        Ok(Pattern::Literal(Literal::Integer(n)))
+    }
+
+    /// Check if the current '(' opens a lambda parameter list,
+    /// i.e. its matching ')' is followed by '=>'
+    fn is_lambda_params(&self) -> bool {
+        let mut depth = 0;
+        for (idx, token) in self.tokens.iter().enumerate().skip(self.current) {
+            match token.token_type {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return matches!(
+                            self.tokens.get(idx + 1).map(|t| &t.token_type),
+                            Some(TokenType::Arrow)
+                        );
+                    }
+                }
+                TokenType::Newline | TokenType::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
     /// Check if the current position looks like a record literal
     /// A record literal starts with { identifier: ... }
     fn is_record_literal(&self) -> bool {
         // We need to look ahead: if we see { identifier : ... } it's a record
         // If we see { identifier (not :) it's a block
+        self.is_label()
+    }
+
+    /// Check if the current position is `identifier :`, as starts a record
+    /// field or a named argument
+    fn is_label(&self) -> bool {
         let mut idx = self.current;
         
         // Check if we're at an identifier
@@ -767,7 +1222,7 @@ impl Parser {
             // Parse the field value expression
             let value = self.parse_expression()?;
             
-            fields.push((field_name, value));
+            fields.push((self.symbols.intern(&field_name), value));
             
             // Check for comma or end of record
             if !self.match_token(TokenType::Comma) {
@@ -787,7 +1242,21 @@ impl Parser {
     // Helper methods
 
     fn skip_newlines(&mut self) {
-        while self.match_token(TokenType::Newline) || self.match_token(TokenType::Comment) {
+        while self.match_token(TokenType::Newline)
+            || self.match_token(TokenType::Comment)
+            || self.match_token(TokenType::DocComment(String::new()))
+        {
+            // Skip
+        }
+    }
+
+    /// Skip newlines and `;`, either of which may end a statement in a block
+    fn skip_statement_separators(&mut self) {
+        while self.match_token(TokenType::Newline)
+            || self.match_token(TokenType::Comment)
+            || self.match_token(TokenType::DocComment(String::new()))
+            || self.match_token(TokenType::Semicolon)
+        {
             // Skip
         }
     }
@@ -870,10 +1339,21 @@ impl Parser {
     }
 }
 
+/// Whether `expr` is a bare name or a `::` path of names, the only things
+/// that can be qualified with `::`
+fn is_path(expr: &Expression) -> bool {
+    match expr {
+        Expression::Identifier(_) => true,
+        Expression::FieldAccess { object, .. } => is_path(object),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::lexer::Lexer;
+    use crate::symbol::Symbol;
 
     fn parse_source(source: &str) -> Result<Module> {
         let mut lexer = Lexer::new(source);
@@ -882,6 +1362,18 @@ mod tests {
         parser.parse()
     }
 
+    /// Parse `expr` as the only statement of a function body
+    fn parse_expr(expr: &str) -> Expression {
+        let module = parse_source(&format!("proto test() {{ {} }}", expr)).unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Expression(expr) = &func.body[0].kind else {
+            panic!("Expected expression statement");
+        };
+        expr.clone()
+    }
+
     #[test]
     fn test_simple_function() {
         let source = r#"
@@ -915,6 +1407,87 @@ mod tests {
         assert_eq!(module.declarations.len(), 1);
     }
 
+    #[test]
+    fn test_pipe_precedence() {
+        let ident = |name: &str| Box::new(Expression::Identifier(name.to_string()));
+        let pipe = |left, right| Expression::Pipe { left, right };
+
+        // The right side of |> is a single call, not an additive expression
+        assert_eq!(parse_expr("a |> f + 1"), Expression::Binary {
+            left: Box::new(pipe(ident("a"), ident("f"))),
+            op: BinaryOp::Add,
+            right: Box::new(Expression::Literal(Literal::Integer(1))),
+        });
+
+        // Pipes chain left to right
+        assert_eq!(
+            parse_expr("a |> f |> g"),
+            pipe(Box::new(pipe(ident("a"), ident("f"))), ident("g")),
+        );
+
+        // ...and bind tighter than multiplication on either side
+        assert_eq!(parse_expr("2 * a |> f"), Expression::Binary {
+            left: Box::new(Expression::Literal(Literal::Integer(2))),
+            op: BinaryOp::Multiply,
+            right: Box::new(pipe(ident("a"), ident("f"))),
+        });
+
+        // A call on the right still takes its own arguments
+        assert_eq!(parse_expr("a |> f(b)"), pipe(ident("a"), Box::new(Expression::Call {
+            callee: ident("f"),
+            args: vec![Expression::Identifier("b".to_string())],
+        })));
+    }
+
+    #[test]
+    fn test_ternary() {
+        let int = |n| Box::new(Expression::Literal(Literal::Integer(n)));
+        let ident = |name: &str| Box::new(Expression::Identifier(name.to_string()));
+
+        // Binds looser than comparison and nests to the right
+        assert_eq!(parse_expr("a > 0 ? 1 : b ? 2 : 3"), Expression::If {
+            condition: Box::new(Expression::Binary { left: ident("a"), op: BinaryOp::Greater, right: int(0) }),
+            then_branch: int(1),
+            else_branch: Some(Box::new(Expression::If {
+                condition: ident("b"),
+                then_branch: int(2),
+                else_branch: Some(int(3)),
+            })),
+        });
+
+        assert!(parse_source("proto test() { a ? 1 }").is_err());
+    }
+
+    #[test]
+    fn test_in_operator() {
+        let module = parse_source("proto test() { x + 1 in xs == true }").unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Expression(expr) = &func.body[0].kind else {
+            panic!("Expected expression statement");
+        };
+        let ident = |name: &str| Box::new(Expression::Identifier(name.to_string()));
+
+        // `in` binds like `<`: looser than `+`, tighter than `==`
+        assert_eq!(*expr, Expression::Binary {
+            left: Box::new(Expression::Binary {
+                left: Box::new(Expression::Binary {
+                    left: ident("x"),
+                    op: BinaryOp::Add,
+                    right: Box::new(Expression::Literal(Literal::Integer(1))),
+                }),
+                op: BinaryOp::In,
+                right: ident("xs"),
+            }),
+            op: BinaryOp::Equal,
+            right: Box::new(Expression::Literal(Literal::Boolean(true))),
+        });
+
+        // `for ... in` is unaffected
+        assert!(parse_source("proto test() { for x in xs where x in ys { x } }").is_ok());
+    }
+
     #[test]
     fn test_match_expression() {
         let source = r#"
@@ -931,6 +1504,438 @@ mod tests {
         assert_eq!(module.declarations.len(), 1);
     }
 
+    #[test]
+    fn test_generic_function() {
+        let source = r#"
+            proto id<T>(x: T) -> T {
+                return x
+            }
+
+            proto swap<A, B>(a: A, b: B) -> List<B> {
+                return [b]
+            }
+
+            proto plain(x) {
+                return x
+            }
+        "#;
+
+        let module = parse_source(source).unwrap();
+        let type_params: Vec<Vec<String>> = module.declarations.iter()
+            .map(|decl| match decl {
+                Declaration::Function(func) => func.type_params.clone(),
+                _ => panic!("Expected function declaration"),
+            })
+            .collect();
+        assert_eq!(type_params, vec![
+            vec!["T".to_string()],
+            vec!["A".to_string(), "B".to_string()],
+            vec![],
+        ]);
+
+        let Declaration::Function(id) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        assert_eq!(id.params[0].type_annotation, Some(TypeAnnotation::Named("T".to_string())));
+        assert_eq!(id.return_type, Some(TypeAnnotation::Named("T".to_string())));
+
+        assert!(parse_source("proto bad<>(x) { return x }").is_err());
+        assert!(parse_source("proto bad<T(x) { return x }").is_err());
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let source = r#"
+            /// Squares a number.
+            ///   Indentation after the first space is kept.
+            proto square(x) {
+                /// Doc comments inside a body are ignored
+                return x * x
+            }
+
+            // Not documentation
+            proto plain() {
+                return 0
+            }
+
+            /// A point in the plane
+            type Point = { x: Int, y: Int }
+        "#;
+
+        let module = parse_source(source).unwrap();
+        let Declaration::Function(square) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        assert_eq!(
+            square.doc.as_deref(),
+            Some("Squares a number.\n  Indentation after the first space is kept.")
+        );
+        assert_eq!(square.body.len(), 1);
+
+        let Declaration::Function(plain) = &module.declarations[1] else {
+            panic!("Expected function declaration");
+        };
+        assert_eq!(plain.doc, None);
+
+        let Declaration::Type(point) = &module.declarations[2] else {
+            panic!("Expected type declaration");
+        };
+        assert_eq!(point.doc.as_deref(), Some("A point in the plane"));
+
+        // Doc comments with no declaration after them are dropped
+        let module = parse_source("proto f() {\n    return 0\n}\n\n/// Trailing\n///").unwrap();
+        assert_eq!(module.declarations.len(), 1);
+        assert!(parse_source("/// Nothing to document\n").unwrap().declarations.is_empty());
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let source = r#"
+            proto main() {
+                for x in [1, 2] {
+                    continue
+                }
+                break
+            }
+        "#;
+
+        let err = parse_source(source).unwrap_err();
+        assert_eq!(err.to_string(), "'break' outside of a loop at line 6, column 17");
+
+        // A lambda body is a new function, even inside a loop
+        let source = r#"
+            proto main() {
+                for x in [1, 2] {
+                    let f = y => { break }
+                }
+            }
+        "#;
+        assert!(parse_source(source).is_err());
+    }
+
+    #[test]
+    fn test_import_alias() {
+        let source = r#"
+            import math as m
+            import math
+        "#;
+        
+        let module = parse_source(source).unwrap();
+        assert_eq!(module.declarations.len(), 2);
+        
+        match &module.declarations[0] {
+            Declaration::Import(import) => {
+                assert_eq!(import.module, "math");
+                assert_eq!(import.alias.as_deref(), Some("m"));
+                assert_eq!(import.binding_name(), "m");
+            }
+            _ => panic!("Expected import declaration"),
+        }
+        
+        match &module.declarations[1] {
+            Declaration::Import(import) => {
+                assert_eq!(import.alias, None);
+                assert_eq!(import.binding_name(), "math");
+            }
+            _ => panic!("Expected import declaration"),
+        }
+    }
+
+    #[test]
+    fn test_module_paths() {
+        let source = r#"
+            import math
+            proto main() {
+                return math::sqrt(16.0)
+            }
+        "#;
+
+        let module = parse_source(source).unwrap();
+        let Declaration::Function(func) = &module.declarations[1] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Return(Some(Expression::Call { callee, args })) = &func.body[0].kind else {
+            panic!("Expected a returned call");
+        };
+        assert_eq!(**callee, Expression::FieldAccess {
+            object: Box::new(Expression::Identifier("math".to_string())),
+            field: Symbol::new("sqrt"),
+        });
+        assert_eq!(args.len(), 1);
+
+        assert!(parse_source("proto main() { return f()::g }").is_err());
+        assert!(parse_source("proto main() { return math:: }").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_token_window() {
+        let tokens = Lexer::new("proto main() { let = 5 }").tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let err = parser.parse().unwrap_err();
+        let err = err.downcast_ref::<ParseError>().unwrap();
+        assert_eq!(err.token_index, 6);
+        assert!(err.message.starts_with("Expected variable name"), "{}", err.message);
+
+        let window = err.token_window(parser.tokens(), 2);
+        let lines: Vec<&str> = window.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2], r#">>     6  Equal "=" at 1:20"#);
+        assert!(lines[1].starts_with("       5  Let"));
+    }
+
+    #[test]
+    fn test_non_integer_ranges_are_rejected() {
+        let err = parse_source("proto f(b) { return match b { true..false => 1, _ => 0 } }").unwrap_err();
+        assert_eq!(err.to_string(), "Range patterns need integer bounds, got 'true' at line 1");
+
+        assert!(parse_source(r#"proto f(s) { return match s { "a".."z" => 1, _ => 0 } }"#).is_err());
+        assert!(parse_source("proto f(x) { return match x { 1.5..2 => 1, _ => 0 } }").is_err());
+        assert!(parse_source("proto f(x) { return match x { 1..true => 1, _ => 0 } }").is_err());
+    }
+
+    #[test]
+    fn test_let_mut() {
+        let module = parse_source("proto main() {\n let mut a = 1\n var b = 2\n let c = 3\n return a\n}").unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let mutability: Vec<(&str, bool)> = func.body[..3].iter()
+            .map(|stmt| match &stmt.kind {
+                StatementKind::VariableDecl { name, mutable, .. } => (name.as_str(), *mutable),
+                _ => panic!("Expected a variable declaration"),
+            })
+            .collect();
+        assert_eq!(mutability, vec![("a", true), ("b", true), ("c", false)]);
+
+        assert!(parse_source("proto main() { var mut a = 1 }").is_err());
+    }
+
+    #[test]
+    fn test_assignment() {
+        let module = parse_source("proto main() {\n var xs = [1]\n xs = [2, 3]\n xs[0] = xs[1] + 1\n return xs\n}").unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Assignment { target: Expression::Identifier(name), .. } = &func.body[1].kind else {
+            panic!("Expected an assignment to a variable");
+        };
+        assert_eq!(name, "xs");
+        assert!(matches!(
+            &func.body[2].kind,
+            StatementKind::Assignment { target: Expression::IndexAccess { .. }, value: Expression::Binary { .. } }
+        ));
+
+        // `==` is still a comparison
+        assert!(parse_source("proto main() { var x = 1\n x == 2 }").is_ok());
+
+        let err = parse_source("proto main() { f() = 1 }").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid assignment target at line 1, column 16");
+        assert!(parse_source("proto main() { r.x = 1 }").is_err());
+    }
+
+    #[test]
+    fn test_slices() {
+        let returned = |body: &str| {
+            let module = parse_source(&format!("proto main() {{ return {} }}", body)).unwrap();
+            let Declaration::Function(func) = &module.declarations[0] else {
+                panic!("Expected function declaration");
+            };
+            let StatementKind::Return(Some(expr)) = &func.body[0].kind else {
+                panic!("Expected a return");
+            };
+            expr.clone()
+        };
+        let items = || Box::new(Expression::Identifier("items".to_string()));
+        let int = |n| Some(Box::new(Expression::Literal(Literal::Integer(n))));
+
+        assert_eq!(returned("items[1:3]"), Expression::SliceAccess { object: items(), start: int(1), end: int(3) });
+        assert_eq!(returned("items[:2]"), Expression::SliceAccess { object: items(), start: None, end: int(2) });
+        assert_eq!(returned("items[2:]"), Expression::SliceAccess { object: items(), start: int(2), end: None });
+        assert_eq!(returned("items[:]"), Expression::SliceAccess { object: items(), start: None, end: None });
+        assert!(matches!(returned("items[2]"), Expression::IndexAccess { .. }));
+        assert!(parse_source("proto main() { return items[1:2:3] }").is_err());
+    }
+
+    #[test]
+    fn test_solve_objective() {
+        let source = r#"
+            solve cheapest() {
+                let x in range(0, 5)
+                ensure x > 1
+                minimize x * 3
+                return x
+            }
+        "#;
+        
+        let module = parse_source(source).unwrap();
+        let Declaration::Solve(solve) = &module.declarations[0] else {
+            panic!("Expected solve block");
+        };
+        assert_eq!(solve.constraints.len(), 3);
+        assert!(matches!(&solve.constraints[0], Constraint::Choice { name, .. } if name == "x"));
+        assert!(matches!(&solve.constraints[1], Constraint::Ensure(_)));
+        assert!(matches!(
+            &solve.constraints[2],
+            Constraint::Objective { kind: ObjectiveKind::Min, .. }
+        ));
+    }
+
+    #[test]
+    fn test_solve_return_is_last() {
+        let source = r#"
+            solve pick() {
+                let x in range(0, 5)
+                return x
+                ensure x > 1
+            }
+        "#;
+        let error = parse_source(source).unwrap_err().to_string();
+        assert_eq!(error, "'return' must be the last line of solve block pick, but more follows at line 5");
+
+        let twice = "solve pick() {\n    let x in range(0, 5)\n    return x\n    return x + 1\n}";
+        assert!(parse_source(twice).is_err());
+    }
+
+    #[test]
+    fn test_block_statement() {
+        let source = r#"
+            proto main() {
+                { let tmp = 1; log(tmp) }
+                return 0
+            }
+        "#;
+        
+        let module = parse_source(source).unwrap();
+        let Declaration::Function(main) = &module.declarations[0] else {
+            panic!("Expected function");
+        };
+        assert_eq!(main.body.len(), 2);
+        match &main.body[0].kind {
+            StatementKind::Expression(Expression::Block(stmts)) => assert_eq!(stmts.len(), 2),
+            other => panic!("Expected block statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_record_pattern() {
+        let source = r#"
+            proto main(point) {
+                return match point {
+                    { x: 0, y } => y,
+                    {} => 1,
+                    _ => 0
+                }
+            }
+        "#;
+        
+        let module = parse_source(source).unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Return(Some(Expression::Match { arms, .. })) = &func.body[0].kind else {
+            panic!("Expected match expression");
+        };
+        assert_eq!(arms[0].pattern, Pattern::Record(vec![
+            (Symbol::new("x"), Pattern::Literal(Literal::Integer(0))),
+            (Symbol::new("y"), Pattern::Identifier("y".to_string())),
+        ]));
+        assert_eq!(arms[1].pattern, Pattern::Record(vec![]));
+    }
+
+    #[test]
+    fn test_list_pattern() {
+        let source = r#"
+            proto main(xs) {
+                return match xs { [] => 0, [x] => x, [first, ..rest] => first }
+            }
+        "#;
+        
+        let module = parse_source(source).unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Return(Some(Expression::Match { arms, .. })) = &func.body[0].kind else {
+            panic!("Expected match expression");
+        };
+        assert_eq!(arms[0].pattern, Pattern::List(vec![], None));
+        assert_eq!(arms[1].pattern, Pattern::List(vec![Pattern::Identifier("x".to_string())], None));
+        assert_eq!(arms[2].pattern, Pattern::List(
+            vec![Pattern::Identifier("first".to_string())],
+            Some("rest".to_string()),
+        ));
+    }
+
+    #[test]
+    fn test_negative_patterns() {
+        let source = r#"
+            proto sign(x) {
+                return match x {
+                    -1 => "minus one",
+                    -5..-2 => "negative",
+                    _ => "other"
+                }
+            }
+        "#;
+        
+        let module = parse_source(source).unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Return(Some(Expression::Match { arms, .. })) = &func.body[0].kind else {
+            panic!("Expected return of a match expression");
+        };
+        
+        assert_eq!(arms[0].pattern, Pattern::Literal(Literal::Integer(-1)));
+        assert_eq!(arms[1].pattern, Pattern::Range(
+            Box::new(Pattern::Literal(Literal::Integer(-5))),
+            Box::new(Pattern::Literal(Literal::Integer(-2))),
+        ));
+    }
+
+    #[test]
+    fn test_reversed_range_pattern() {
+        let source = r#"
+            proto grade(n) {
+                return match n {
+                    10..5 => "never",
+                    _ => "other"
+                }
+            }
+        "#;
+        let err = parse_source(source).unwrap_err();
+        assert_eq!(err.to_string(), "Range pattern 10..5 is empty: its end is below its start at line 4");
+
+        let err = parse_source("proto f(n) { return match n { 1..x => 0 } }").unwrap_err();
+        assert!(err.to_string().starts_with("Range pattern must end with an integer literal"));
+
+        // A single-value range is allowed
+        assert!(parse_source("proto f(n) { return match n { 5..5 => 0 } }").is_ok());
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let source = r#"
+            proto test() {
+                let square = x => x * x
+                let add = (a, b: Int) => a + b
+                let grouped = (1 + 2) * 3
+            }
+        "#;
+        
+        let module = parse_source(source).unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        
+        let lambda_arity = |stmt: &Statement| match &stmt.kind {
+            StatementKind::VariableDecl { initializer: Expression::Lambda { params, .. }, .. } => Some(params.len()),
+            _ => None,
+        };
+        assert_eq!(lambda_arity(&func.body[0]), Some(1));
+        assert_eq!(lambda_arity(&func.body[1]), Some(2));
+        assert_eq!(lambda_arity(&func.body[2]), None);
+    }
+
     #[test]
     fn test_variable_declarations() {
         let source = r#"
@@ -944,4 +1949,77 @@ mod tests {
         let module = parse_source(source).unwrap();
         assert_eq!(module.declarations.len(), 1);
     }
+
+    #[test]
+    fn test_spread_arguments() {
+        let module = parse_source("proto main() { return add3(1, ...rest) }").unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Return(Some(Expression::Call { args, .. })) = &func.body[0].kind else {
+            panic!("Expected a returned call");
+        };
+        assert_eq!(args, &vec![
+            Expression::Literal(Literal::Integer(1)),
+            Expression::Spread(Box::new(Expression::Identifier("rest".to_string()))),
+        ]);
+
+        // `...` is only allowed in argument lists
+        assert!(parse_source("proto main() { return ...rest }").is_err());
+    }
+
+    #[test]
+    fn test_cast_precedence() {
+        let module = parse_source("proto main() { return 1 + -n * m as Float as String }").unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Return(Some(expr)) = &func.body[0].kind else {
+            panic!("Expected a return");
+        };
+        let cast = |expr: Expression, target: &str| Expression::Cast {
+            expr: Box::new(expr),
+            target: TypeAnnotation::Named(target.to_string()),
+        };
+        let m = cast(cast(Expression::Identifier("m".to_string()), "Float"), "String");
+        let negated = Expression::Unary { op: UnaryOp::Negate, expr: Box::new(Expression::Identifier("n".to_string())) };
+        assert_eq!(expr, &Expression::Binary {
+            left: Box::new(Expression::Literal(Literal::Integer(1))),
+            op: BinaryOp::Add,
+            right: Box::new(Expression::Binary {
+                left: Box::new(negated),
+                op: BinaryOp::Multiply,
+                right: Box::new(m),
+            }),
+        });
+
+        assert!(parse_source("proto main() { return x as }").is_err());
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let module = parse_source(r#"proto main() { return greet("Ada", greeting: "Hi", loud: x) }"#).unwrap();
+        let Declaration::Function(func) = &module.declarations[0] else {
+            panic!("Expected function declaration");
+        };
+        let StatementKind::Return(Some(Expression::Call { args, .. })) = &func.body[0].kind else {
+            panic!("Expected a returned call");
+        };
+        assert_eq!(args, &vec![
+            Expression::Literal(Literal::String("Ada".to_string())),
+            Expression::NamedArg {
+                name: "greeting".to_string(),
+                value: Box::new(Expression::Literal(Literal::String("Hi".to_string()))),
+            },
+            Expression::NamedArg {
+                name: "loud".to_string(),
+                value: Box::new(Expression::Identifier("x".to_string())),
+            },
+        ]);
+
+        let error = parse_source("proto main() { return greet(name: 1, \"Hi\") }").unwrap_err();
+        assert!(error.to_string().contains("Positional argument after named arguments"));
+        let error = parse_source("proto main() { return greet(name: 1, name: 2) }").unwrap_err();
+        assert!(error.to_string().contains("Argument name is given twice"));
+    }
 }
@@ -0,0 +1,142 @@
+//! Side-effect analysis for `mrc run --deny-side-effects`
+//!
+//! A function is impure if it refers to one of the interpreter's impure
+//! builtins (IO, randomness, the clock), or to another function that is.
+//! References count as well as calls, since a function passed to `fill` or
+//! `flat_map` runs too. Locals that shadow a function name are not tracked,
+//! so the analysis errs on the side of reporting.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::ast::*;
+use crate::ast::visitor::{self, Visitor};
+use crate::interpreter::IMPURE_BUILTINS;
+
+/// The call graph of a module's functions
+pub struct Purity {
+    /// Names each function refers to, in order of first reference
+    references: HashMap<String, Vec<String>>,
+}
+
+impl Purity {
+    /// Build the call graph of `module`
+    pub fn new(module: &Module) -> Self {
+        let mut references = HashMap::new();
+        for decl in &module.declarations {
+            if let Declaration::Function(func) = decl {
+                let mut collector = References::default();
+                visitor::walk_function(&mut collector, func);
+                references.insert(func.name.clone(), collector.names);
+            }
+        }
+        Purity { references }
+    }
+
+    /// Whether the module declares a function called `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.references.contains_key(name)
+    }
+
+    /// The shortest chain of calls from `name` to an impure builtin, starting
+    /// with `name` and ending with the builtin, or `None` if it is pure
+    pub fn side_effect_path(&self, name: &str) -> Option<Vec<String>> {
+        // Breadth-first, remembering how each function was reached
+        let mut reached_from: HashMap<&str, &str> = HashMap::new();
+        let mut seen = HashSet::from([name]);
+        let mut queue = VecDeque::from([name]);
+
+        while let Some(current) = queue.pop_front() {
+            for callee in self.references.get(current).into_iter().flatten() {
+                if IMPURE_BUILTINS.contains(&callee.as_str()) {
+                    let mut path = vec![callee.clone(), current.to_string()];
+                    let mut node = current;
+                    while let Some(&caller) = reached_from.get(node) {
+                        path.push(caller.to_string());
+                        node = caller;
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                if self.references.contains_key(callee) && seen.insert(callee) {
+                    reached_from.insert(callee, current);
+                    queue.push_back(callee);
+                }
+            }
+        }
+        None
+    }
+
+    /// Every function with a side effect, sorted by name
+    pub fn impure_functions(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.references.keys()
+            .filter(|name| self.side_effect_path(name).is_some())
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+/// Collects the identifiers an expression tree refers to
+#[derive(Default)]
+struct References {
+    names: Vec<String>,
+}
+
+impl Visitor for References {
+    fn visit_expression(&mut self, expr: &Expression) {
+        if let Expression::Identifier(name) = expr {
+            if !self.names.contains(name) {
+                self.names.push(name.clone());
+            }
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn purity_of(source: &str) -> Purity {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let module = parser.parse().unwrap();
+        Purity::new(&module)
+    }
+
+    #[test]
+    fn test_indirect_log_is_impure() {
+        let purity = purity_of(r#"
+            proto report(x) {
+                log(x)
+                return x
+            }
+
+            proto scale(x) {
+                return report(x * 2)
+            }
+
+            proto total(xs) {
+                return flat_map(xs, x => [scale(x)])
+            }
+
+            proto square(x) {
+                return x * x
+            }
+
+            proto count(n) {
+                return if n == 0 { 0 } else { count(n - 1) + square(n) }
+            }
+        "#);
+
+        let path = |name: &str| purity.side_effect_path(name).map(|path| path.join(" -> "));
+        assert_eq!(path("report").as_deref(), Some("report -> log"));
+        assert_eq!(path("total").as_deref(), Some("total -> scale -> report -> log"));
+        assert_eq!(path("square"), None);
+        assert_eq!(path("count"), None);
+        assert_eq!(purity.impure_functions(), vec!["report", "scale", "total"]);
+    }
+}
@@ -0,0 +1,143 @@
+//! Project metrics for `mrc stats`
+//!
+//! Counts are summed over every module added. The stability score is the
+//! share of functions that have been hardened to `solid`.
+
+use crate::ast::*;
+use crate::ast::visitor::{self, Visitor};
+
+/// Totals over one or more modules
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub files: usize,
+    pub lines: usize,
+    pub proto_functions: usize,
+    pub solid_functions: usize,
+    pub type_declarations: usize,
+    /// Statements in function bodies, including nested ones
+    pub function_statements: usize,
+}
+
+impl Stats {
+    /// Add the metrics of a module parsed from `source`
+    pub fn add_module(&mut self, source: &str, module: &Module) {
+        self.files += 1;
+        self.lines += source.lines().count();
+        for decl in &module.declarations {
+            match decl {
+                Declaration::Function(func) => {
+                    match func.mode {
+                        FunctionMode::Proto => self.proto_functions += 1,
+                        FunctionMode::Solid => self.solid_functions += 1,
+                    }
+                    let mut counter = StatementCount::default();
+                    visitor::walk_function(&mut counter, func);
+                    self.function_statements += counter.count;
+                }
+                Declaration::Type(_) => self.type_declarations += 1,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn functions(&self) -> usize {
+        self.proto_functions + self.solid_functions
+    }
+
+    /// Mean statements per function; 0 without functions
+    pub fn average_function_length(&self) -> f64 {
+        if self.functions() == 0 {
+            return 0.0;
+        }
+        self.function_statements as f64 / self.functions() as f64
+    }
+
+    /// Percentage of functions that are solid; 0 without functions
+    pub fn stability(&self) -> f64 {
+        if self.functions() == 0 {
+            return 0.0;
+        }
+        self.solid_functions as f64 * 100.0 / self.functions() as f64
+    }
+
+    /// The metrics as aligned `name: value` lines
+    pub fn report(&self) -> String {
+        let rows = [
+            ("files", self.files.to_string()),
+            ("lines", self.lines.to_string()),
+            ("functions", format!(
+                "{} ({} proto, {} solid)",
+                self.functions(), self.proto_functions, self.solid_functions
+            )),
+            ("type declarations", self.type_declarations.to_string()),
+            ("average function length", format!("{:.1} statements", self.average_function_length())),
+            ("stability", format!("{:.1}% solid", self.stability())),
+        ];
+        rows.iter()
+            .map(|(name, value)| format!("{:<25} {}\n", format!("{}:", name), value))
+            .collect()
+    }
+}
+
+/// Counts the statements in a function body
+#[derive(Default)]
+struct StatementCount {
+    count: usize,
+}
+
+impl Visitor for StatementCount {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        self.count += 1;
+        visitor::walk_statement(self, stmt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_stats_of_module() {
+        let source = r#"type Point = { x: Int, y: Int }
+
+proto norm(p) {
+    let squared = p.x * p.x + p.y * p.y
+    return sqrt(to_float(squared))
+}
+
+solid add(a: Int, b: Int) -> Int {
+    return a + b
+}
+
+proto main() {
+    for i in range(0, 3) {
+        log(i)
+    }
+    return add(1, 2)
+}
+"#;
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let module = Parser::new(tokens).parse().unwrap();
+
+        let mut stats = Stats::default();
+        stats.add_module(source, &module);
+        assert_eq!(stats, Stats {
+            files: 1,
+            lines: 17,
+            proto_functions: 2,
+            solid_functions: 1,
+            type_declarations: 1,
+            function_statements: 6,
+        });
+        assert_eq!(stats.report(), "\
+files:                    1
+lines:                    17
+functions:                3 (2 proto, 1 solid)
+type declarations:        1
+average function length:  2.0 statements
+stability:                33.3% solid
+");
+    }
+}
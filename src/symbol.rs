@@ -0,0 +1,134 @@
+//! Interned names for record fields
+//!
+//! A `Symbol` is a shared copy of a name together with its hash. A
+//! `SymbolTable` hands out one copy per distinct name, so two symbols from
+//! the same table are the same name exactly when they point at the same
+//! copy, and record lookups hash and compare without reading the text.
+//! Symbols from different tables fall back to comparing it.
+//!
+//! There is no table shared by the whole process: the parser interns the
+//! names written in the source, and the interpreter has its own table for
+//! names it only meets at runtime, which is freed along with it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::rc::Rc;
+
+/// An interned name
+#[derive(Clone)]
+pub struct Symbol(Rc<Name>);
+
+struct Name {
+    hash: u64,
+    text: Box<str>,
+}
+
+impl Symbol {
+    /// A symbol for `name` that belongs to no table, for a one-off lookup
+    pub fn new(name: &str) -> Symbol {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        Symbol(Rc::new(Name { hash: hasher.finish(), text: name.into() }))
+    }
+
+    /// The interned name
+    pub fn as_str(&self) -> &str {
+        &self.0.text
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+            || (self.0.hash == other.0.hash && self.0.text == other.0.text)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.0.hash);
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Alphabetical, like the names themselves
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// One symbol per distinct name
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: HashMap<Box<str>, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The symbol for `name`, adding it to the table if it's new
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.lookup(name) {
+            return symbol;
+        }
+        let symbol = Symbol::new(name);
+        self.symbols.insert(name.into(), symbol.clone());
+        symbol
+    }
+
+    /// The symbol for `name` if it's already in the table, leaving the
+    /// table as it is
+    pub fn lookup(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_name_same_symbol() {
+        let mut table = SymbolTable::new();
+        let name = String::from("total");
+        let first = table.intern(&name);
+        let second = table.intern("total");
+
+        assert_eq!(first, second);
+        assert!(Rc::ptr_eq(&first.0, &second.0));
+        assert_ne!(first, table.intern("count"));
+        assert_eq!(first.to_string(), "total");
+        assert!(table.intern("count") < first);
+
+        // Looking a name up doesn't add it
+        assert_eq!(table.lookup("total"), Some(first.clone()));
+        assert_eq!(table.lookup("missing"), None);
+        assert_eq!(table.lookup("missing"), None);
+
+        // Symbols from elsewhere still match by name
+        assert_eq!(Symbol::new("total"), first);
+    }
+}
@@ -0,0 +1,79 @@
+//! Text tables for `mrc run --out-format table`
+//!
+//! A list of records becomes one row per record, with a column for every
+//! key any record has, in order of first appearance. Cells are padded to
+//! the widest value in their column; missing fields are left blank.
+
+use indexmap::IndexSet;
+use crate::interpreter::value::Value;
+use crate::symbol::Symbol;
+
+/// Render a list of records as an aligned table, or `None` for any other value
+pub fn render_table(value: &Value) -> Option<String> {
+    let Value::List(items) = value else {
+        return None;
+    };
+    let records = items.iter()
+        .map(|item| match item {
+            Value::Record(fields) => Some(fields),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let columns: IndexSet<Symbol> = records.iter().flat_map(|fields| fields.keys().cloned()).collect();
+    let rows: Vec<Vec<String>> = records.iter()
+        .map(|fields| columns.iter()
+            .map(|column| fields.get(column).map(|value| value.to_string()).unwrap_or_default())
+            .collect())
+        .collect();
+    let widths: Vec<usize> = columns.iter()
+        .enumerate()
+        .map(|(i, column)| rows.iter()
+            .map(|row| row[i].chars().count())
+            .fold(column.as_str().chars().count(), usize::max))
+        .collect();
+
+    let header: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut out = String::new();
+    for row in [&header, &rule].into_iter().chain(&rows) {
+        let cells: Vec<String> = row.iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+
+    fn record(fields: &[(&str, Value)]) -> Value {
+        Value::Record(fields.iter().map(|(key, value)| (Symbol::new(key), value.clone())).collect::<IndexMap<_, _>>())
+    }
+
+    #[test]
+    fn test_two_row_table() {
+        let rows = Value::list(vec![
+            record(&[("name", Value::String("ada".to_string())), ("age", Value::Integer(36))]),
+            record(&[("name", Value::String("grace".to_string())), ("lang", Value::String("COBOL".to_string()))]),
+        ]);
+
+        assert_eq!(render_table(&rows).unwrap(), "\
+name   age  lang
+-----  ---  -----
+ada    36
+grace       COBOL
+");
+    }
+
+    #[test]
+    fn test_other_shapes_are_not_tables() {
+        assert_eq!(render_table(&Value::Integer(1)), None);
+        assert_eq!(render_table(&Value::list(vec![Value::Integer(1)])), None);
+    }
+}
@@ -1,12 +1,28 @@
 use crate::ast::*;
 use crate::interpreter::value::Value;
-use super::{Type, TypeEnvironment, TypeError, GhostAttribute, GhostValue, annotation_to_type};
+use super::{Type, TypeEnvironment, TypeError, SpannedTypeError, TypeWarning, GhostAttribute, GhostValue, annotation_to_type};
+use indexmap::IndexMap;
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
 
 /// Type checker for Morph programs
 pub struct TypeChecker {
     environment: TypeEnvironment,
-    errors: Vec<TypeError>,
+    errors: Vec<SpannedTypeError>,
+    warnings: Vec<TypeWarning>,
+    /// Location of the innermost statement the current error came from
+    error_span: Option<Span>,
+    /// Common type of the values returned so far by the function being checked
+    returns: Option<Type>,
+    /// Name of the function being checked, if any
+    function: Option<String>,
+    /// Whether the code being checked is in a solid function, the only
+    /// place discarded values are warned about
+    solid: bool,
+    /// Types of functions and `let` bindings, in the order they were checked
+    inferred: IndexMap<String, Type>,
+    /// Parameter names of the module's functions, to place named arguments
+    param_names: HashMap<String, Vec<String>>,
 }
 
 impl TypeChecker {
@@ -14,17 +30,71 @@ impl TypeChecker {
         TypeChecker {
             environment: TypeEnvironment::new(),
             errors: Vec::new(),
+            warnings: Vec::new(),
+            error_span: None,
+            returns: None,
+            function: None,
+            solid: false,
+            inferred: IndexMap::new(),
+            param_names: HashMap::new(),
         }
     }
 
+    /// Warnings found by the last `check_module`, in source order
+    pub fn warnings(&self) -> &[TypeWarning] {
+        &self.warnings
+    }
+
+    /// Types inferred by the last `check_module`: each function's type under
+    /// its name, each of its `let` bindings as `function::name`, and
+    /// top-level bindings under their own names
+    pub fn inferred_types(&self) -> &IndexMap<String, Type> {
+        &self.inferred
+    }
+
+    /// Record an error, attaching the location of the statement it came from
+    fn report(&mut self, error: TypeError) {
+        self.errors.push(SpannedTypeError {
+            error,
+            span: self.error_span.take(),
+        });
+    }
+
+    /// Remember `span` as the location of a failure in `result`, unless a
+    /// statement nested inside it has already claimed the error
+    fn locate<T>(&mut self, span: Span, result: Result<T, TypeError>) -> Result<T, TypeError> {
+        if result.is_err() && self.error_span.is_none() {
+            self.error_span = Some(span);
+        }
+        result
+    }
+
     /// Check a complete module
-    pub fn check_module(&mut self, module: &Module) -> Result<(), Vec<TypeError>> {
-        // First pass: register all type declarations
+    pub fn check_module(&mut self, module: &Module) -> Result<(), Vec<SpannedTypeError>> {
+        self.warnings.clear();
+
+        // Declare every type name before resolving any definition, so
+        // definitions can refer to themselves and to later types
         for decl in &module.declarations {
             if let Declaration::Type(type_decl) = decl {
-                if let Err(e) = self.register_type_declaration(type_decl) {
-                    self.errors.push(e);
+                self.environment.define_type(type_decl.name.clone(), Type::Named(type_decl.name.clone()));
+            }
+        }
+
+        // First pass: register all imports and type declarations
+        for decl in &module.declarations {
+            match decl {
+                Declaration::Import(import) => {
+                    if let Err(e) = self.register_import(import) {
+                        self.report(e);
+                    }
                 }
+                Declaration::Type(type_decl) => {
+                    if let Err(e) = self.register_type_declaration(type_decl) {
+                        self.report(e);
+                    }
+                }
+                _ => {}
             }
         }
 
@@ -32,22 +102,38 @@ impl TypeChecker {
         for decl in &module.declarations {
             if let Declaration::Function(func) = decl {
                 if let Err(e) = self.register_function_signature(func) {
-                    self.errors.push(e);
+                    self.report(e);
+                }
+            }
+        }
+
+        // Top-level bindings, in order, so function bodies can see them
+        for decl in &module.declarations {
+            if let Declaration::Global(stmt) = decl {
+                self.error_span = None;
+                if let Err(e) = self.check_statement(stmt) {
+                    self.report(e);
                 }
             }
         }
 
         // Third pass: type check function bodies
         for decl in &module.declarations {
+            self.error_span = None;
             match decl {
                 Declaration::Function(func) => {
                     if let Err(e) = self.check_function(func) {
-                        self.errors.push(e);
+                        self.report(e);
                     }
                 }
                 Declaration::Solve(solve) => {
                     if let Err(e) = self.check_solve_block(solve) {
-                        self.errors.push(e);
+                        self.report(e);
+                    }
+                }
+                Declaration::Verify(verify) => {
+                    if let Err(e) = self.check_verify_block(verify) {
+                        self.report(e);
                     }
                 }
                 _ => {}
@@ -61,11 +147,23 @@ impl TypeChecker {
         }
     }
 
+    /// Register an imported module's exports under its binding name
+    fn register_import(&mut self, import: &Import) -> Result<(), TypeError> {
+        let ty = module_type(&import.module)
+            .ok_or_else(|| TypeError::Custom(format!("Unknown module: {}", import.module)))?;
+        self.environment.define_variable(import.binding_name().to_string(), ty);
+        Ok(())
+    }
+
     /// Register a type declaration
     fn register_type_declaration(&mut self, decl: &TypeDecl) -> Result<(), TypeError> {
         let ty = match &decl.definition {
             TypeDefinition::Alias(annotation) => {
-                annotation_to_type(annotation, &self.environment)?
+                let ty = annotation_to_type(annotation, &self.environment)?;
+                if self.resolve(ty.clone()) == Type::Named(decl.name.clone()) {
+                    return Err(TypeError::Custom(format!("Type {} is defined as itself", decl.name)));
+                }
+                ty
             }
             TypeDefinition::Record(fields) => {
                 let mut field_types = std::collections::HashMap::new();
@@ -74,7 +172,7 @@ impl TypeChecker {
                 }
                 Type::Record(field_types)
             }
-            TypeDefinition::Enum(variants) => {
+            TypeDefinition::Enum(_variants) => {
                 // For now, enums are treated as strings
                 Type::String
             }
@@ -84,13 +182,24 @@ impl TypeChecker {
         Ok(())
     }
 
+    /// A scope in which a function's generic type parameters name
+    /// `Type::Generic` types
+    fn generic_scope(&self, func: &FunctionDecl) -> TypeEnvironment {
+        let mut scope = TypeEnvironment::with_parent(self.environment.clone());
+        for name in &func.type_params {
+            scope.define_type(name.clone(), Type::Generic(name.clone()));
+        }
+        scope
+    }
+
     /// Register a function signature
     fn register_function_signature(&mut self, func: &FunctionDecl) -> Result<(), TypeError> {
+        let scope = self.generic_scope(func);
         let param_types: Result<Vec<_>, _> = func.params
             .iter()
             .map(|p| {
                 if let Some(ref annotation) = p.type_annotation {
-                    annotation_to_type(annotation, &self.environment)
+                    annotation_to_type(annotation, &scope)
                 } else {
                     Ok(Type::Variable(format!("param_{}", p.name)))
                 }
@@ -98,27 +207,33 @@ impl TypeChecker {
             .collect();
         
         let return_type = if let Some(ref annotation) = func.return_type {
-            annotation_to_type(annotation, &self.environment)?
+            annotation_to_type(annotation, &scope)?
         } else {
             Type::Unit
         };
         
         let func_type = Type::Function(param_types?, Box::new(return_type));
         self.environment.define_variable(func.name.clone(), func_type);
+        self.param_names.insert(func.name.clone(), func.params.iter().map(|p| p.name.clone()).collect());
         
         Ok(())
     }
 
     /// Type check a function
     fn check_function(&mut self, func: &FunctionDecl) -> Result<(), TypeError> {
-        // Create new scope for function
+        // Listed ahead of its bindings; the return type is filled in below
+        if let Some(ty) = self.environment.get_variable(&func.name) {
+            self.inferred.insert(func.name.clone(), ty);
+        }
+
+        // Create new scope for function, with its type parameters
         let previous = self.environment.clone();
-        self.environment = TypeEnvironment::with_parent(self.environment.clone());
+        self.environment = self.generic_scope(func);
         
         // Bind parameters
         for param in &func.params {
             let param_type = if let Some(ref annotation) = param.type_annotation {
-                annotation_to_type(annotation, &previous)?
+                annotation_to_type(annotation, &self.environment)?
             } else {
                 Type::Variable(format!("param_{}", param.name))
             };
@@ -126,20 +241,92 @@ impl TypeChecker {
         }
         
         // Get expected return type
-        let expected_return = if let Some(ref annotation) = func.return_type {
-            annotation_to_type(annotation, &previous)?
+        let _expected_return = if let Some(ref annotation) = func.return_type {
+            annotation_to_type(annotation, &self.environment)?
         } else {
             Type::Unit
         };
         
         // Check function body
-        for stmt in &func.body {
-            self.check_statement(stmt)?;
-        }
+        let outer_returns = self.returns.take();
+        let outer_function = self.function.replace(func.name.clone());
+        let outer_solid = std::mem::replace(&mut self.solid, func.mode == FunctionMode::Solid);
+        let body = func.body.iter().enumerate().try_for_each(|(i, stmt)| match &stmt.kind {
+            // The last statement is the body's value when no `return` ends
+            // it, so its value isn't discarded
+            StatementKind::Expression(expr) if i + 1 == func.body.len() => {
+                let ty = self.infer_expression(expr);
+                self.locate(stmt.span, ty).map(drop)
+            }
+            _ => self.check_statement(stmt),
+        });
+        let returns = std::mem::replace(&mut self.returns, outer_returns);
+        self.function = outer_function;
+        self.solid = outer_solid;
+        body?;
         
         // Restore environment
         self.environment = previous;
         
+        // Without an annotation, the function returns whatever its returns
+        // have in common
+        if let (None, Some(ret)) = (&func.return_type, returns) {
+            if let Some(Type::Function(params, _)) = self.environment.get_variable(&func.name) {
+                let func_type = Type::Function(params, Box::new(ret));
+                self.inferred.insert(func.name.clone(), func_type.clone());
+                self.environment.define_variable(func.name.clone(), func_type);
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// The definition a `Type::Named` stands for, following aliases of
+    /// aliases; other types are returned as they are. A name whose
+    /// definition hasn't been resolved yet stays a `Type::Named`.
+    fn resolve(&self, mut ty: Type) -> Type {
+        let mut seen = HashSet::new();
+        while let Type::Named(name) = &ty {
+            if !seen.insert(name.clone()) {
+                return Type::Error;
+            }
+            match self.environment.get_type(name) {
+                Some(Type::Named(next)) if next == *name => break,
+                Some(definition) => ty = definition,
+                None => return Type::Error,
+            }
+        }
+        ty
+    }
+
+    /// The name and parameter names of the module function `callee` calls,
+    /// unless a local binding hides it
+    fn function_params<'a>(&'a self, callee: &'a Expression) -> Option<(&'a str, &'a [String])> {
+        let Expression::Identifier(name) = callee else {
+            return None;
+        };
+        if !self.environment.is_global_variable(name) {
+            return None;
+        }
+        Some((name, self.param_names.get(name)?))
+    }
+
+    /// Note a `let` binding's type for `inferred_types`
+    fn record_binding(&mut self, name: &str, ty: &Type) {
+        let key = match &self.function {
+            Some(function) => format!("{}::{}", function, name),
+            None => name.to_string(),
+        };
+        self.inferred.insert(key, ty.clone());
+    }
+
+    /// Fold the type of a `return` into the function's return type
+    fn record_return(&mut self, ty: Type) -> Result<(), TypeError> {
+        let joined = match self.returns.take() {
+            Some(previous) => common_type(&previous, &ty)?,
+            None => ty,
+        };
+        self.returns = Some(joined);
         Ok(())
     }
 
@@ -166,13 +353,24 @@ impl TypeChecker {
                     let ty = self.infer_expression(expr)?;
                     self.environment.define_variable(name.clone(), ty);
                 }
-                Constraint::Ensure(expr) => {
-                    let ty = self.infer_expression(expr)?;
-                    if ty != Type::Bool {
-                        return Err(TypeError::Mismatch {
-                            expected: Type::Bool,
+                Constraint::Ensure(expr) => self.check_ensure(expr)?,
+                Constraint::Choice { name, domain } => {
+                    let element = match self.infer_expression(domain)? {
+                        Type::List(element) => *element,
+                        ty @ Type::Variable(_) => ty,
+                        ty => return Err(TypeError::Mismatch {
+                            expected: Type::List(Box::new(Type::Variable("a".to_string()))),
                             got: ty,
-                        });
+                        }),
+                    };
+                    self.environment.define_variable(name.clone(), element);
+                }
+                Constraint::Objective { expr, .. } => {
+                    let ty = self.infer_expression(expr)?;
+                    if !matches!(ty, Type::Int | Type::Float | Type::Variable(_)) {
+                        return Err(TypeError::InvalidOperation(
+                            format!("Objective must be numeric, got {:?}", ty)
+                        ));
                     }
                 }
             }
@@ -184,39 +382,132 @@ impl TypeChecker {
         Ok(())
     }
 
+    /// Type check a verify block
+    fn check_verify_block(&mut self, verify: &VerifyBlock) -> Result<(), TypeError> {
+        let previous = self.environment.clone();
+        self.environment = TypeEnvironment::with_parent(self.environment.clone());
+        
+        let result = verify.constraints.iter().try_for_each(|constraint| match constraint {
+            Constraint::Binding { name, expr } => {
+                let ty = self.infer_expression(expr)?;
+                self.environment.define_variable(name.clone(), ty);
+                Ok(())
+            }
+            Constraint::Ensure(expr) => self.check_ensure(expr),
+            Constraint::Choice { .. } | Constraint::Objective { .. } => Ok(()),
+        });
+        
+        self.environment = previous;
+        result
+    }
+
+    /// An `ensure` condition must be a Bool
+    fn check_ensure(&mut self, expr: &Expression) -> Result<(), TypeError> {
+        let ty = self.infer_expression(expr)?;
+        if ty != Type::Bool {
+            return Err(TypeError::Mismatch {
+                expected: Type::Bool,
+                got: ty,
+            });
+        }
+        Ok(())
+    }
+
+    /// Define the variables a pattern binds when matched against `ty`
+    fn bind_pattern(&mut self, pattern: &Pattern, ty: &Type) {
+        match pattern {
+            Pattern::Identifier(name) => {
+                self.environment.define_variable(name.clone(), ty.clone());
+            }
+            Pattern::Record(fields) => {
+                for (name, field_pattern) in fields {
+                    let field_type = match ty {
+                        Type::Record(field_types) => field_types.get(name.as_str()).cloned(),
+                        _ => None,
+                    };
+                    let field_type = field_type.unwrap_or_else(|| Type::Variable(format!("field_{}", name)));
+                    self.bind_pattern(field_pattern, &field_type);
+                }
+            }
+            Pattern::Tuple(items) => {
+                for item in items {
+                    self.bind_pattern(item, &Type::Variable("tuple_item".to_string()));
+                }
+            }
+            Pattern::List(items, rest) => {
+                let element = match ty {
+                    Type::List(element) => (**element).clone(),
+                    _ => Type::Variable("element".to_string()),
+                };
+                for item in items {
+                    self.bind_pattern(item, &element);
+                }
+                if let Some(rest) = rest {
+                    self.environment.define_variable(rest.clone(), Type::List(Box::new(element)));
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range(..) => {}
+        }
+    }
+
+    /// Warn about an expression statement whose value nothing uses, in
+    /// solid code
+    fn warn_if_discarded(&mut self, expr: &Expression, ty: &Type, span: Span) {
+        if self.solid && discards_value(expr, ty) {
+            self.warnings.push(TypeWarning {
+                message: format!("result of type {} is discarded", ty),
+                span,
+            });
+        }
+    }
+
     /// Type check a statement
     fn check_statement(&mut self, stmt: &Statement) -> Result<(), TypeError> {
-        match stmt {
-            Statement::VariableDecl { name, type_annotation, initializer, .. } => {
-                let inferred = self.infer_expression(initializer)?;
-                
+        let result = self.check_statement_kind(&stmt.kind, stmt.span);
+        self.locate(stmt.span, result)
+    }
+
+    fn check_statement_kind(&mut self, kind: &StatementKind, span: Span) -> Result<(), TypeError> {
+        match kind {
+            StatementKind::VariableDecl { name, type_annotation, initializer, mutable } => {
                 // If type annotation provided, check compatibility
-                if let Some(ref annotation) = type_annotation {
+                let ty = if let Some(ref annotation) = type_annotation {
                     let annotated = annotation_to_type(annotation, &self.environment)?;
+                    let inferred = self.infer_expected(initializer, &annotated)?;
                     if !self.is_compatible(&inferred, &annotated) {
                         return Err(TypeError::Mismatch {
                             expected: annotated,
                             got: inferred,
                         });
                     }
-                    self.environment.define_variable(name.clone(), annotated);
+                    annotated
+                } else {
+                    self.infer_expression(initializer)?
+                };
+                self.record_binding(name, &ty);
+                if *mutable {
+                    self.environment.define_variable(name.clone(), ty);
                 } else {
-                    self.environment.define_variable(name.clone(), inferred);
+                    self.environment.define_constant(name.clone(), ty);
                 }
                 Ok(())
             }
-            Statement::Expression(expr) => {
-                self.infer_expression(expr)?;
+            StatementKind::Expression(expr) => {
+                let ty = self.infer_expression(expr)?;
+                self.warn_if_discarded(expr, &ty, span);
                 Ok(())
             }
-            Statement::Return(expr) => {
-                if let Some(expr) = expr {
-                    self.infer_expression(expr)?;
-                }
-                Ok(())
+            StatementKind::Return(expr) => {
+                let ty = match expr {
+                    Some(expr) => self.infer_expression(expr)?,
+                    None => Type::Unit,
+                };
+                self.record_return(ty)
             }
-            Statement::For { variable, iterable, guard, body } => {
+            StatementKind::Break | StatementKind::Continue => Ok(()),
+            StatementKind::For { variable, iterable, guard, body } => {
                 let iter_type = self.infer_expression(iterable)?;
+                let iter_type = self.resolve(iter_type);
                 let element_type = match iter_type {
                     Type::List(elem) => *elem,
                     _ => return Err(TypeError::Custom(
@@ -250,47 +541,61 @@ impl TypeChecker {
                 self.environment = previous;
                 Ok(())
             }
-            Statement::Assignment { target, value } => {
-                // For now, simple assignment checking
-                self.infer_expression(value)?;
+            StatementKind::Assignment { target, value } => {
+                // A `let` can't be assigned to, nor can the elements of the
+                // list it holds
+                let variable = match target {
+                    Expression::IndexAccess { object, .. } => object.as_ref(),
+                    target => target,
+                };
+                if let Expression::Identifier(name) = variable {
+                    if self.environment.is_constant(name) {
+                        return Err(TypeError::InvalidOperation(
+                            format!("Cannot assign to {}, which is declared with let", name)
+                        ));
+                    }
+                }
+                // List literals are loosely typed, so the value only has to
+                // have a type in common with the target
+                let target_type = self.infer_expression(target)?;
+                let value_type = self.infer_expected(value, &target_type)?;
+                if !self.is_compatible(&value_type, &target_type) {
+                    common_type(&self.resolve(target_type), &self.resolve(value_type))?;
+                }
                 Ok(())
             }
         }
     }
 
+    /// Infer the type of an expression where a value of type `expected` is
+    /// wanted, so that list literals take their element type from it
+    /// rather than leaving it unknown
+    fn infer_expected(&mut self, expr: &Expression, expected: &Type) -> Result<Type, TypeError> {
+        match (expr, expected) {
+            (Expression::Literal(Literal::List(items)), Type::List(elem)) => {
+                for item in items {
+                    let item_type = self.infer_expected(item, elem)?;
+                    if !self.is_compatible(&item_type, elem) {
+                        return Err(TypeError::Mismatch {
+                            expected: elem.as_ref().clone(),
+                            got: item_type,
+                        });
+                    }
+                }
+                Ok(expected.clone())
+            }
+            _ => self.infer_expression(expr),
+        }
+    }
+
     /// Infer the type of an expression
     fn infer_expression(&mut self, expr: &Expression) -> Result<Type, TypeError> {
         match expr {
             Expression::Literal(lit) => self.infer_literal(lit),
             Expression::Identifier(name) => {
                 // Check for built-in functions first
-                match name.as_str() {
-                    "print" | "log" => {
-                        // Built-in print/log functions accept any arguments and return Unit
-                        return Ok(Type::Function(
-                            vec![Type::Variable("args".to_string())],
-                            Box::new(Type::Unit)
-                        ));
-                    }
-                    "len" => {
-                        return Ok(Type::Function(
-                            vec![Type::Variable("collection".to_string())],
-                            Box::new(Type::Int)
-                        ));
-                    }
-                    "range" => {
-                        return Ok(Type::Function(
-                            vec![Type::Int, Type::Int],
-                            Box::new(Type::List(Box::new(Type::Int)))
-                        ));
-                    }
-                    "sqrt" => {
-                        return Ok(Type::Function(
-                            vec![Type::Float],
-                            Box::new(Type::Float)
-                        ));
-                    }
-                    _ => {}
+                if let Some(ty) = builtin_type(name) {
+                    return Ok(ty);
                 }
                 self.environment.get_variable(name)
                     .ok_or_else(|| TypeError::UndefinedVariable(name.clone()))
@@ -298,6 +603,10 @@ impl TypeChecker {
             Expression::Binary { left, op, right } => {
                 let left_type = self.infer_expression(left)?;
                 let right_type = self.infer_expression(right)?;
+                // A literal zero divisor fails on every run, so reject it statically
+                if matches!(op, BinaryOp::Divide | BinaryOp::Modulo) && is_literal_zero(right) {
+                    return Err(TypeError::InvalidOperation("division by zero".to_string()));
+                }
                 self.infer_binary_op(&left_type, op, &right_type)
             }
             Expression::Unary { op, expr } => {
@@ -305,6 +614,43 @@ impl TypeChecker {
                 self.infer_unary_op(op, &expr_type)
             }
             Expression::Call { callee, args } => {
+                // Variadic builtins can't be described by a function type
+                if let Expression::Identifier(name) = callee.as_ref() {
+                    match name.as_str() {
+                        "print" | "log" => {
+                            for arg in args {
+                                self.infer_expression(arg)?;
+                            }
+                            return Ok(Type::Unit);
+                        }
+                        // push(xs, x) needs x to fit the elements xs already has
+                        "push" if args.len() == 2 => {
+                            let list_type = self.infer_expression(&args[0])?;
+                            let list_type = self.resolve(list_type);
+                            let item_type = self.infer_expression(&args[1])?;
+                            return match list_type {
+                                Type::List(elem) if matches!(*elem, Type::Variable(_)) => {
+                                    Ok(Type::List(Box::new(item_type)))
+                                }
+                                Type::List(elem) if self.is_compatible(&item_type, &elem) => Ok(Type::List(elem)),
+                                Type::List(elem) => Err(TypeError::Mismatch {
+                                    expected: *elem,
+                                    got: item_type,
+                                }),
+                                Type::Variable(_) => Ok(Type::List(Box::new(item_type))),
+                                other => Err(TypeError::Custom(format!("push() needs a list, got {:?}", other))),
+                            };
+                        }
+                        // call(f, a, b) is checked as f(a, b)
+                        "call" if !args.is_empty() => {
+                            return self.infer_expression(&Expression::Call {
+                                callee: Box::new(args[0].clone()),
+                                args: args[1..].to_vec(),
+                            });
+                        }
+                        _ => {}
+                    }
+                }
                 let callee_type = self.infer_expression(callee)?;
                 let arg_types: Result<Vec<_>, _> = args
                     .iter()
@@ -313,42 +659,101 @@ impl TypeChecker {
                 
                 match callee_type {
                     Type::Function(params, ret) => {
-                        let arg_types = arg_types?;
+                        let mut arg_types = arg_types?;
+                        // How many arguments a spread passes is only known at
+                        // runtime, so only the arguments before the first one
+                        // have a known parameter, and there's no arity check
+                        if let Some(spread) = args.iter().position(|arg| matches!(arg, Expression::Spread(_))) {
+                            let mut bindings = HashMap::new();
+                            for (param, arg) in params.iter().zip(&arg_types[..spread]) {
+                                unify(param, arg, &mut bindings)?;
+                            }
+                            return Ok(substitute(&ret, &bindings));
+                        }
+                        // Function types don't name their parameters, so named
+                        // arguments are only placed in calls of known functions
+                        if args.iter().any(|arg| matches!(arg, Expression::NamedArg { .. })) {
+                            let Some((name, param_names)) = self.function_params(callee) else {
+                                return Ok(*ret);
+                            };
+                            arg_types = place_named_arguments(name, param_names, args, arg_types)?;
+                        }
                         if params.len() != arg_types.len() {
                             return Err(TypeError::ArityMismatch {
                                 expected: params.len(),
                                 got: arg_types.len(),
                             });
                         }
-                        Ok(*ret)
+                        // Each call instantiates the callee's type parameters afresh
+                        let mut bindings = HashMap::new();
+                        for (param, arg) in params.iter().zip(&arg_types) {
+                            unify(param, arg, &mut bindings)?;
+                        }
+                        Ok(substitute(&ret, &bindings))
                     }
                     _ => Err(TypeError::Custom("Not a function".to_string())),
                 }
             }
-            Expression::Pipe { left, right } => {
+            Expression::Pipe { right, .. } => {
                 // For now, treat pipe as function call
                 self.infer_expression(right)
             }
             Expression::Match { expr, arms } => {
-                let _match_type = self.infer_expression(expr)?;
-                // Infer type from first arm
-                if let Some(first_arm) = arms.first() {
-                    self.infer_expression(&first_arm.expr)
-                } else {
-                    Ok(Type::Unit)
+                let mut match_type = self.infer_expression(expr)?;
+                // Literal and range patterns say what an untyped scrutinee must be
+                for arm in arms {
+                    let Some(pattern_type) = pattern_type(&arm.pattern) else {
+                        continue;
+                    };
+                    match_type = common_type(&match_type, &pattern_type)?;
                 }
+
+                // Check every arm with its pattern's bindings in scope; the
+                // match has the type the arms have in common
+                let mut result: Option<Type> = None;
+                for arm in arms {
+                    let previous = self.environment.clone();
+                    self.environment = TypeEnvironment::with_parent(self.environment.clone());
+                    self.bind_pattern(&arm.pattern, &match_type);
+                    let arm_type = self.infer_expression(&arm.expr);
+                    self.environment = previous;
+                    let arm_type = arm_type?;
+                    result = Some(match result {
+                        Some(ty) => common_type(&ty, &arm_type)?,
+                        None => arm_type,
+                    });
+                }
+                Ok(result.unwrap_or(Type::Unit))
             }
             Expression::Block(stmts) => {
                 let previous = self.environment.clone();
                 self.environment = TypeEnvironment::with_parent(self.environment.clone());
                 
+                // A block evaluates to its last statement: the value of an
+                // expression or return, and Unit for anything else
                 let mut result = Type::Unit;
-                for stmt in stmts {
-                    if let Statement::Expression(expr) = stmt {
-                        result = self.infer_expression(expr)?;
-                    } else {
-                        self.check_statement(stmt)?;
-                    }
+                for (i, stmt) in stmts.iter().enumerate() {
+                    result = match &stmt.kind {
+                        StatementKind::Expression(expr) => {
+                            let ty = self.infer_expression(expr);
+                            let ty = self.locate(stmt.span, ty)?;
+                            if i + 1 < stmts.len() {
+                                self.warn_if_discarded(expr, &ty, stmt.span);
+                            }
+                            ty
+                        }
+                        StatementKind::Return(Some(expr)) => {
+                            let ty = self.infer_expression(expr).and_then(|ty| {
+                                self.record_return(ty.clone())?;
+                                Ok(ty)
+                            });
+                            self.locate(stmt.span, ty)?
+                        }
+                        _ => {
+                            self.check_statement(stmt)?;
+                            Type::Unit
+                        }
+                    };
                 }
                 
                 self.environment = previous;
@@ -366,32 +771,30 @@ impl TypeChecker {
                 let then_type = self.infer_expression(then_branch)?;
                 if let Some(else_expr) = else_branch {
                     let else_type = self.infer_expression(else_expr)?;
-                    // For now, require exact match
-                    if then_type != else_type {
-                        return Err(TypeError::Mismatch {
-                            expected: then_type,
-                            got: else_type,
-                        });
-                    }
+                    return common_type(&then_type, &else_type);
                 }
                 
                 Ok(then_type)
             }
             Expression::FieldAccess { object, field } => {
                 let obj_type = self.infer_expression(object)?;
-                match obj_type {
+                match self.resolve(obj_type) {
                     Type::Record(fields) => {
-                        fields.get(field)
+                        fields.get(field.as_str())
                             .cloned()
                             .ok_or_else(|| TypeError::Custom(
                                 format!("Field '{}' not found", field)
                             ))
                     }
+                    // An untyped value, such as a `--json-input` record, is
+                    // only known to have the field at runtime
+                    Type::Variable(_) => Ok(Type::Variable(format!("field_{}", field))),
                     _ => Err(TypeError::Custom("Not a record".to_string())),
                 }
             }
             Expression::IndexAccess { object, index } => {
                 let obj_type = self.infer_expression(object)?;
+                let obj_type = self.resolve(obj_type);
                 let idx_type = self.infer_expression(index)?;
                 
                 if idx_type != Type::Int {
@@ -407,6 +810,24 @@ impl TypeChecker {
                     _ => Err(TypeError::Custom("Not indexable".to_string())),
                 }
             }
+            Expression::SliceAccess { object, start, end } => {
+                let obj_type = self.infer_expression(object)?;
+                let obj_type = self.resolve(obj_type);
+                for bound in [start, end].into_iter().flatten() {
+                    let bound_type = self.infer_expression(bound)?;
+                    if bound_type != Type::Int {
+                        return Err(TypeError::Mismatch {
+                            expected: Type::Int,
+                            got: bound_type,
+                        });
+                    }
+                }
+                
+                match obj_type {
+                    Type::List(_) | Type::String | Type::Variable(_) => Ok(obj_type),
+                    _ => Err(TypeError::Custom("Not sliceable".to_string())),
+                }
+            }
             Expression::Lambda { params, body } => {
                 // Create new scope
                 let previous = self.environment.clone();
@@ -423,14 +844,52 @@ impl TypeChecker {
                     param_types.push(param_type);
                 }
                 
-                let ret_type = self.infer_expression(body)?;
+                // A `return` in the body leaves the lambda, not the enclosing function
+                let outer_returns = self.returns.take();
+                let ret_type = self.infer_expression(body);
+                self.returns = outer_returns;
+                let ret_type = ret_type?;
                 
                 self.environment = previous;
                 Ok(Type::Function(param_types, Box::new(ret_type)))
             }
+            Expression::Partial { callee, args } => {
+                self.infer_expression(&Expression::partial_lambda((**callee).clone(), args.clone()))
+            }
             Expression::Claim(expr) => {
                 self.infer_expression(expr)
             }
+            Expression::NamedArg { value, .. } => self.infer_expression(value),
+            Expression::Cast { expr, target } => {
+                let from = self.infer_expression(expr)?;
+                let from = self.resolve(from);
+                let to = annotation_to_type(target, &self.environment)?;
+                // Casts convert at runtime by the target's name, which
+                // only built-in scalar types have there: not aliases,
+                // declared types or lists
+                let scalar = matches!(
+                    target,
+                    TypeAnnotation::Named(name) if matches!(name.as_str(), "Int" | "Float" | "Complex" | "String" | "Bool" | "Unit")
+                );
+                let castable = scalar && (from == to || matches!(
+                    (&from, &to),
+                    (Type::Variable(_) | Type::Error, _)
+                        | (Type::Int | Type::Float, Type::Int | Type::Float)
+                        | (Type::Int | Type::Float | Type::Complex, Type::String)
+                ));
+                if !castable {
+                    return Err(TypeError::InvalidOperation(format!("Cannot cast {} to {}", from, target)));
+                }
+                Ok(to)
+            }
+            Expression::Spread(expr) => {
+                let list_type = self.infer_expression(expr)?;
+                let list_type = self.resolve(list_type);
+                match list_type {
+                    Type::List(_) | Type::Variable(_) => Ok(list_type),
+                    _ => Err(TypeError::Custom(format!("Cannot spread {:?}", list_type))),
+                }
+            }
         }
     }
 
@@ -465,12 +924,43 @@ impl TypeChecker {
                     (Type::Int, Type::Int) => Ok(Type::Int),
                     (Type::Float, Type::Float) => Ok(Type::Float),
                     (Type::Int, Type::Float) | (Type::Float, Type::Int) => Ok(Type::Float),
+                    // Real numbers mix with complex ones as their real part
+                    (Type::Complex, Type::Int | Type::Float | Type::Complex | Type::Variable(_))
+                    | (Type::Int | Type::Float | Type::Variable(_), Type::Complex) if *op != BinaryOp::Modulo => {
+                        Ok(Type::Complex)
+                    }
                     (Type::String, Type::String) if *op == BinaryOp::Add => Ok(Type::String),
+                    // Repetition: "ab" * 3, [0] * 3 and the count-first forms
+                    (Type::String, Type::Int) | (Type::Int, Type::String) if *op == BinaryOp::Multiply => {
+                        Ok(Type::String)
+                    }
+                    (Type::List(elem), Type::Int) | (Type::Int, Type::List(elem)) if *op == BinaryOp::Multiply => {
+                        Ok(Type::List(elem.clone()))
+                    }
                     // Allow operations with type variables (for polymorphic functions)
                     (Type::Variable(_), Type::Int) | (Type::Int, Type::Variable(_)) => Ok(Type::Int),
                     (Type::Variable(_), Type::Float) | (Type::Float, Type::Variable(_)) => Ok(Type::Float),
                     (Type::Variable(_), Type::String) | (Type::String, Type::Variable(_)) if *op == BinaryOp::Add => Ok(Type::String),
                     (Type::Variable(_), Type::Variable(_)) => Ok(Type::Variable("result".to_string())),
+                    // Records overload `+`/`-` with `__add__`/`__sub__` methods
+                    (Type::Record(fields), Type::Record(_)) => {
+                        let hook = match op {
+                            BinaryOp::Add => fields.get("__add__"),
+                            BinaryOp::Subtract => fields.get("__sub__"),
+                            _ => None,
+                        };
+                        match hook {
+                            Some(Type::Function(_, ret)) => Ok(ret.as_ref().clone()),
+                            // Record literals aren't typed field by field yet,
+                            // so their methods are only checked at runtime
+                            None if fields.is_empty() && matches!(op, BinaryOp::Add | BinaryOp::Subtract) => {
+                                Ok(Type::Variable("result".to_string()))
+                            }
+                            _ => Err(TypeError::InvalidOperation(
+                                format!("Cannot {:?} {:?} and {:?}", op, left, right)
+                            )),
+                        }
+                    }
                     _ => Err(TypeError::InvalidOperation(
                         format!("Cannot {:?} {:?} and {:?}", op, left, right)
                     )),
@@ -479,6 +969,13 @@ impl TypeChecker {
             BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEq | BinaryOp::Greater | BinaryOp::GreaterEq => {
                 Ok(Type::Bool)
             }
+            BinaryOp::In => match (left, right) {
+                (_, Type::List(_)) | (_, Type::Variable(_)) => Ok(Type::Bool),
+                (Type::String, Type::Record(_)) | (Type::Variable(_), Type::Record(_)) => Ok(Type::Bool),
+                _ => Err(TypeError::InvalidOperation(
+                    format!("Cannot test membership of {:?} in {:?}", left, right)
+                )),
+            },
         }
     }
 
@@ -502,11 +999,225 @@ impl TypeChecker {
     fn is_compatible(&self, inferred: &Type, annotated: &Type) -> bool {
         match (inferred, annotated) {
             (Type::Int, Type::Float) => true, // Int can be used where Float expected
+            (Type::Never, _) => true, // Never produces a value to mismatch
+            (Type::Named(_), _) | (_, Type::Named(_)) => {
+                let (inferred, annotated) = (self.resolve(inferred.clone()), self.resolve(annotated.clone()));
+                if matches!(inferred, Type::Named(_)) || matches!(annotated, Type::Named(_)) {
+                    inferred == annotated
+                } else {
+                    self.is_compatible(&inferred, &annotated)
+                }
+            }
             (a, b) => a == b,
         }
     }
 }
 
+/// Whether an expression statement throws away a value that was probably
+/// meant to be used. Calls may be run for their effects, and `if`, `match`
+/// and blocks for the calls inside them, so only other expressions count.
+fn discards_value(expr: &Expression, ty: &Type) -> bool {
+    let effectful = matches!(
+        expr,
+        Expression::Call { .. } | Expression::Pipe { .. } | Expression::If { .. }
+            | Expression::Match { .. } | Expression::Block(_) | Expression::Claim(_)
+    );
+    !effectful && !matches!(ty, Type::Unit | Type::Variable(_) | Type::Error)
+}
+
+/// Put the types of a call's named arguments in the places of the
+/// parameters they name, after the positional ones, as the interpreter does
+fn place_named_arguments(
+    function: &str,
+    param_names: &[String],
+    args: &[Expression],
+    arg_types: Vec<Type>,
+) -> Result<Vec<Type>, TypeError> {
+    let mut slots: Vec<Option<Type>> = Vec::new();
+    for (arg, ty) in args.iter().zip(arg_types) {
+        let Expression::NamedArg { name, .. } = arg else {
+            slots.push(Some(ty));
+            continue;
+        };
+        let Some(index) = param_names.iter().position(|param| param == name) else {
+            return Err(TypeError::Custom(format!("{} has no parameter called {}", function, name)));
+        };
+        if slots.len() <= index {
+            slots.resize(index + 1, None);
+        }
+        if slots[index].replace(ty).is_some() {
+            return Err(TypeError::Custom(format!("{} is given argument {} twice", function, name)));
+        }
+    }
+    if slots.len() > param_names.len() {
+        return Err(TypeError::ArityMismatch { expected: param_names.len(), got: slots.len() });
+    }
+    slots.resize(param_names.len(), None);
+    slots.into_iter()
+        .zip(param_names)
+        .map(|(slot, param)| slot.ok_or_else(|| {
+            TypeError::Custom(format!("{} is missing argument {}", function, param))
+        }))
+        .collect()
+}
+
+/// The type both `a` and `b` can be used as: Int widens to Float, real
+/// numbers widen to Complex, and a type variable or Never takes on the
+/// other side's type
+fn common_type(a: &Type, b: &Type) -> Result<Type, TypeError> {
+    match (a, b) {
+        _ if a == b => Ok(a.clone()),
+        (Type::Never, ty) | (ty, Type::Never) => Ok(ty.clone()),
+        (Type::Int, Type::Float) | (Type::Float, Type::Int) => Ok(Type::Float),
+        (Type::Int | Type::Float, Type::Complex) | (Type::Complex, Type::Int | Type::Float) => Ok(Type::Complex),
+        (Type::Variable(_), ty) | (ty, Type::Variable(_)) => Ok(ty.clone()),
+        (Type::List(a), Type::List(b)) => Ok(Type::List(Box::new(common_type(a, b)?))),
+        _ => Err(TypeError::Mismatch { expected: a.clone(), got: b.clone() }),
+    }
+}
+
+/// Bind the generic type parameters in `param` to the matching parts of
+/// `arg`, failing if a parameter was already bound to a different type
+fn unify(param: &Type, arg: &Type, bindings: &mut HashMap<String, Type>) -> Result<(), TypeError> {
+    match (param, arg) {
+        (Type::Generic(name), _) => {
+            match bindings.get(name) {
+                // Type variables are unknown and Never fits anything, so
+                // neither conflicts
+                Some(_) if matches!(arg, Type::Variable(_) | Type::Never) => {}
+                Some(bound) if !matches!(bound, Type::Variable(_) | Type::Never) && bound != arg => {
+                    return Err(TypeError::Mismatch { expected: bound.clone(), got: arg.clone() });
+                }
+                _ => {
+                    bindings.insert(name.clone(), arg.clone());
+                }
+            }
+            Ok(())
+        }
+        (Type::List(param), Type::List(arg)) => unify(param, arg, bindings),
+        (Type::Function(params, param_ret), Type::Function(args, arg_ret)) => {
+            for (param, arg) in params.iter().zip(args) {
+                unify(param, arg, bindings)?;
+            }
+            unify(param_ret, arg_ret, bindings)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replace the bound generic type parameters in `ty`
+fn substitute(ty: &Type, bindings: &HashMap<String, Type>) -> Type {
+    match ty {
+        Type::Generic(name) => bindings.get(name).cloned().unwrap_or_else(|| ty.clone()),
+        Type::List(elem) => Type::List(Box::new(substitute(elem, bindings))),
+        Type::Function(params, ret) => Type::Function(
+            params.iter().map(|param| substitute(param, bindings)).collect(),
+            Box::new(substitute(ret, bindings)),
+        ),
+        _ => ty.clone(),
+    }
+}
+
+/// Type of a built-in function, if `name` is one
+fn builtin_type(name: &str) -> Option<Type> {
+    let var = |name: &str| Type::Variable(name.to_string());
+    let list = |ty: Type| Type::List(Box::new(ty));
+    let func = |params: Vec<Type>, ret: Type| Type::Function(params, Box::new(ret));
+
+    let ty = match name {
+        // print, log and call take any number of arguments, so calls to them
+        // are checked specially; these types only describe them as values
+        "print" | "log" => func(vec![var("args")], Type::Unit),
+        "call" => func(vec![var("function"), var("args")], var("result")),
+        "apply" => func(vec![var("function"), list(var("args"))], var("result")),
+        "len" => func(vec![var("collection")], Type::Int),
+        // Calls to push are checked against the list's element type
+        "push" => func(vec![list(var("a")), var("a")], list(var("a"))),
+        "error" => func(vec![Type::String], Type::Never),
+        "range" => func(vec![Type::Int, Type::Int], list(Type::Int)),
+        "sqrt" => func(vec![Type::Float], Type::Float),
+        "repeat" => func(vec![var("a"), Type::Int], list(var("a"))),
+        "fill" => func(vec![Type::Int, func(vec![Type::Int], var("a"))], list(var("a"))),
+        "zip_with" => func(vec![list(var("a")), list(var("b")), func(vec![var("a"), var("b")], var("c"))], list(var("c"))),
+        // The elements of each triple may differ in type, so it's untyped
+        "zip3" => func(vec![list(var("a")), list(var("b")), list(var("c"))], list(var("triple"))),
+        "flat_map" => func(vec![list(var("a")), func(vec![var("a")], list(var("b")))], list(var("b"))),
+        "fold_right" => func(vec![list(var("a")), var("b"), func(vec![var("a"), var("b")], var("b"))], var("b")),
+        "scan" => func(vec![list(var("a")), var("b"), func(vec![var("b"), var("a")], var("b"))], list(var("b"))),
+        // Group keys are only known at runtime, so the record is untyped
+        "group_by" => func(vec![list(var("a")), func(vec![var("a")], var("key"))], var("groups")),
+        "min_by" | "max_by" => func(vec![list(var("a")), func(vec![var("a")], var("key"))], var("a")),
+        "partition" => func(vec![list(var("a")), func(vec![var("a")], Type::Bool)], list(list(var("a")))),
+        "take" | "drop" => func(vec![list(var("a")), Type::Int], list(var("a"))),
+        "chunk" => func(vec![list(var("a")), Type::Int], list(list(var("a")))),
+        "flatten" => func(vec![list(list(var("a")))], list(var("a"))),
+        "transpose" => func(vec![list(list(var("a")))], list(list(var("a")))),
+        // contains works on a String (substring) or a List (element)
+        "contains" => func(vec![var("haystack"), var("needle")], Type::Bool),
+        "starts_with" | "ends_with" => func(vec![Type::String, Type::String], Type::Bool),
+        "pad_left" | "pad_right" | "center" => func(vec![Type::String, Type::Int, Type::String], Type::String),
+        "index" | "rindex" => func(vec![Type::String, Type::String], Type::Int),
+        "char_at" => func(vec![Type::String, Type::Int], Type::String),
+        "substring" => func(vec![Type::String, Type::Int, Type::Int], Type::String),
+        "split_lines" | "read_lines" => func(vec![Type::String], list(Type::String)),
+        "stdin" => func(vec![], Type::String),
+        "stdin_lines" => func(vec![], list(Type::String)),
+        "sort" => func(vec![list(var("a"))], list(var("a"))),
+        "to_int" => func(vec![var("value")], Type::Int),
+        "to_float" => func(vec![var("value")], Type::Float),
+        "map_values" => func(vec![var("record"), func(vec![var("a")], var("b"))], var("record")),
+        "filter_entries" => func(vec![var("record"), func(vec![Type::String, var("a")], Type::Bool)], var("record")),
+        "random" => func(vec![], Type::Float),
+        "env" => func(vec![Type::String], Type::String),
+        "random_int" => func(vec![Type::Int, Type::Int], Type::Int),
+        "complex" => func(vec![Type::Float, Type::Float], Type::Complex),
+        "abs_diff" | "gcd" | "lcm" => func(vec![Type::Int, Type::Int], Type::Int),
+        // Instants are opaque, so the checker leaves them untyped
+        "now" => func(vec![], var("instant")),
+        "elapsed" => func(vec![var("instant")], Type::Float),
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// The type of value a literal or range pattern can match
+fn pattern_type(pattern: &Pattern) -> Option<Type> {
+    match pattern {
+        Pattern::Literal(Literal::Integer(_)) => Some(Type::Int),
+        Pattern::Literal(Literal::Float(_)) => Some(Type::Float),
+        Pattern::Literal(Literal::String(_)) => Some(Type::String),
+        Pattern::Literal(Literal::Boolean(_)) => Some(Type::Bool),
+        Pattern::Range(low, _) => pattern_type(low),
+        _ => None,
+    }
+}
+
+/// Whether an expression is the literal `0` or `0.0`
+fn is_literal_zero(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(Literal::Integer(n)) => *n == 0,
+        Expression::Literal(Literal::Float(f)) => *f == 0.0,
+        _ => false,
+    }
+}
+
+/// Type of a built-in module, as a record of its exported members
+fn module_type(name: &str) -> Option<Type> {
+    let float_fn = |arity: usize| Type::Function(vec![Type::Float; arity], Box::new(Type::Float));
+    let members: Vec<(&str, Type)> = match name {
+        "math" => vec![
+            ("sqrt", float_fn(1)),
+            ("floor", float_fn(1)),
+            ("ceil", float_fn(1)),
+            ("abs", Type::Function(vec![Type::Variable("n".to_string())], Box::new(Type::Variable("n".to_string())))),
+            ("pow", float_fn(2)),
+            ("pi", Type::Float),
+        ],
+        _ => return None,
+    };
+    Some(Type::Record(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect()))
+}
+
 /// Validate a value against Ghost type constraints (runtime validation in proto mode)
 pub fn validate_ghost_type(value: &Value, ghost_attrs: &[GhostAttribute]) -> Result<(), TypeError> {
     for attr in ghost_attrs {
@@ -577,4 +1288,740 @@ impl Default for TypeChecker {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn check_source(source: &str) -> Result<(), Vec<SpannedTypeError>> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let module = parser.parse().unwrap();
+
+        let mut checker = TypeChecker::new();
+        checker.check_module(&module)
+    }
+
+    #[test]
+    fn test_import_alias() {
+        let source = r#"
+            import math as m
+            proto main() {
+                let root = m.sqrt(16.0)
+                return root
+            }
+        "#;
+
+        assert!(check_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_assignment() {
+        let source = r#"
+            proto main() {
+                var total = 0
+                var xs = [1, 2]
+                for x in xs {
+                    total = total + x
+                }
+                xs[0] = total
+                return xs
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let errors = check_source("proto main() {\n var x = 1\n x = \"one\"\n}").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::String });
+        let errors = check_source("let limit = 3\nproto main() {\n limit = 4\n}").unwrap_err();
+        assert_eq!(
+            errors[0].error,
+            TypeError::InvalidOperation("Cannot assign to limit, which is declared with let".to_string())
+        );
+        let errors = check_source("proto main() {\n let xs = [1]\n xs[0] = 2\n}").unwrap_err();
+        assert_eq!(errors[0].span, Some(Span::new(3, 2)));
+
+        // A `var` of the same name hides the `let`
+        assert!(check_source("let x = 1\nproto main() {\n var x = 2\n x = 3\n}").is_ok());
+    }
+
+    #[test]
+    fn test_spread_arguments() {
+        let check = |call: &str| check_source(&format!(
+            "proto pair<T>(first: T, second: T) -> List<T> {{ return [first, second] }}\nproto main() {{ let rest = [2]\nreturn {} }}",
+            call,
+        ));
+
+        assert!(check("pair(...rest)").is_ok());
+        assert!(check("pair(1, ...rest)").is_ok());
+        assert!(check("pair(...rest, \"two\")").is_ok());
+
+        // The arguments before a spread still bind type parameters
+        let source = r#"
+            proto pair<T>(first: T, second: T) -> List<T> { return [first, second] }
+            proto main() {
+                let rest = [2]
+                let xs: List<String> = pair(1, ...rest)
+                return xs
+            }
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch {
+            expected: Type::List(Box::new(Type::String)),
+            got: Type::List(Box::new(Type::Int)),
+        });
+        let errors = check("pair(1, \"two\", ...rest)").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::String });
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let check = |call: &str| check_source(&format!(
+            "proto pair<T>(first: T, second: T) -> List<T> {{ return [first, second] }}\nproto main() {{ return {} }}",
+            call,
+        ));
+
+        assert!(check("pair(1, second: 2)").is_ok());
+        assert!(check("pair(second: 2, first: 1)").is_ok());
+
+        // Named arguments are unified with the parameters they name, and
+        // the positional ones as usual
+        let errors = check("pair(1, second: \"two\")").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::String });
+        let errors = check("pair(second: \"two\", first: 1)").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::String });
+        let errors = check("pair(1, third: 3)").unwrap_err();
+        assert_eq!(errors[0].error.to_string(), "pair has no parameter called third");
+        let errors = check("pair(second: 2)").unwrap_err();
+        assert_eq!(errors[0].error.to_string(), "pair is missing argument first");
+
+        // A local binding hides the function's parameter names
+        let source = r#"
+            proto pair<T>(first: T, second: T) -> List<T> { return [first, second] }
+            proto main() {
+                let pair = (a, b) => a
+                return pair(1, b: "two")
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_block_ending_in_for_is_unit() {
+        let source = r#"
+            proto main() {
+                let xs = [1, 2, 3]
+                let x: Unit = {
+                    for i in xs {
+                        log(i)
+                    }
+                }
+                let y: Int = {
+                    for i in xs {
+                        log(i)
+                    }
+                }
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::Unit });
+    }
+
+    #[test]
+    fn test_block_ending_in_expression() {
+        let source = r#"
+            proto main() {
+                let x: Int = {
+                    let a = 1
+                    a + 1
+                }
+                let y: Unit = {
+                    let b = x
+                }
+                return x
+            }
+        "#;
+
+        assert!(check_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_list_constructors() {
+        let source = r#"
+            proto main() {
+                let zeros = repeat(0, 3)
+                let squares = fill(3, i => i * i)
+                return len(zeros) + len(squares)
+            }
+        "#;
+
+        assert!(check_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_sequence_repetition() {
+        let source = r#"
+            proto main() {
+                let rule: String = "-" * 20
+                let zeros = [0] * 3
+                return len(rule) + len(zeros)
+            }
+        "#;
+
+        assert!(check_source(source).is_ok());
+
+        let errors = check_source("proto main() { return \"ab\" - 3 }").unwrap_err();
+        assert!(matches!(errors[0].error, TypeError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_record_operator_hooks() {
+        let source = r#"
+            proto main() {
+                let add = (a, b) => a
+                let v = { x: 1, __add__: add }
+                return v + v
+            }
+        "#;
+
+        assert!(check_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_module() {
+        let source = r#"
+            import nope
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors, vec![SpannedTypeError {
+            error: TypeError::Custom("Unknown module: nope".to_string()),
+            span: None,
+        }]);
+    }
+
+    #[test]
+    fn test_solve_objective_must_be_numeric() {
+        let source = r#"
+            solve pick() {
+                let n in range(0, 3)
+                ensure n > 0
+                minimize n * 2
+                return n
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let source = r#"
+            solve pick() {
+                let n in range(0, 3)
+                maximize n > 1
+                return n
+            }
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert!(matches!(errors[0].error, TypeError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_literal_zero_divisor() {
+        for source in [
+            "proto main() { return 5 / 0 }",
+            "proto main() { return 5 % 0 }",
+            "proto main() { return 5.0 / 0.0 }",
+        ] {
+            let errors = check_source(source).unwrap_err();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].error, TypeError::InvalidOperation("division by zero".to_string()));
+        }
+
+        // Only literal divisors are known to be zero before running
+        let source = r#"
+            proto main() {
+                let d = 0
+                return 5 / d
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_error_reports_statement_line() {
+        let source = r#"
+            proto main() {
+                let ok = 1
+                let bad: Int = "text"
+                return ok
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Some(Span::new(4, 17)));
+        assert!(errors[0].to_string().starts_with("line 4, column 17: [E0001] Type mismatch"));
+
+        // Errors inside a nested block point at the inner statement
+        let source = r#"
+            proto main() {
+                let total = {
+                    let a = 1
+                    a + "b"
+                }
+                return total
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].span.map(|span| span.line), Some(5));
+    }
+
+    #[test]
+    fn test_in_operator() {
+        let source = r#"
+            proto main(xs: List<Int>) {
+                let found: Bool = 2 in xs
+                let has_key: Bool = "x" in { x: 1 }
+                return found
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let errors = check_source("proto main() { return 1 in 2 }").unwrap_err();
+        assert!(matches!(errors[0].error, TypeError::InvalidOperation(_)));
+
+        let errors = check_source("proto main() { return 1 in { x: 1 } }").unwrap_err();
+        assert!(matches!(errors[0].error, TypeError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_field_access_on_untyped_parameter() {
+        let source = r#"
+            proto main(config) {
+                return config.retries + 1
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let errors = check_source("proto main() { return 1.retries }").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Custom("Not a record".to_string()));
+    }
+
+    #[test]
+    fn test_generic_identity() {
+        let source = r#"
+            proto id<T>(x: T) -> T {
+                return x
+            }
+
+            proto main() {
+                let n: Int = id(1)
+                let s: String = id("one")
+                let f: Float = id(n) + 0.5
+                return s
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let source = r#"
+            proto id<T>(x: T) -> T {
+                return x
+            }
+
+            proto main() {
+                let s: String = id(1)
+                return s
+            }
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::String, got: Type::Int });
+    }
+
+    #[test]
+    fn test_generic_pair_swap() {
+        let source = r#"
+            proto swap<T>(pair: List<T>) -> List<T> {
+                return [pair[1], pair[0]]
+            }
+
+            proto first<A, B>(a: A, b: B) -> A {
+                return a
+            }
+
+            proto main(names: List<String>) {
+                let swapped: List<String> = swap(names)
+                let head: String = first(swapped[0], 2)
+                return head
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        // Both arguments must agree on a shared type parameter
+        let source = r#"
+            proto pair<T>(a: T, b: T) -> List<T> {
+                return [a, b]
+            }
+
+            proto main() {
+                return pair(1, "two")
+            }
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::String });
+    }
+
+    #[test]
+    fn test_returns_widen_to_float() {
+        let source = r#"
+            proto pick(flag: Bool) {
+                if flag {
+                    return 1
+                } else {
+                    return 2.0
+                }
+            }
+
+            proto main() {
+                let x: Float = pick(true)
+                let y: Int = pick(false)
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::Float });
+        assert_eq!(errors[0].span, Some(Span::new(12, 17)));
+    }
+
+    #[test]
+    fn test_incompatible_returns() {
+        let source = r#"
+            proto describe(n: Int) {
+                if n > 0 {
+                    return n
+                } else {
+                    return "none"
+                }
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::String });
+        assert_eq!(errors[0].span, Some(Span::new(6, 21)));
+    }
+
+    #[test]
+    fn test_complex_arithmetic() {
+        let source = r#"
+            proto main() {
+                let z: Complex = complex(1.0, 2.0) * 2 + 0.5
+                let w: Complex = z / z
+                let r = z % 2
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Some(Span::new(5, 17)));
+    }
+
+    #[test]
+    fn test_variadic_builtins() {
+        let source = r#"
+            proto add(a: Int, b: Int) -> Int { return a + b }
+
+            proto main() {
+                log("sum", call(add, 1, 2))
+                let total: Int = call(add, 3, 4)
+                let wrong = call(add, 1)
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, TypeError::ArityMismatch { expected: 2, got: 1 });
+        assert_eq!(errors[0].span, Some(Span::new(7, 17)));
+    }
+
+    #[test]
+    fn test_match_binds_scrutinee_type() {
+        let source = r#"
+            proto describe(n: Int) -> String {
+                return match n {
+                    0 => "zero",
+                    1..9 => "small",
+                    other => other + "!"
+                }
+            }
+
+            proto bump(x) {
+                let next: Int = match x {
+                    0 => 1,
+                    n => n + 1
+                }
+                return next
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].error,
+            TypeError::InvalidOperation("Cannot Add Int and String".to_string()),
+        );
+        assert_eq!(errors[0].span, Some(Span::new(3, 17)));
+    }
+
+    #[test]
+    fn test_match_pattern_must_fit_scrutinee() {
+        let source = r#"
+            proto main() {
+                let word = "one"
+                let n = match word {
+                    "one" => 1,
+                    2 => 2,
+                    _ => 0
+                }
+            }
+        "#;
+
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::String, got: Type::Int });
+    }
+
+    #[test]
+    fn test_discarded_value_warning() {
+        let source = r#"
+            solid main() {
+                let x = 1
+                x + 1
+                log(x)
+                len([x])
+                return x
+            }
+        "#;
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(lexer.tokenize().unwrap());
+        let module = parser.parse().unwrap();
+
+        let mut checker = TypeChecker::new();
+        assert!(checker.check_module(&module).is_ok());
+        assert_eq!(checker.warnings().len(), 1);
+        assert_eq!(checker.warnings()[0].message, "result of type Int is discarded");
+        assert_eq!(checker.warnings()[0].span, Span::new(4, 17));
+    }
+
+    #[test]
+    fn test_discarded_value_warning_names_type() {
+        let source = r#"
+            solid main() {
+                (a: Int, b: String) => a
+                return 0
+            }
+        "#;
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(lexer.tokenize().unwrap());
+        let module = parser.parse().unwrap();
+
+        let mut checker = TypeChecker::new();
+        assert!(checker.check_module(&module).is_ok());
+        assert_eq!(checker.warnings()[0].message, "result of type (Int, String) -> Int is discarded");
+    }
+
+    #[test]
+    fn test_discarded_value_warning_scope() {
+        let warnings = |source: &str| {
+            let mut lexer = Lexer::new(source);
+            let mut parser = Parser::new(lexer.tokenize().unwrap());
+            let module = parser.parse().unwrap();
+            let mut checker = TypeChecker::new();
+            assert!(checker.check_module(&module).is_ok());
+            // Checking again starts from no warnings
+            assert!(checker.check_module(&module).is_ok());
+            checker.warnings().iter().map(|w| w.span.line).collect::<Vec<_>>()
+        };
+
+        // The last statement of a body is its value
+        let source = r#"
+            solid double(n: Int) -> Int { n * 2 }
+            solid main() {
+                let f = (n: Int) => { n + 1
+                    n * 2 }
+                let y = { 1
+                    2 }
+                f(y)
+            }
+        "#;
+        assert_eq!(warnings(source), vec![4, 6]);
+
+        // Only solid code is warned about
+        let source = r#"
+            proto main() {
+                let x = 1
+                x + 1
+                return x
+            }
+        "#;
+        assert!(warnings(source).is_empty());
+    }
+
+    #[test]
+    fn test_never_fits_any_type() {
+        let source = r#"
+            proto fail(message) {
+                return error(message)
+            }
+
+            proto checked(n: Int) {
+                let doubled: Int = if n > 0 { n * 2 } else { error("negative") }
+                let label: String = fail("unreachable")
+                return doubled
+            }
+
+            proto main() {
+                let x: Int = error("no")
+                let y: Float = fail("no")
+                return checked(x) + y
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+    }
+
+    #[test]
+    fn test_annotation_types_list_literals() {
+        let source = r#"
+            proto main() {
+                let xs: List<Int> = []
+                let ys: List<Float> = [1, 2.5]
+                let zs: List<Float> = push(ys, 3)
+                let first: Int = push(xs, 1)[0]
+                return zs
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let source = r#"
+            proto main() {
+                let xs: List<Int> = []
+                return push(xs, "three")
+            }
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Int, got: Type::String });
+
+        let source = r#"
+            proto main() {
+                let xs: List<Int> = [1, "two"]
+                return xs
+            }
+        "#;
+        assert!(check_source(source).is_err());
+    }
+
+    #[test]
+    fn test_inferred_types() {
+        let source = r#"
+            let limit = 10
+
+            proto add(a: Int, b: Int) -> Int {
+                return a + b
+            }
+
+            proto main() {
+                let total = add(1, limit)
+                let ratio = total * 1.5
+                return ratio
+            }
+        "#;
+
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(lexer.tokenize().unwrap());
+        let module = parser.parse().unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check_module(&module).unwrap();
+
+        let inferred: Vec<String> = checker.inferred_types().iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect();
+        assert_eq!(inferred, vec![
+            "limit: Int",
+            "add: (Int, Int) -> Int",
+            "main: () -> Float",
+            "main::total: Int",
+            "main::ratio: Float",
+        ]);
+    }
+
+    #[test]
+    fn test_cast_types() {
+        let source = r#"
+            proto average(total: Int, count: Int) -> Float {
+                return total as Float / count as Float
+            }
+
+            proto label(x: Float) -> String {
+                return x as String
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let source = r#"
+            proto main() {
+                let n: Int = 2.5 as Int
+                let s: Float = n as String
+                return s
+            }
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::Float, got: Type::String });
+
+        let errors = check_source("proto main() { return \"7\" as Int }").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::InvalidOperation("Cannot cast String to Int".to_string()));
+
+        // A cast to a value's own type is allowed, but not to an alias of
+        // it, which the runtime can't see
+        assert!(check_source("proto main() { return true as Bool }").is_ok());
+        let errors = check_source("type Meters = Float\nproto main() { return 3 as Meters }").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::InvalidOperation("Cannot cast Int to Meters".to_string()));
+        let errors = check_source("type Meters = Float\nproto main() { return 3.0 as Meters }").unwrap_err();
+        assert_eq!(errors[0].error, TypeError::InvalidOperation("Cannot cast Float to Meters".to_string()));
+    }
+
+    #[test]
+    fn test_recursive_types() {
+        let source = r#"
+            type Tree = { value: Int, children: Forest }
+            type Forest = List<Tree>
+
+            proto first_child(t: Tree) -> Int {
+                let child: Tree = t.children[0]
+                return child.value + child.children[0].value
+            }
+
+            proto main() {
+                return 0
+            }
+        "#;
+        assert!(check_source(source).is_ok());
+
+        let source = r#"
+            type Tree = { value: Int, children: List<Tree> }
+
+            proto label(t: Tree) -> String {
+                let name: String = t.children[0].value
+                return name
+            }
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Mismatch { expected: Type::String, got: Type::Int });
+
+        let source = r#"
+            type A = B
+            type B = A
+        "#;
+        let errors = check_source(source).unwrap_err();
+        assert_eq!(errors[0].error, TypeError::Custom("Type B is defined as itself".to_string()));
+    }
+}
@@ -0,0 +1,103 @@
+//! Long-form explanations of type error codes for `mrc check --explain`
+
+/// Each code with its explanation, in code order
+const EXPLANATIONS: &[(&str, &str)] = &[
+    ("E0001", "\
+A value has a different type from the one required where it is used, such
+as an annotated variable initialised with a value of another type.
+
+    let count: Int = \"three\"
+
+Change the value, or the annotation, so that the two agree. Int converts to
+Float, and Int or Float to Complex, without an explicit conversion."),
+    ("E0002", "\
+A name is used that isn't defined in any enclosing scope, either as a
+variable, a parameter, a function or a builtin.
+
+    proto main() {
+        return totl + 1
+    }
+
+Check the spelling, and that the variable is declared before it is used."),
+    ("E0003", "\
+A type annotation names a type that doesn't exist.
+
+    let name: Strin = \"Ada\"
+
+The built-in types are Int, Float, Complex, String, Bool, Unit and Never;
+any other name needs a `type` declaration."),
+    ("E0004", "\
+A function is called with a different number of arguments from the number
+of parameters it declares.
+
+    proto add(a, b) {
+        return a + b
+    }
+
+    proto main() {
+        return add(1, 2, 3)
+    }
+
+Pass exactly one argument per parameter. Arguments spread with `...` are
+only counted when the program runs."),
+    ("E0005", "\
+An operator or construct is applied to values it doesn't support, such as
+negating a string, or dividing by a literal zero.
+
+    proto main() {
+        return 10 / 0
+    }
+
+The message says which operation failed and the types involved."),
+    ("E0006", "\
+A value doesn't satisfy the constraints of a Ghost type, such as a string
+that doesn't match its `Regex` pattern or a number outside `Min`/`Max`.
+
+    type Email = String <Ghost: \"Regex\", pattern: \"^.+@.+$\">
+
+The check only runs in proto mode; hardened code trusts the constraint."),
+    ("E0007", "\
+A type error with no more specific code, such as calling a value that
+isn't a function or importing a module that doesn't exist.
+
+    proto main() {
+        let x = 5
+        return x(1)
+    }
+
+The message describes the problem."),
+];
+
+/// The explanation of an error code like `E0001`, or `None` if there is
+/// no such code
+pub fn explanation(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code))
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Type, TypeError};
+
+    #[test]
+    fn test_every_code_is_explained() {
+        let errors = [
+            TypeError::Mismatch { expected: Type::Int, got: Type::String },
+            TypeError::UndefinedVariable("x".to_string()),
+            TypeError::UndefinedType("T".to_string()),
+            TypeError::ArityMismatch { expected: 2, got: 3 },
+            TypeError::InvalidOperation("division by zero".to_string()),
+            TypeError::GhostValidationFailed { type_name: "Int".to_string(), reason: "too big".to_string() },
+            TypeError::Custom("Not a function".to_string()),
+        ];
+        for error in &errors {
+            assert!(explanation(error.code()).is_some(), "no explanation for {}", error.code());
+        }
+
+        assert_eq!(errors[0].code(), "E0001");
+        assert!(explanation("e0001").unwrap().contains("let count: Int = \"three\""));
+        assert_eq!(explanation("E9999"), None);
+    }
+}
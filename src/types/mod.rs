@@ -1,7 +1,8 @@
 use crate::ast::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub mod checker;
+pub mod explain;
 
 pub use checker::{TypeChecker, validate_ghost_type};
 
@@ -12,12 +13,17 @@ pub enum Type {
     Int,
     /// Floating point type
     Float,
+    /// Complex number type
+    Complex,
     /// String type
     String,
     /// Boolean type
     Bool,
     /// Unit/void type
     Unit,
+    /// Type of expressions that never produce a value, such as `error(...)`;
+    /// usable wherever any type is expected
+    Never,
     /// List of elements of a specific type
     List(Box<Type>),
     /// Record with named fields
@@ -26,6 +32,10 @@ pub enum Type {
     Function(Vec<Type>, Box<Type>),
     /// Generic type parameter
     Generic(String),
+    /// A declared type, by name. Stands in for a type inside its own
+    /// definition, or inside one declared before it, so types can refer
+    /// to themselves and to each other
+    Named(String),
     /// Ghost type with metadata (validation in proto, stripped in solid)
     Ghost(Box<Type>, Vec<GhostAttribute>),
     /// Type variable for inference
@@ -34,6 +44,37 @@ pub enum Type {
     Error,
 }
 
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::Complex => write!(f, "Complex"),
+            Type::String => write!(f, "String"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Unit => write!(f, "Unit"),
+            Type::Never => write!(f, "Never"),
+            Type::List(elem) => write!(f, "List<{}>", elem),
+            Type::Record(fields) => {
+                // Fields are unordered, so sort them for a stable rendering
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                let fields: Vec<String> = fields.iter().map(|(name, ty)| format!("{}: {}", name, ty)).collect();
+                write!(f, "{{ {} }}", fields.join(", "))
+            }
+            Type::Function(params, ret) => {
+                let params: Vec<String> = params.iter().map(Type::to_string).collect();
+                write!(f, "({}) -> {}", params.join(", "), ret)
+            }
+            Type::Generic(name) | Type::Named(name) => write!(f, "{}", name),
+            Type::Ghost(inner, _) => write!(f, "{}", inner),
+            // Not yet known, so marked apart from named types
+            Type::Variable(name) => write!(f, "?{}", name),
+            Type::Error => write!(f, "<error>"),
+        }
+    }
+}
+
 /// Ghost type attributes for validation and optimization hints
 #[derive(Debug, Clone, PartialEq)]
 pub struct GhostAttribute {
@@ -61,6 +102,21 @@ pub enum TypeError {
     Custom(String),
 }
 
+impl TypeError {
+    /// Stable code identifying the kind of error, explained by `mrc check --explain`
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeError::Mismatch { .. } => "E0001",
+            TypeError::UndefinedVariable(_) => "E0002",
+            TypeError::UndefinedType(_) => "E0003",
+            TypeError::ArityMismatch { .. } => "E0004",
+            TypeError::InvalidOperation(_) => "E0005",
+            TypeError::GhostValidationFailed { .. } => "E0006",
+            TypeError::Custom(_) => "E0007",
+        }
+    }
+}
+
 impl std::fmt::Display for TypeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -83,10 +139,44 @@ impl std::fmt::Display for TypeError {
 
 impl std::error::Error for TypeError {}
 
+/// A type error and the location of the statement it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedTypeError {
+    pub error: TypeError,
+    /// `None` for errors outside any statement (imports, signatures)
+    pub span: Option<Span>,
+}
+
+impl std::fmt::Display for SpannedTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{}: [{}] {}", span, self.error.code(), self.error),
+            None => write!(f, "[{}] {}", self.error.code(), self.error),
+        }
+    }
+}
+
+impl std::error::Error for SpannedTypeError {}
+
+/// Code that type checks but is probably a mistake
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeWarning {
+    pub message: String,
+    pub span: Span,
+}
+
+impl std::fmt::Display for TypeWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
 /// Type environment for tracking variable and function types
 #[derive(Debug, Clone)]
 pub struct TypeEnvironment {
     variables: HashMap<String, Type>,
+    /// Variables of this scope declared with `let`, which can't be assigned to
+    constants: HashSet<String>,
     types: HashMap<String, Type>,
     parent: Option<Box<TypeEnvironment>>,
 }
@@ -95,6 +185,7 @@ impl TypeEnvironment {
     pub fn new() -> Self {
         let mut env = TypeEnvironment {
             variables: HashMap::new(),
+            constants: HashSet::new(),
             types: HashMap::new(),
             parent: None,
         };
@@ -108,6 +199,7 @@ impl TypeEnvironment {
     pub fn with_parent(parent: TypeEnvironment) -> Self {
         TypeEnvironment {
             variables: HashMap::new(),
+            constants: HashSet::new(),
             types: HashMap::new(),
             parent: Some(Box::new(parent)),
         }
@@ -117,14 +209,32 @@ impl TypeEnvironment {
         // Built-in types are implicitly defined
         self.types.insert("Int".to_string(), Type::Int);
         self.types.insert("Float".to_string(), Type::Float);
+        self.types.insert("Complex".to_string(), Type::Complex);
+        self.types.insert("Never".to_string(), Type::Never);
         self.types.insert("String".to_string(), Type::String);
         self.types.insert("Bool".to_string(), Type::Bool);
         self.types.insert("Unit".to_string(), Type::Unit);
     }
     
     pub fn define_variable(&mut self, name: String, ty: Type) {
+        self.constants.remove(&name);
         self.variables.insert(name, ty);
     }
+
+    /// Define a variable that can't be assigned to
+    pub fn define_constant(&mut self, name: String, ty: Type) {
+        self.constants.insert(name.clone());
+        self.variables.insert(name, ty);
+    }
+
+    /// Whether the variable `name` refers to was declared with `let`
+    pub fn is_constant(&self, name: &str) -> bool {
+        match &self.parent {
+            _ if self.variables.contains_key(name) => self.constants.contains(name),
+            Some(parent) => parent.is_constant(name),
+            None => false,
+        }
+    }
     
     pub fn define_type(&mut self, name: String, ty: Type) {
         self.types.insert(name, ty);
@@ -140,6 +250,16 @@ impl TypeEnvironment {
         }
     }
     
+    /// Whether `name` is a variable of the outermost scope, not hidden by
+    /// one of the same name in a scope nested inside it
+    pub fn is_global_variable(&self, name: &str) -> bool {
+        match &self.parent {
+            _ if self.variables.contains_key(name) => self.parent.is_none(),
+            Some(parent) => parent.is_global_variable(name),
+            None => false,
+        }
+    }
+    
     pub fn get_type(&self, name: &str) -> Option<Type> {
         if let Some(ty) = self.types.get(name) {
             Some(ty.clone())